@@ -1,4 +1,10 @@
 //! This module provides a bunch of trading bots, no guarantee implied or given.
 
+/// Cross-exchange arbitrage spread between Independent Reserve and Kraken.
+pub mod arb;
+
 /// A spread trading bot.
 pub mod spread;
+
+/// Publish live bot stats to local subscribers.
+pub mod stats;
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    fmt,
+    fs::OpenOptions,
+    io::prelude::*,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    cli::Format,
+    config::Key,
+    market::{kraken, Exchange, Market, OrderBook, Pair},
+    num,
+};
+
+const SAMPLE_PERIOD_SECS: u64 = 10; // Get both orderbooks every X seconds.
+const LOG_ENTRY_PERIOD_SECS: u64 = 3600; // Once an hour
+
+/// Bot output log file.
+const LOG_FILE: &str = "arb-bot.log";
+
+/// Kraken pair, prices are converted to AUD using IR's FX rate.
+const KRAKEN_PAIR: (&str, &str) = ("Xbt", "Usd");
+
+/// Spreads between the two exchanges, in AUD, from the top of each book. A
+/// positive spread is an arbitrage opportunity (before fees).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct CrossSpread {
+    /// IR best bid less Kraken best ask i.e., buy on Kraken, sell on IR.
+    pub ir_bid_kraken_ask: Decimal,
+    /// Kraken best bid less IR best ask i.e., buy on IR, sell on Kraken.
+    pub kraken_bid_ir_ask: Decimal,
+}
+
+impl CrossSpread {
+    /// Calculate the cross spreads from IR's AUD book and Kraken's USD book,
+    /// converting Kraken prices at `usd_aud` AUD per USD. `None` if either
+    /// book is missing a side.
+    pub fn new(ir: &OrderBook, kraken: &OrderBook, usd_aud: Decimal) -> Option<Self> {
+        let kraken_bid = num::convert(kraken.best_bid()?, usd_aud);
+        let kraken_ask = num::convert(kraken.best_ask()?, usd_aud);
+
+        Some(CrossSpread {
+            ir_bid_kraken_ask: ir.best_bid()? - kraken_ask,
+            kraken_bid_ir_ask: kraken_bid - ir.best_ask()?,
+        })
+    }
+}
+
+impl fmt::Display for CrossSpread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "IR bid - Kraken ask: ${} \t Kraken bid - IR ask: ${}",
+            num::to_aud_string(&self.ir_bid_kraken_ask),
+            num::to_aud_string(&self.kraken_bid_ir_ask),
+        )
+    }
+}
+
+/// Min and max of each cross spread seen since the last log entry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct ArbMinMax {
+    ir_bid_kraken_ask: Option<(Decimal, Decimal)>,
+    kraken_bid_ir_ask: Option<(Decimal, Decimal)>,
+}
+
+impl ArbMinMax {
+    /// Include `spread` in the min/max values.
+    pub fn update(&mut self, spread: &CrossSpread) {
+        update_range(&mut self.ir_bid_kraken_ask, spread.ir_bid_kraken_ask);
+        update_range(&mut self.kraken_bid_ir_ask, spread.kraken_bid_ir_ask);
+    }
+}
+
+fn update_range(range: &mut Option<(Decimal, Decimal)>, x: Decimal) {
+    *range = match *range {
+        Some((min, max)) => Some((min.min(x), max.max(x))),
+        None => Some((x, x)),
+    };
+}
+
+impl fmt::Display for ArbMinMax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = |r: Option<(Decimal, Decimal)>| match r {
+            Some((min, max)) => format!(
+                "min: {} max: {}",
+                num::to_aud_string(&min),
+                num::to_aud_string(&max)
+            ),
+            None => "no samples".to_string(),
+        };
+        write!(
+            f,
+            "IR bid - Kraken ask {} \t Kraken bid - IR ask {}",
+            range(self.ir_bid_kraken_ask),
+            range(self.kraken_bid_ir_ask),
+        )
+    }
+}
+
+/// A timestamped sample or log entry.
+#[derive(Clone, Debug, Serialize)]
+struct Timestamped<T> {
+    /// RFC 3339 timestamp.
+    timestamp: String,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl<T> Timestamped<T> {
+    fn now(value: T) -> Self {
+        Timestamped {
+            timestamp: Utc::now().to_rfc3339(),
+            value,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Timestamped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.timestamp, self.value)
+    }
+}
+
+/// Entry point for the arbitrage bot, samples the BTC order books on IR and
+/// Kraken and logs the min/max cross spreads to the log file once an hour.
+/// With `Format::Json` each sample and log entry is also written to stdout as
+/// a JSON object. IR requests go through `proxy`, if set.
+pub async fn run(kraken_read: Key, proxy: Option<String>, format: Format) -> Result<()> {
+    let mut ir = Market::default();
+    if let Some(proxy) = proxy {
+        ir = ir.with_proxy(&proxy)?;
+    }
+    let kraken = kraken::Api::with_key(&kraken_read)
+        .map_err(|e| anyhow!("failed to create kraken api: {}", e))?;
+
+    let log_file = Path::new(LOG_FILE);
+    info!("writing min/max values to {}", log_file.display());
+
+    let mut values = ArbMinMax::default();
+    let mut last_log = Instant::now();
+    loop {
+        match sample(&ir, &kraken).await {
+            Ok(Some(spread)) => {
+                emit(format, &Timestamped::now(spread));
+                values.update(&spread);
+            }
+            Ok(None) => warn!("skipping sample, empty orderbook"),
+            Err(e) => warn!("skipping sample: {}", e),
+        }
+
+        if last_log.elapsed() >= Duration::from_secs(LOG_ENTRY_PERIOD_SECS) {
+            let entry = Timestamped::now(values);
+            emit(format, &entry);
+            write_to_file(log_file, format, &entry)?;
+            values = ArbMinMax::default();
+            last_log = Instant::now();
+        }
+
+        tokio::time::delay_for(Duration::from_secs(SAMPLE_PERIOD_SECS)).await;
+    }
+}
+
+/// Fetch both orderbooks and the FX rate and calculate the cross spread.
+async fn sample(ir: &Market, kraken: &dyn Exchange) -> Result<Option<CrossSpread>> {
+    let ir_book = ir.order_book().await?;
+    let usd_aud = ir.fx_rate("Usd", "Aud").await?;
+
+    let (base, quote) = KRAKEN_PAIR;
+    let kraken_book = kraken.order_book(&Pair::new(base, quote)).await?;
+
+    Ok(CrossSpread::new(&ir_book, &kraken_book, usd_aud))
+}
+
+/// Write `value` to stdout with `Format::Json`, otherwise trace it.
+#[allow(clippy::print_stdout)]
+fn emit<T: Serialize + fmt::Display>(format: Format, value: &T) {
+    match format.render(value) {
+        Ok(s) if format == Format::Json => println!("{}", s),
+        Ok(s) => info!("{}", s),
+        Err(e) => error!("failed to render: {}", e),
+    }
+}
+
+/// Append `entry` to `file`, one line per entry.
+fn write_to_file<T: Serialize + fmt::Display>(
+    file: &Path,
+    format: Format,
+    entry: &T,
+) -> Result<()> {
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .with_context(|| format!("failed to open/create file: {}", file.display()))?;
+
+    writeln!(f, "{}", format.render(entry)?)
+        .with_context(|| format!("failed to write to file: {}", file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn book(bid: u32, ask: u32) -> OrderBook {
+        let one = Decimal::from(1);
+        OrderBook::from_levels(
+            vec![(Decimal::from(bid), one), (Decimal::from(bid - 10), one)],
+            vec![(Decimal::from(ask), one), (Decimal::from(ask + 10), one)],
+        )
+    }
+
+    #[test]
+    fn cross_spread_converts_kraken_prices() {
+        let ir = book(14_100, 14_200); // AUD
+        let kraken = book(9_900, 10_000); // USD
+        let usd_aud = Decimal::new(14, 1);
+
+        let got = CrossSpread::new(&ir, &kraken, usd_aud).unwrap();
+
+        // 14,100 - 10,000 * 1.4
+        assert_that(&got.ir_bid_kraken_ask).is_equal_to(Decimal::from(100));
+        // 9,900 * 1.4 - 14,200
+        assert_that(&got.kraken_bid_ir_ask).is_equal_to(Decimal::from(-340));
+    }
+
+    #[test]
+    fn cross_spread_needs_both_sides() {
+        let ir = OrderBook::from_levels(vec![], vec![(Decimal::from(100), Decimal::from(1))]);
+
+        assert_that(&CrossSpread::new(&ir, &book(90, 100), Decimal::from(1))).is_none();
+    }
+
+    #[test]
+    fn min_max_accumulates_each_spread() {
+        let mut v = ArbMinMax::default();
+        for (a, b) in &[(10, -5), (-20, 3), (5, 0)] {
+            v.update(&CrossSpread {
+                ir_bid_kraken_ask: Decimal::from(*a),
+                kraken_bid_ir_ask: Decimal::from(*b),
+            });
+        }
+
+        assert_that(&v.ir_bid_kraken_ask)
+            .is_equal_to(Some((Decimal::from(-20), Decimal::from(10))));
+        assert_that(&v.kraken_bid_ir_ask).is_equal_to(Some((Decimal::from(-5), Decimal::from(3))));
+    }
+
+    fn entry() -> Timestamped<ArbMinMax> {
+        let mut values = ArbMinMax::default();
+        values.update(&CrossSpread {
+            ir_bid_kraken_ask: Decimal::from(10),
+            kraken_bid_ir_ask: Decimal::from(-5),
+        });
+        Timestamped {
+            timestamp: "2020-06-01T12:00:00+00:00".to_string(),
+            value: values,
+        }
+    }
+
+    #[test]
+    fn log_entries_are_appended_in_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("arb.log");
+
+        write_to_file(&path, Format::Json, &entry()).unwrap();
+        write_to_file(&path, Format::Human, &entry()).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_that(&lines.len()).is_equal_to(2);
+
+        let json: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_that(&json["timestamp"]).is_equal_to(serde_json::json!("2020-06-01T12:00:00+00:00"));
+        assert_that(&json["ir_bid_kraken_ask"]).is_equal_to(serde_json::json!(["10", "10"]));
+        assert_that(&json["kraken_bid_ir_ask"]).is_equal_to(serde_json::json!(["-5", "-5"]));
+
+        assert_that(&lines[1].starts_with("2020-06-01T12:00:00+00:00 IR bid - Kraken ask min:"))
+            .is_true();
+    }
+}
@@ -1,58 +1,447 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::prelude::*;
+use futures::{future::LocalBoxFuture, FutureExt, Stream, StreamExt};
+use num_traits::identities::Zero;
 use rust_decimal::Decimal;
-use std::{fmt, fs::OpenOptions, io::prelude::*, str::FromStr, time::Duration};
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{prelude::*, BufReader},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
 
-use crate::{config::Key, market::Market, num};
+use crate::{
+    bot::stats::StatsServer,
+    cli::Format,
+    market::{order_book_stream, Market, OrderBook},
+    num::{self, Percent, Price},
+};
 
-const DEBUG: bool = true;
+/// Default bot output log file.
+const DEFAULT_LOG_FILE: &str = "spread-bot.log";
 
-/// Bot output log file.
-const LOG_FILE: &str = "spread-bot.log";
+const DEFAULT_SAMPLE_PERIOD_SECS: u64 = 5; // Get orderbook every X seconds.
+const FAST_SAMPLE_PERIOD_SECS: u64 = 1; // Lower bound, keeps us under API rate limits.
+const DEFAULT_LOG_ENTRY_PERIOD_SECS: u64 = 3600; // Once an hour
 
-const SAMPLE_PERIOD_SECS: u64 = 5; // Get orderbook every X seconds.
-const LOG_ENTRY_PERIOD_SECS: u64 = 3600; // Once an hour
+/// Default spread percent histogram bucket edges.
+const DEFAULT_BUCKET_EDGES: [&str; 3] = ["0.002", "0.003", "0.004"];
 
-/// Entry point for the spread-bot
-pub async fn run(read: Key) -> Result<()> {
-    let mut values = MinMax::default();
-    let m = Market::default().with_read_only(read);
+/// Number of order book levels used to calculate the imbalance.
+const IMBALANCE_DEPTH: usize = 10;
+/// Imbalance magnitude above which we switch to the fast sample period.
+const IMBALANCE_THRESHOLD: &str = "0.5";
 
-    info!("writing min/max values to {}", LOG_FILE);
-    write_to_file(LOG_FILE, &values).await?;
+/// How often the bot samples the order book.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// Sample every `sample_period_secs`, from the config file or
+    /// `--sample-period`.
+    Fixed,
+    /// Sample every `FAST_SAMPLE_PERIOD_SECS` while the order book imbalance
+    /// exceeds `IMBALANCE_THRESHOLD`, otherwise as for `Fixed`.
+    Imbalance,
+    /// Keep the order book up to date from the exchange's WebSocket feed,
+    /// sampling the latest book every `FAST_SAMPLE_PERIOD_SECS`.
+    Stream,
+}
+
+impl FromStr for Sampling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fixed" => Ok(Sampling::Fixed),
+            "imbalance" => Ok(Sampling::Imbalance),
+            "stream" => Ok(Sampling::Stream),
+            _ => bail!(
+                "unknown sampling mode: {} (expected fixed, imbalance, or stream)",
+                s
+            ),
+        }
+    }
+}
+
+/// Spread bot settings, read from the `[spread_bot]` section of the config
+/// file. Missing values take their defaults.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SpreadBotConfig {
+    /// Seconds between order book samples, values below
+    /// `FAST_SAMPLE_PERIOD_SECS` are clamped to it.
+    pub sample_period_secs: u64,
+    /// Seconds between log file entries.
+    pub log_entry_period_secs: u64,
+    /// File the min/max values are logged to.
+    pub log_file: PathBuf,
+    /// Format of the log file entries.
+    pub log_format: LogFormat,
+    /// Volume (base currency) of the buy and sell orders used to calculate the
+    /// spread.
+    pub fill_volume: Decimal,
+    /// Edges of the spread percent histogram buckets, as fractions in
+    /// ascending order e.g., ["0.002", "0.003"] gives buckets <0.2%,
+    /// 0.2-0.3%, and >0.3%.
+    pub bucket_edges: Vec<Decimal>,
+    /// Log each sample.
+    pub debug: bool,
+}
+
+impl SpreadBotConfig {
+    /// Override the configured values with those given on the command line.
+    pub fn with_overrides(
+        self,
+        sample_period_secs: Option<u64>,
+        log_entry_period_secs: Option<u64>,
+        fill_volume: Option<Decimal>,
+    ) -> Self {
+        SpreadBotConfig {
+            sample_period_secs: sample_period_secs.unwrap_or(self.sample_period_secs),
+            log_entry_period_secs: log_entry_period_secs.unwrap_or(self.log_entry_period_secs),
+            fill_volume: fill_volume.unwrap_or(self.fill_volume),
+            ..self
+        }
+    }
+}
+
+impl Default for SpreadBotConfig {
+    fn default() -> Self {
+        SpreadBotConfig {
+            sample_period_secs: DEFAULT_SAMPLE_PERIOD_SECS,
+            log_entry_period_secs: DEFAULT_LOG_ENTRY_PERIOD_SECS,
+            log_file: PathBuf::from(DEFAULT_LOG_FILE),
+            log_format: LogFormat::Prose,
+            fill_volume: Decimal::from(1),
+            bucket_edges: DEFAULT_BUCKET_EDGES
+                .iter()
+                .map(|s| Decimal::from_str(s).unwrap())
+                .collect(),
+            debug: true,
+        }
+    }
+}
+
+/// Format of the spread bot log file.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable line with the spread bucket counts.
+    Prose,
+    /// Comma separated values, with a header line when the file is created.
+    /// We refuse to append to a file whose header has different buckets.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Entry point for the spread-bot, samples the order book of `m`'s pair. If
+/// `bps` is set spread percentages are displayed in basis points. With
+/// `Format::Json` each sample is written to stdout as a JSON object. If
+/// `stats_addr` is set the current min/max and mean values are published to
+/// subscribers on that address after each sample.
+pub async fn run(
+    m: Market,
+    config: SpreadBotConfig,
+    bps: bool,
+    sampling: Sampling,
+    format: Format,
+    stats_addr: Option<SocketAddr>,
+) -> Result<()> {
+    let mut values = MinMax::new(&config.bucket_edges);
+    if config.log_format == LogFormat::Csv {
+        check_csv_header(&config.log_file, &csv_header(&values))?;
+    }
+
+    let stats = match stats_addr {
+        Some(addr) => Some(StatsServer::bind(addr).await?),
+        None => None,
+    };
+
+    info!("writing min/max values to {}", config.log_file.display());
 
-    let mut loop_counter = 0;
+    let record = |values: &mut MinMax, orderbook: Option<OrderBook>| {
+        let imbalance = match orderbook {
+            Some(orderbook) => update_values(&config, &orderbook, values, bps, format),
+            None => None,
+        };
+
+        if let Some(stats) = &stats {
+            if let Err(e) = stats.publish(values) {
+                warn!("failed to publish stats: {}", e);
+            }
+        }
+
+        sample_period(&config, sampling, imbalance)
+    };
+    let flush = |values: &mut MinMax| {
+        write_to_file(&config.log_file, config.log_format, values, bps)?;
+        *values = MinMax::new(&config.bucket_edges);
+        Ok(())
+    };
+
+    let flush_period = Duration::from_secs(config.log_entry_period_secs);
+    let schedule = Schedule::new(Instant::now(), flush_period);
+    match sampling {
+        Sampling::Stream => {
+            let mut books = Latest(Box::pin(order_book_stream(m.pair().clone())));
+            sample_loop(schedule, &mut books, &mut values, record, flush).await
+        }
+        _ => sample_loop(schedule, &mut Poll(&m), &mut values, record, flush).await,
+    }
+}
+
+/// Source of order books for the spread bot.
+trait Sampler {
+    /// The next order book, `None` to skip this sample.
+    fn next_book(&mut self) -> LocalBoxFuture<'_, Result<Option<OrderBook>>>;
+}
+
+/// Fetches the order book from the REST API for each sample.
+struct Poll<'a>(&'a Market);
+
+impl Sampler for Poll<'_> {
+    fn next_book(&mut self) -> LocalBoxFuture<'_, Result<Option<OrderBook>>> {
+        async move { Ok(fetch_order_book(self.0).await) }.boxed_local()
+    }
+}
+
+/// Takes the latest order book from a stream for each sample.
+struct Latest<S>(S);
+
+impl<S> Sampler for Latest<S>
+where
+    S: Stream<Item = Result<OrderBook>> + Unpin,
+{
+    fn next_book(&mut self) -> LocalBoxFuture<'_, Result<Option<OrderBook>>> {
+        latest_order_book(&mut self.0).boxed_local()
+    }
+}
+
+/// Take samples from `sampler` and pass them to `record`, which returns the
+/// time to wait before the next sample, and `flush` the values on `schedule`.
+/// A flush that comes due while waiting for a sample is made without waiting
+/// for the sample to finish. Runs until `sampler` or `flush` errors.
+async fn sample_loop<S, R, F>(
+    mut schedule: Schedule,
+    sampler: &mut S,
+    values: &mut MinMax,
+    mut record: R,
+    mut flush: F,
+) -> Result<()>
+where
+    S: Sampler,
+    R: FnMut(&mut MinMax, Option<OrderBook>) -> Duration,
+    F: FnMut(&mut MinMax) -> Result<()>,
+{
     loop {
-        update_values(&m, &mut values).await;
+        if schedule.sample_due(Instant::now()) {
+            let mut next = sampler.next_book();
+            let orderbook = loop {
+                let flush_in = schedule.until_flush(Instant::now());
+                tokio::select! {
+                    orderbook = &mut next => break orderbook?,
+                    _ = tokio::time::delay_for(flush_in) => {
+                        if schedule.flush_due(Instant::now()) {
+                            flush(values)?;
+                        }
+                    }
+                }
+            };
 
-        let time_running = loop_counter * SAMPLE_PERIOD_SECS;
+            let period = record(values, orderbook);
+            schedule.sampled(Instant::now(), period);
+        }
 
-        if time_running > LOG_ENTRY_PERIOD_SECS {
-            write_to_file(LOG_FILE, &values).await?;
+        if schedule.flush_due(Instant::now()) {
+            flush(values)?;
+        }
 
-            values = MinMax::default();
-            loop_counter = 0;
-        } else {
-            loop_counter += 1;
+        tokio::time::delay_for(schedule.until_next(Instant::now())).await;
+    }
+}
+
+/// Wall clock deadlines for taking samples and for flushing the accumulated
+/// values to the log file. The deadlines are independent so a stalled sample
+/// does not delay the flush.
+#[derive(Clone, Copy, Debug)]
+struct Schedule {
+    next_sample: Instant,
+    next_flush: Instant,
+    flush_period: Duration,
+}
+
+impl Schedule {
+    /// Sample immediately, first flush after `flush_period`.
+    fn new(now: Instant, flush_period: Duration) -> Self {
+        Schedule {
+            next_sample: now,
+            next_flush: now + flush_period,
+            flush_period,
         }
+    }
+
+    fn sample_due(&self, now: Instant) -> bool {
+        now >= self.next_sample
+    }
+
+    /// Record that we took a sample at `now`, next one due after `period`.
+    fn sampled(&mut self, now: Instant, period: Duration) {
+        self.next_sample = now + period;
+    }
+
+    /// True if a flush is due, advances the flush deadline. Flush deadlines
+    /// stay aligned to the start time, any missed while stalled are skipped.
+    fn flush_due(&mut self, now: Instant) -> bool {
+        if now < self.next_flush {
+            return false;
+        }
+        while self.next_flush <= now {
+            self.next_flush += self.flush_period;
+        }
+        true
+    }
+
+    /// Time to wait until the next flush.
+    fn until_flush(&self, now: Instant) -> Duration {
+        self.next_flush.saturating_duration_since(now)
+    }
 
-        tokio::time::delay_for(Duration::from_secs(SAMPLE_PERIOD_SECS)).await;
+    /// Time to wait until the next deadline.
+    fn until_next(&self, now: Instant) -> Duration {
+        let next = self.next_sample.min(self.next_flush);
+        next.saturating_duration_since(now)
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Time to wait before taking the next sample, never less than
+/// `FAST_SAMPLE_PERIOD_SECS`.
+fn sample_period(
+    config: &SpreadBotConfig,
+    sampling: Sampling,
+    imbalance: Option<Decimal>,
+) -> Duration {
+    let threshold = Decimal::from_str(IMBALANCE_THRESHOLD).unwrap();
+
+    match (sampling, imbalance) {
+        (Sampling::Stream, _) => Duration::from_secs(FAST_SAMPLE_PERIOD_SECS),
+        (Sampling::Imbalance, Some(x)) if x.abs() > threshold => {
+            Duration::from_secs(FAST_SAMPLE_PERIOD_SECS)
+        }
+        _ => Duration::from_secs(config.sample_period_secs.max(FAST_SAMPLE_PERIOD_SECS)),
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct MinMax {
     min_spread: Decimal,
     max_spread: Decimal,
     min_percent: Decimal,
     max_percent: Decimal,
 
-    // Percentage counters, identifier refers to 0.x %
-    less_than_two: u32,
-    two_to_three: u32,
-    three_to_four: u32,
-    greater_than_four: u32,
+    /// Number of spreads recorded.
+    samples: u32,
+    /// Mean spread and spread percent, `None` until we record a spread.
+    mean_spread: Option<Decimal>,
+    mean_percent: Option<Decimal>,
+    #[serde(skip)]
+    sum_spread: Decimal,
+    #[serde(skip)]
+    sum_percent: Decimal,
+
+    /// Spread percent histogram, (lower edge, count) in ascending order. The
+    /// first bucket's lower edge is zero.
+    buckets: Vec<(Decimal, u32)>,
+}
+
+impl MinMax {
+    /// New values with a histogram bucket between each of `edges`.
+    pub fn new(edges: &[Decimal]) -> Self {
+        let mut edges = edges.to_vec();
+        edges.sort();
+        edges.dedup();
+
+        let mut buckets = vec![(Decimal::zero(), 0)];
+        buckets.extend(edges.into_iter().filter(|e| !e.is_zero()).map(|e| (e, 0)));
+
+        Self {
+            min_spread: Decimal::max_value(),
+            max_spread: Decimal::min_value(),
+
+            min_percent: Decimal::max_value(),
+            max_percent: Decimal::min_value(),
+
+            samples: 0,
+            mean_spread: None,
+            mean_percent: None,
+            sum_spread: Decimal::zero(),
+            sum_percent: Decimal::zero(),
+
+            buckets,
+        }
+    }
+
+    /// Record a sampled `spread` and spread `percent`.
+    pub(crate) fn record(&mut self, spread: Decimal, percent: Decimal) {
+        if spread < self.min_spread {
+            self.min_spread = spread;
+        }
+        if spread > self.max_spread {
+            self.max_spread = spread;
+        }
+
+        if percent < self.min_percent {
+            self.min_percent = percent;
+        }
+        if percent > self.max_percent {
+            self.max_percent = percent;
+        }
+
+        self.samples += 1;
+        self.sum_spread += spread;
+        self.sum_percent += percent;
+        let n = Decimal::from(self.samples);
+        self.mean_spread = Some(self.sum_spread / n);
+        self.mean_percent = Some(self.sum_percent / n);
+
+        self.record_percent(percent);
+    }
+
+    /// Count `percent` in its histogram bucket.
+    fn record_percent(&mut self, percent: Decimal) {
+        let bucket = self
+            .buckets
+            .iter()
+            .rposition(|(lower, _)| percent >= *lower)
+            .unwrap_or(0);
+        self.buckets[bucket].1 += 1;
+    }
+
+    /// Bucket labels e.g., ["<20", "20-30", ">30"], scaled by `unit` e.g.,
+    /// 100 for percent.
+    fn bucket_labels(&self, unit: Decimal) -> Vec<String> {
+        let edges: Vec<Decimal> = self
+            .buckets
+            .iter()
+            .skip(1)
+            .map(|(lower, _)| (lower * unit).normalize())
+            .collect();
+
+        if edges.is_empty() {
+            return vec!["all".to_string()];
+        }
+
+        let mut labels = vec![format!("<{}", edges[0])];
+        for pair in edges.windows(2) {
+            labels.push(format!("{}-{}", pair[0], pair[1]));
+        }
+        labels.push(format!(">{}", edges[edges.len() - 1]));
+        labels
+    }
 }
 
 impl fmt::Display for MinMax {
@@ -65,82 +454,133 @@ impl fmt::Display for MinMax {
     }
 }
 
-impl Default for MinMax {
-    fn default() -> Self {
-        Self {
-            min_spread: Decimal::max_value(),
-            max_spread: Decimal::min_value(),
+/// A single spread sample, emitted when running with `Format::Json`.
+#[derive(Clone, Debug, Serialize)]
+struct Sample {
+    /// RFC 3339 timestamp.
+    timestamp: String,
+    spread: Decimal,
+    percent: Decimal,
+    imbalance: Option<Decimal>,
+}
 
-            min_percent: Decimal::max_value(),
-            max_percent: Decimal::min_value(),
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spread: {} percent: {}", self.spread, self.percent)
+    }
+}
 
-            less_than_two: 0,
-            two_to_three: 0,
-            three_to_four: 0,
-            greater_than_four: 0,
+/// Get the orderbook from the exchange, `None` if we should skip this sample.
+/// Each sample must be a new book so we bypass the market's cache. Transient
+/// errors are already retried by the market's API client.
+async fn fetch_order_book(m: &Market) -> Option<OrderBook> {
+    match m.order_book_fresh().await {
+        Ok(orderbook) => Some(orderbook),
+        Err(e) => {
+            warn!("skipping sample, failed to get orderbook: {}", e);
+            None
+        }
+    }
+}
+
+/// Wait for the next orderbook from `books` then skip to the latest one
+/// already received, `None` if we should skip this sample.
+async fn latest_order_book<S>(books: &mut S) -> Result<Option<OrderBook>>
+where
+    S: Stream<Item = Result<OrderBook>> + Unpin,
+{
+    let mut latest = books.next().await;
+    while let Some(Some(next)) = books.next().now_or_never() {
+        latest = Some(next);
+    }
+
+    match latest {
+        Some(Ok(orderbook)) => Ok(Some(orderbook)),
+        Some(Err(e)) => {
+            warn!("skipping sample, orderbook stream error: {}", e);
+            Ok(None)
         }
+        None => bail!("orderbook stream ended"),
     }
 }
 
-/// Get orderbook then calculate and store spread/percent values.
-async fn update_values(m: &Market, v: &mut MinMax) {
-    let orderbook = m.order_book().await.expect("failed to get orderbook");
+/// Calculate and store spread/percent values for `orderbook`. Returns the
+/// order book imbalance.
+fn update_values(
+    config: &SpreadBotConfig,
+    orderbook: &OrderBook,
+    v: &mut MinMax,
+    bps: bool,
+    format: Format,
+) -> Option<Decimal> {
+    let imbalance = orderbook.imbalance(IMBALANCE_DEPTH);
 
-    let (bid, ask) = match orderbook.spread_to_fill(Decimal::from(1)) {
+    let (bid, ask) = match orderbook.spread_to_fill(config.fill_volume) {
         Ok(s) => s,
         Err(e) => {
             info!("failed to get spread: {}", e);
-            return;
+            return imbalance;
         }
     };
 
     let (spread, percent) = num::spread_percent(&bid, &ask);
+    v.record(spread, percent);
 
-    if spread < v.min_spread {
-        v.min_spread = spread;
-    }
-    if spread > v.max_spread {
-        v.max_spread = spread;
-    }
-
-    if percent < v.min_percent {
-        v.min_percent = percent;
-    }
-    if percent > v.max_percent {
-        v.max_percent = percent;
+    if format == Format::Json {
+        let sample = Sample {
+            timestamp: Utc::now().to_rfc3339(),
+            spread,
+            percent,
+            imbalance,
+        };
+        emit(format, &sample);
+    } else if config.debug {
+        let log_entry = log_entry(v, bps);
+        let percent = if bps {
+            Percent(percent).to_bps_string()
+        } else {
+            format!("%{}", Percent(percent))
+        };
+        info!("\t ${} \t {} \t {}", Price(spread), percent, log_entry,);
     }
 
-    if percent < Decimal::from_str("0.002").unwrap() {
-        v.less_than_two += 1;
-    } else if percent < Decimal::from_str("0.003").unwrap() {
-        v.two_to_three += 1;
-    } else if percent < Decimal::from_str("0.004").unwrap() {
-        v.three_to_four += 1;
-    } else {
-        v.greater_than_four += 1;
-    }
+    imbalance
+}
 
-    if DEBUG {
-        let log_entry = log_entry(v);
-        info!(
-            "\t ${} \t %{} \t {}",
-            num::to_aud_string(&spread),
-            num::to_percent_string(&percent),
-            log_entry,
-        );
+/// Write `sample` to stdout.
+#[allow(clippy::print_stdout)]
+fn emit(format: Format, sample: &Sample) {
+    match format.render(sample) {
+        Ok(s) => println!("{}", s),
+        Err(e) => error!("failed to render sample: {}", e),
     }
 }
 
 /// Write values to file.
-async fn write_to_file(file: &str, v: &MinMax) -> Result<()> {
+fn write_to_file(file: &Path, format: LogFormat, v: &MinMax, bps: bool) -> Result<()> {
+    if format == LogFormat::Csv {
+        check_csv_header(file, &csv_header(v))?;
+    }
+
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .append(true)
         .open(file)
-        .with_context(|| format!("failed to open/create file: {}", file))?;
+        .with_context(|| format!("failed to open/create file: {}", file.display()))?;
+
+    let is_empty = file.metadata().map(|m| m.len() == 0).unwrap_or(false);
+    if format == LogFormat::Csv && is_empty {
+        if let Err(e) = writeln!(file, "{}", csv_header(v)) {
+            error!("Couldn't write to file: {}", e);
+        }
+    }
 
-    let s = log_entry(v);
+    let s = match format {
+        LogFormat::Prose => log_entry(v, bps),
+        LogFormat::Csv => csv_record(&Utc::now(), v),
+        LogFormat::Json => json_record(&Utc::now(), v)?,
+    };
     if let Err(e) = writeln!(file, "{}", s) {
         error!("Couldn't write to file: {}", e);
     }
@@ -148,15 +588,447 @@ async fn write_to_file(file: &str, v: &MinMax) -> Result<()> {
     Ok(())
 }
 
-fn log_entry(v: &MinMax) -> String {
+/// Header line for `LogFormat::Csv`, one column per histogram bucket named
+/// for the bucket's lower edge.
+fn csv_header(v: &MinMax) -> String {
+    let mut header = "timestamp,min_spread,max_spread,min_percent,max_percent".to_string();
+    for (lower, _) in v.buckets.iter() {
+        header.push_str(&format!(",bucket_{}", lower));
+    }
+    header
+}
+
+/// Errors if `file` already starts with a header other than `header`, the
+/// records would not line up with its columns.
+fn check_csv_header(file: &Path, header: &str) -> Result<()> {
+    let f = match File::open(file) {
+        Ok(f) => f,
+        Err(_) => return Ok(()),
+    };
+    let first = match BufReader::new(f).lines().next() {
+        Some(line) => line.with_context(|| format!("failed to read file: {}", file.display()))?,
+        None => return Ok(()),
+    };
+    if first != header {
+        bail!(
+            "{} has CSV header '{}', expected '{}' for the configured bucket edges",
+            file.display(),
+            first,
+            header
+        );
+    }
+    Ok(())
+}
+
+fn csv_record(timestamp: &DateTime<Utc>, v: &MinMax) -> String {
+    let mut record = format!(
+        "{},{},{},{},{}",
+        timestamp.to_rfc3339(),
+        v.min_spread,
+        v.max_spread,
+        v.min_percent,
+        v.max_percent,
+    );
+    for (_, count) in v.buckets.iter() {
+        record.push_str(&format!(",{}", count));
+    }
+    record
+}
+
+fn json_record(timestamp: &DateTime<Utc>, v: &MinMax) -> Result<String> {
+    #[derive(Serialize)]
+    struct Record<'a> {
+        timestamp: String,
+        #[serde(flatten)]
+        values: &'a MinMax,
+    }
+
+    let record = Record {
+        timestamp: timestamp.to_rfc3339(),
+        values: v,
+    };
+    Ok(serde_json::to_string(&record)?)
+}
+
+fn log_entry(v: &MinMax, bps: bool) -> String {
     let local: DateTime<Local> = Local::now();
+    format!(
+        "{} spread counts {}",
+        local.format("%Y-%m-%d %H:%M:%S"),
+        bucket_counts(v, bps),
+    )
+}
 
+// Histogram bucket labels and counts e.g., "bps <20  20-30  >30 :\t1\t2\t3".
+fn bucket_counts(v: &MinMax, bps: bool) -> String {
+    let (unit, scale) = if bps {
+        ("bps", Decimal::from(10_000))
+    } else {
+        ("%", Decimal::from(100))
+    };
+
+    let counts: Vec<String> = v.buckets.iter().map(|(_, c)| c.to_string()).collect();
     format!(
-        "{} spread counts % <2  2-3  3-4  >4 :\t{}\t{}\t{}\t{}",
-        local.format("%Y-%m-%d %H:%M:%S").to_string(),
-        v.less_than_two,
-        v.two_to_three,
-        v.three_to_four,
-        v.greater_than_four,
+        "{} {} :\t{}",
+        unit,
+        v.bucket_labels(scale).join("  "),
+        counts.join("\t")
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{HttpResponse, MockTransport};
+    use reqwest::{
+        header::{HeaderValue, RETRY_AFTER},
+        StatusCode,
+    };
+    use spectral::prelude::*;
+    use std::sync::Arc;
+
+    // Unavailable, retry immediately.
+    fn unavailable() -> HttpResponse {
+        let mut res = HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, "");
+        res.headers
+            .insert(RETRY_AFTER, HeaderValue::from_static("0"));
+        res
+    }
+
+    fn mock_market(mock: MockTransport) -> (Market, Arc<MockTransport>) {
+        let mock = Arc::new(mock);
+        (Market::default().with_transport(mock.clone()), mock)
+    }
+
+    #[tokio::test]
+    async fn fetch_order_book_survives_transient_errors() {
+        let (m, mock) = mock_market(
+            MockTransport::new()
+                .with_response("GetOrderBook", unavailable())
+                .with_response("GetOrderBook", unavailable())
+                .with_json(
+                    "GetOrderBook",
+                    include_str!("../../testdata/GetOrderBook.json"),
+                ),
+        );
+
+        let book = fetch_order_book(&m).await;
+
+        assert_that(&book.is_some()).is_true();
+        assert_that(&mock.requests().len()).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn fetch_order_book_skips_sample_when_unavailable() {
+        let (m, mock) =
+            mock_market(MockTransport::new().with_response("GetOrderBook", unavailable()));
+
+        let book = fetch_order_book(&m).await;
+
+        assert_that(&book.is_none()).is_true();
+        // Only the API client's three attempts, no extra retries on top.
+        assert_that(&mock.requests().len()).is_equal_to(3);
+    }
+
+    #[test]
+    fn high_imbalance_shortens_sample_period() {
+        let calm = Some(Decimal::from_str("0.1").unwrap());
+        let volatile = Some(Decimal::from_str("-0.8").unwrap());
+
+        let config = SpreadBotConfig::default();
+        let normal = Duration::from_secs(DEFAULT_SAMPLE_PERIOD_SECS);
+        let fast = Duration::from_secs(FAST_SAMPLE_PERIOD_SECS);
+
+        let period = |sampling, imbalance| sample_period(&config, sampling, imbalance);
+        assert_that(&period(Sampling::Imbalance, calm)).is_equal_to(normal);
+        assert_that(&period(Sampling::Imbalance, volatile)).is_equal_to(fast);
+        assert_that(&period(Sampling::Imbalance, None)).is_equal_to(normal);
+        assert_that(&period(Sampling::Fixed, volatile)).is_equal_to(normal);
+        assert_that(&period(Sampling::Stream, calm)).is_equal_to(fast);
+    }
+
+    #[test]
+    fn zero_sample_period_is_clamped() {
+        let config = SpreadBotConfig::default().with_overrides(Some(0), None, None);
+        let fast = Duration::from_secs(FAST_SAMPLE_PERIOD_SECS);
+
+        assert_that(&sample_period(&config, Sampling::Fixed, None)).is_equal_to(fast);
+        assert_that(&sample_period(&config, Sampling::Imbalance, None)).is_equal_to(fast);
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let config =
+            SpreadBotConfig::default().with_overrides(Some(1), None, Some(Decimal::new(5, 1)));
+
+        assert_that(&config.sample_period_secs).is_equal_to(1);
+        assert_that(&config.log_entry_period_secs).is_equal_to(DEFAULT_LOG_ENTRY_PERIOD_SECS);
+        assert_that(&config.fill_volume).is_equal_to(Decimal::new(5, 1));
+    }
+
+    #[test]
+    fn spread_depends_on_fill_volume() {
+        let one = Decimal::from(1);
+        let book = OrderBook::from_levels(
+            vec![(Decimal::from(99), one), (Decimal::from(97), one)],
+            vec![(Decimal::from(101), one), (Decimal::from(103), one)],
+        );
+
+        let spread = |volume| {
+            let config = SpreadBotConfig::default().with_overrides(None, None, Some(volume));
+            let mut v = MinMax::new(&config.bucket_edges);
+            update_values(&config, &book, &mut v, false, Format::Human);
+            v.max_spread
+        };
+
+        assert_that(&spread(one)).is_equal_to(Decimal::from(2));
+        assert_that(&spread(Decimal::from(2))).is_equal_to(Decimal::from(4));
+    }
+
+    /// Sampler whose samples take `delay` to arrive.
+    struct Slow {
+        delay: Duration,
+    }
+
+    impl Sampler for Slow {
+        fn next_book(&mut self) -> LocalBoxFuture<'_, Result<Option<OrderBook>>> {
+            let delay = self.delay;
+            async move {
+                tokio::time::delay_for(delay).await;
+                Ok(None)
+            }
+            .boxed_local()
+        }
+    }
+
+    #[tokio::test]
+    async fn stalled_sample_does_not_delay_flush_in_loop() {
+        let start = Instant::now();
+        let schedule = Schedule::new(start, Duration::from_millis(50));
+        let mut sampler = Slow {
+            delay: Duration::from_millis(500),
+        };
+        let mut values = MinMax::new(&SpreadBotConfig::default().bucket_edges);
+        let mut samples = 0;
+        let mut flushes = vec![];
+
+        let res = sample_loop(
+            schedule,
+            &mut sampler,
+            &mut values,
+            |_, _| {
+                samples += 1;
+                Duration::from_secs(60)
+            },
+            |_| {
+                flushes.push(start.elapsed());
+                if flushes.len() == 3 {
+                    bail!("stop");
+                }
+                Ok(())
+            },
+        )
+        .await;
+
+        assert_that(&res.is_err()).is_true();
+        // All three flushes happened while the first sample was still pending.
+        assert_that(&samples).is_equal_to(0);
+        assert_that(&flushes[2]).is_less_than(Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn loop_records_samples_then_flushes() {
+        let start = Instant::now();
+        let schedule = Schedule::new(start, Duration::from_millis(50));
+        let mut sampler = Slow {
+            delay: Duration::from_millis(1),
+        };
+        let mut values = MinMax::new(&SpreadBotConfig::default().bucket_edges);
+        let mut samples = 0;
+
+        let res = sample_loop(
+            schedule,
+            &mut sampler,
+            &mut values,
+            |_, _| {
+                samples += 1;
+                Duration::from_millis(10)
+            },
+            |_| bail!("stop"),
+        )
+        .await;
+
+        assert_that(&res.is_err()).is_true();
+        assert_that(&samples).is_greater_than(1);
+    }
+
+    /// Clock we advance by hand.
+    struct MockClock(Instant);
+
+    impl MockClock {
+        fn now(&self) -> Instant {
+            self.0
+        }
+
+        fn advance(&mut self, secs: u64) {
+            self.0 += Duration::from_secs(secs);
+        }
+    }
+
+    #[test]
+    fn schedule_samples_then_flushes_on_independent_deadlines() {
+        let mut clock = MockClock(Instant::now());
+        let mut schedule = Schedule::new(clock.now(), Duration::from_secs(60));
+
+        // Sample first, no flush before any samples are taken.
+        assert_that(&schedule.sample_due(clock.now())).is_true();
+        assert_that(&schedule.flush_due(clock.now())).is_false();
+
+        let mut samples = 0;
+        let mut flushes = 0;
+        for _ in 0..130 {
+            if schedule.sample_due(clock.now()) {
+                samples += 1;
+                schedule.sampled(clock.now(), Duration::from_secs(5));
+            }
+            if schedule.flush_due(clock.now()) {
+                flushes += 1;
+            }
+            clock.advance(1);
+        }
+
+        assert_that(&samples).is_equal_to(26);
+        assert_that(&flushes).is_equal_to(2);
+        assert_that(&schedule.until_next(clock.now())).is_equal_to(Duration::from_secs(0));
+    }
+
+    #[test]
+    fn stalled_sample_does_not_shift_flush_deadline() {
+        let mut clock = MockClock(Instant::now());
+        let mut schedule = Schedule::new(clock.now(), Duration::from_secs(60));
+
+        // A sample that takes longer than two flush periods.
+        clock.advance(150);
+        schedule.sampled(clock.now(), Duration::from_secs(5));
+
+        assert_that(&schedule.flush_due(clock.now())).is_true();
+        assert_that(&schedule.flush_due(clock.now())).is_false();
+        // Next flush is at 180s, aligned to the start time.
+        assert_that(&schedule.until_next(clock.now())).is_equal_to(Duration::from_secs(5));
+        clock.advance(29);
+        assert_that(&schedule.flush_due(clock.now())).is_false();
+        clock.advance(1);
+        assert_that(&schedule.flush_due(clock.now())).is_true();
+    }
+
+    fn timestamp() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2020-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn sample_values() -> MinMax {
+        let mut v = MinMax::new(&SpreadBotConfig::default().bucket_edges);
+        v.min_spread = Decimal::from(10);
+        v.max_spread = Decimal::from(25);
+        v.min_percent = Decimal::from_str("0.0015").unwrap();
+        v.max_percent = Decimal::from_str("0.0041").unwrap();
+        for (count, bucket) in [4, 3, 2, 1].iter().zip(v.buckets.iter_mut()) {
+            bucket.1 = *count;
+        }
+        v
+    }
+
+    #[test]
+    fn csv_header_and_record() {
+        let timestamp = timestamp();
+
+        assert_that(&csv_header(&sample_values())).is_equal_to(
+            "timestamp,min_spread,max_spread,min_percent,max_percent,\
+             bucket_0,bucket_0.002,bucket_0.003,bucket_0.004"
+                .to_string(),
+        );
+        assert_that(&csv_record(&timestamp, &sample_values()))
+            .is_equal_to("2020-06-01T12:00:00+00:00,10,25,0.0015,0.0041,4,3,2,1".to_string());
+    }
+
+    #[test]
+    fn csv_refuses_to_append_with_different_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spread.csv");
+
+        let v = sample_values();
+        write_to_file(&path, LogFormat::Csv, &v, false).unwrap();
+        write_to_file(&path, LogFormat::Csv, &v, false).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_that(&contents.lines().count()).is_equal_to(3);
+
+        let edges = vec![Decimal::zero(), Decimal::from_str("0.01").unwrap()];
+        let other = MinMax::new(&edges);
+        let err = write_to_file(&path, LogFormat::Csv, &other, false).unwrap_err();
+        assert_that(&err.to_string().contains("bucket edges")).is_true();
+        assert_that(&std::fs::read_to_string(&path).unwrap()).is_equal_to(contents);
+    }
+
+    #[test]
+    fn json_record_flattens_values() {
+        let timestamp = timestamp();
+
+        let record = json_record(&timestamp, &sample_values()).unwrap();
+        let got: serde_json::Value = serde_json::from_str(&record).unwrap();
+
+        assert_that(&got["timestamp"]).is_equal_to(serde_json::json!("2020-06-01T12:00:00+00:00"));
+        assert_that(&got["min_spread"]).is_equal_to(serde_json::json!("10"));
+        assert_that(&got["buckets"][3]).is_equal_to(serde_json::json!(["0.004", 1]));
+    }
+
+    #[test]
+    fn log_format_from_config() {
+        let config: SpreadBotConfig = toml::from_str("log_format = \"csv\"").unwrap();
+        assert_that(&config.log_format).is_equal_to(LogFormat::Csv);
+    }
+
+    #[test]
+    fn percents_land_in_configured_buckets() {
+        let edges: Vec<Decimal> = ["0.003", "0.001"]
+            .iter()
+            .map(|s| Decimal::from_str(s).unwrap())
+            .collect();
+        let mut v = MinMax::new(&edges);
+
+        for s in &["0", "0.0005", "0.001", "0.0029", "0.003", "0.5"] {
+            v.record_percent(Decimal::from_str(s).unwrap());
+        }
+
+        let counts: Vec<u32> = v.buckets.iter().map(|(_, c)| *c).collect();
+        assert_that(&counts).is_equal_to(vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn records_min_max_and_mean() {
+        let mut v = MinMax::new(&SpreadBotConfig::default().bucket_edges);
+        assert_that(&v.mean_spread).is_none();
+
+        for (spread, percent) in &[(10, "0.001"), (30, "0.006"), (20, "0.002")] {
+            v.record(Decimal::from(*spread), Decimal::from_str(percent).unwrap());
+        }
+
+        assert_that(&v.min_spread).is_equal_to(Decimal::from(10));
+        assert_that(&v.max_spread).is_equal_to(Decimal::from(30));
+        assert_that(&v.samples).is_equal_to(3);
+        assert_that(&v.mean_spread).is_equal_to(Some(Decimal::from(20)));
+        assert_that(&v.mean_percent).is_equal_to(Some(Decimal::from_str("0.003").unwrap()));
+    }
+
+    #[test]
+    fn bucket_labels_match_edges() {
+        let v = sample_values();
+
+        assert_that(&bucket_counts(&v, true))
+            .is_equal_to("bps <20  20-30  30-40  >40 :\t4\t3\t2\t1".to_string());
+        assert_that(&bucket_counts(&v, false))
+            .is_equal_to("% <0.2  0.2-0.3  0.3-0.4  >0.4 :\t4\t3\t2\t1".to_string());
+        assert_that(&MinMax::new(&[]).bucket_labels(Decimal::from(100)))
+            .is_equal_to(vec!["all".to_string()]);
+    }
+}
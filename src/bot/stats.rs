@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::net::SocketAddr;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, RecvError},
+};
+use tracing::{debug, info, warn};
+
+/// Number of updates buffered per subscriber, a subscriber that falls further
+/// behind than this misses the oldest updates.
+const BUFFERED_UPDATES: usize = 16;
+
+/// Publishes stats as newline delimited JSON to any number of TCP subscribers.
+///
+/// Publishing never blocks, slow subscribers drop updates instead of holding
+/// up the caller.
+#[derive(Clone, Debug)]
+pub struct StatsServer {
+    tx: broadcast::Sender<String>,
+    addr: SocketAddr,
+}
+
+impl StatsServer {
+    /// Listen for subscribers on `addr` (use port 0 to pick any free port).
+    pub async fn bind(addr: SocketAddr) -> Result<Self> {
+        let mut listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind stats socket: {}", addr))?;
+        let addr = listener.local_addr()?;
+        let (tx, _) = broadcast::channel(BUFFERED_UPDATES);
+
+        let server = StatsServer { tx, addr };
+        let accept = server.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        debug!("stats subscriber connected: {}", peer);
+                        tokio::spawn(serve(stream, accept.tx.subscribe()));
+                    }
+                    Err(e) => warn!("failed to accept stats subscriber: {}", e),
+                }
+            }
+        });
+
+        info!("publishing stats on {}", addr);
+        Ok(server)
+    }
+
+    /// Address we are listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Send `stats` to all current subscribers.
+    pub fn publish<T: Serialize>(&self, stats: &T) -> Result<()> {
+        let line = serde_json::to_string(stats).context("failed to serialize stats")?;
+        // An error here just means there are no subscribers.
+        let _ = self.tx.send(line);
+        Ok(())
+    }
+}
+
+// Write updates to a single subscriber until it disconnects.
+async fn serve(mut stream: TcpStream, mut rx: broadcast::Receiver<String>) {
+    loop {
+        let line = match rx.recv().await {
+            Ok(line) => line,
+            Err(RecvError::Lagged(n)) => {
+                debug!("stats subscriber lagging, dropped {} updates", n);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let write = async {
+            stream.write_all(line.as_bytes()).await?;
+            stream.write_all(b"\n").await
+        };
+        if let Err(e) = write.await {
+            debug!("stats subscriber disconnected: {}", e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::spread::{MinMax, SpreadBotConfig};
+    use rust_decimal::Decimal;
+    use serde_json::{json, Value};
+    use spectral::prelude::*;
+    use std::{str::FromStr, time::Duration};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    // Stats after sampling spreads of 10, 30, and 20.
+    fn stats() -> MinMax {
+        let mut stats = MinMax::new(&SpreadBotConfig::default().bucket_edges);
+        for (spread, percent) in &[(10, "0.001"), (30, "0.006"), (20, "0.002")] {
+            stats.record(Decimal::from(*spread), Decimal::from_str(percent).unwrap());
+        }
+        stats
+    }
+
+    #[tokio::test]
+    async fn subscriber_receives_published_stats() {
+        let server = StatsServer::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let client = TcpStream::connect(server.local_addr()).await.unwrap();
+        let mut lines = BufReader::new(client).lines();
+
+        let stats = stats();
+
+        // The subscriber is registered asynchronously, keep publishing until
+        // it receives an update.
+        let line = loop {
+            server.publish(&stats).unwrap();
+            let next = tokio::time::timeout(Duration::from_millis(50), lines.next_line());
+            if let Ok(line) = next.await {
+                break line.unwrap().unwrap();
+            }
+        };
+
+        let got: Value = serde_json::from_str(&line).unwrap();
+        assert_that(&got["min_spread"]).is_equal_to(json!("10"));
+        assert_that(&got["max_spread"]).is_equal_to(json!("30"));
+        assert_that(&got["samples"]).is_equal_to(json!(3));
+        assert_that(&got["mean_spread"]).is_equal_to(json!("20"));
+        assert_that(&got["mean_percent"]).is_equal_to(json!("0.003"));
+    }
+
+    #[tokio::test]
+    async fn publish_without_subscribers_does_not_block() {
+        let server = StatsServer::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let stats = stats();
+
+        for _ in 0..BUFFERED_UPDATES * 2 {
+            assert_that(&server.publish(&stats)).is_ok();
+        }
+    }
+}
@@ -1,6 +1,21 @@
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 use structopt::StructOpt;
 
+use crate::{
+    bot::spread::Sampling,
+    config,
+    market::{OrderBook, Pair},
+    num,
+    trace::TraceFormat,
+};
+
 #[derive(Clone, Debug, StructOpt)]
 pub struct Options {
     /// Path to configuration file
@@ -11,12 +26,317 @@ pub struct Options {
     #[structopt(long = "dump-config")]
     pub dump_config: bool,
 
+    /// Emit machine-readable JSON instead of human readable text
+    #[structopt(long = "json", global = true)]
+    pub json: bool,
+
+    /// Trace output format: pretty or json
+    #[structopt(long = "log-format", global = true, default_value = "pretty")]
+    pub log_format: TraceFormat,
+
+    /// Market to query as BASE/QUOTE e.g., Eth/Aud, used by spread and
+    /// spread-bot [default: Xbt/Aud]
+    #[structopt(long = "pair", global = true)]
+    pub pair: Option<Pair>,
+
     #[structopt(subcommand)]
     pub cmd: Option<Cmd>,
 }
 
+impl Options {
+    /// Output format selected on the command line.
+    pub fn format(&self) -> Format {
+        if self.json {
+            Format::Json
+        } else {
+            Format::Human
+        }
+    }
+
+    /// Market selected with `--pair`, errors if the command doesn't take one
+    /// rather than silently ignoring it.
+    pub fn pair(&self) -> Result<Pair> {
+        match (&self.pair, &self.cmd) {
+            (None, _) => Ok(Pair::default()),
+            (Some(pair), Some(Cmd::Spread { .. })) | (Some(pair), Some(Cmd::SpreadBot { .. })) => {
+                Ok(pair.clone())
+            }
+            (Some(_), _) => bail!("--pair is only supported by the spread and spread-bot commands"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, StructOpt)]
 pub enum Cmd {
     Test,
-    SpreadBot,
+    /// Print the spread to fill buy and sell orders of `volume`
+    Spread {
+        /// Order volume (base currency)
+        #[structopt(long = "volume", default_value = "1")]
+        volume: Decimal,
+    },
+    SpreadBot {
+        /// Display spread percentages in basis points
+        #[structopt(long = "bps")]
+        bps: bool,
+
+        /// Order book sampling mode: fixed, imbalance, or stream
+        #[structopt(long = "sampling", default_value = "fixed")]
+        sampling: Sampling,
+
+        /// Publish live stats as newline delimited JSON to TCP subscribers on
+        /// this address e.g., 127.0.0.1:9000
+        #[structopt(long = "stats-addr")]
+        stats_addr: Option<SocketAddr>,
+
+        /// Seconds between order book samples, overrides the config file
+        #[structopt(long = "sample-period")]
+        sample_period: Option<u64>,
+
+        /// Seconds between log file entries, overrides the config file
+        #[structopt(long = "log-period")]
+        log_period: Option<u64>,
+
+        /// Volume (base currency) used to calculate the spread, overrides
+        /// the config file
+        #[structopt(long = "fill-volume")]
+        fill_volume: Option<Decimal>,
+    },
+    /// Log the BTC spread between Independent Reserve and Kraken
+    ArbBot,
+}
+
+/// Output format for command results written to stdout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+impl Format {
+    /// Render `value` ready for printing.
+    pub fn render<T: Serialize + fmt::Display>(self, value: &T) -> Result<String> {
+        match self {
+            Format::Human => Ok(value.to_string()),
+            Format::Json => serde_json::to_string(value).context("failed to serialize output"),
+        }
+    }
+}
+
+/// Output of `--dump-config`, secrets are redacted.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigDump {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl ConfigDump {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file: {}", path.display()))?;
+        let contents = config::redact_toml(&contents)
+            .with_context(|| format!("failed to parse config file: {}", path.display()))?;
+
+        Ok(ConfigDump {
+            path: path.to_path_buf(),
+            contents,
+        })
+    }
+}
+
+impl fmt::Display for ConfigDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Read config file: \n\n{}", self.contents)
+    }
+}
+
+/// Output of the `spread` command.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpreadReport {
+    pub pair: String,
+    pub volume: Decimal,
+    /// Average price to fill a market sell order.
+    pub bid: Decimal,
+    /// Average price to fill a market buy order.
+    pub ask: Decimal,
+    pub spread: Decimal,
+    /// Spread as a fraction of the mid market price.
+    pub percent: Decimal,
+}
+
+impl SpreadReport {
+    pub fn new(pair: &Pair, volume: Decimal, book: &OrderBook) -> Result<Self> {
+        let (bid, ask) = book.spread_to_fill(volume)?;
+        let (spread, percent) = num::spread_percent(&bid, &ask);
+
+        Ok(SpreadReport {
+            pair: pair.to_string(),
+            volume,
+            bid,
+            ask,
+            spread,
+            percent,
+        })
+    }
+}
+
+impl fmt::Display for SpreadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} volume: {} bid: {} ask: {} spread: {} ({}%)",
+            self.pair,
+            self.volume,
+            self.bid,
+            self.ask,
+            self.spread,
+            num::to_percent_string(&(self.percent * Decimal::from(100))),
+        )
+    }
+}
+
+/// Output of a command that has no data of its own to report.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Status {
+    pub command: &'static str,
+    pub status: &'static str,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.command, self.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use spectral::prelude::*;
+
+    #[test]
+    fn dump_config_with_json_flag_emits_parseable_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let contents = "[ir]\n";
+        fs::write(&path, contents).unwrap();
+
+        let options = Options::from_iter(&[
+            "crypto-trader",
+            "--json",
+            "--dump-config",
+            "-c",
+            path.to_str().unwrap(),
+        ]);
+        let dump = ConfigDump::read(options.config_file.as_ref().unwrap()).unwrap();
+        let stdout = options.format().render(&dump).unwrap();
+
+        let got: Value = serde_json::from_str(&stdout).unwrap();
+        assert_that(&got["path"].as_str()).is_equal_to(path.to_str());
+        assert_that(&got["contents"].as_str()).is_equal_to(Some(contents));
+    }
+
+    #[test]
+    fn json_flag_is_accepted_after_subcommand() {
+        let options = Options::from_iter(&["crypto-trader", "spread-bot", "--json"]);
+        assert_that(&options.format()).is_equal_to(Format::Json);
+
+        let options = Options::from_iter(&["crypto-trader", "test"]);
+        assert_that(&options.format()).is_equal_to(Format::Human);
+        assert_that(&options.log_format).is_equal_to(TraceFormat::Pretty);
+
+        let options = Options::from_iter(&["crypto-trader", "spread-bot", "--log-format", "json"]);
+        assert_that(&options.log_format).is_equal_to(TraceFormat::Json);
+    }
+
+    #[test]
+    fn options_parse_each_subcommand() {
+        let options = Options::from_iter(&["crypto-trader", "test"]);
+        assert_that(&matches!(options.cmd, Some(Cmd::Test))).is_true();
+
+        let options = Options::from_iter(&[
+            "crypto-trader",
+            "-c",
+            "/tmp/config.toml",
+            "spread-bot",
+            "--bps",
+            "--sampling",
+            "imbalance",
+            "--sample-period",
+            "2",
+        ]);
+        assert_that(&options.config_file).is_equal_to(Some(PathBuf::from("/tmp/config.toml")));
+        match options.cmd {
+            Some(Cmd::SpreadBot {
+                bps,
+                sampling,
+                sample_period,
+                stats_addr,
+                ..
+            }) => {
+                assert_that(&bps).is_true();
+                assert_that(&sampling).is_equal_to(Sampling::Imbalance);
+                assert_that(&sample_period).is_equal_to(Some(2));
+                assert_that(&stats_addr).is_none();
+            }
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+
+        let options = Options::from_iter(&["crypto-trader", "--dump-config"]);
+        assert_that(&options.dump_config).is_true();
+        assert_that(&options.cmd.is_none()).is_true();
+    }
+
+    #[test]
+    fn pair_flag_and_spread_subcommand() {
+        let options = Options::from_iter(&[
+            "crypto-trader",
+            "spread",
+            "--pair",
+            "eth/aud",
+            "--volume",
+            "0.5",
+        ]);
+        assert_that(&options.pair().unwrap()).is_equal_to(Pair::new("Eth", "Aud"));
+        match options.cmd {
+            Some(Cmd::Spread { volume }) => assert_that(&volume).is_equal_to(Decimal::new(5, 1)),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+
+        let options = Options::from_iter(&["crypto-trader", "test"]);
+        assert_that(&options.pair().unwrap()).is_equal_to(Pair::default());
+
+        let options = Options::from_iter(&["crypto-trader", "spread-bot", "--pair", "Eth/Aud"]);
+        assert_that(&options.pair().unwrap()).is_equal_to(Pair::new("Eth", "Aud"));
+
+        for cmd in &["test", "arb-bot"] {
+            let options = Options::from_iter(&["crypto-trader", "--pair", "Eth/Aud", cmd]);
+            assert_that(&options.pair().is_err()).is_true();
+        }
+        let options = Options::from_iter(&["crypto-trader", "--pair", "Eth/Aud"]);
+        assert_that(&options.pair().is_err()).is_true();
+
+        let res = Options::from_iter_safe(&["crypto-trader", "--pair", "XbtAud", "spread"]);
+        assert_that(&res.is_err()).is_true();
+    }
+
+    #[test]
+    fn dump_config_redacts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let secret = "11111193333335555558888888111111";
+        let contents = format!(
+            "[ir.read_only]\napi_key = \"key\"\napi_secret = \"{}\"\n",
+            secret
+        );
+        fs::write(&path, contents).unwrap();
+
+        let dump = ConfigDump::read(&path).unwrap();
+
+        for format in &[Format::Human, Format::Json] {
+            let out = format.render(&dump).unwrap();
+            assert_that(&out.contains(secret)).is_false();
+        }
+    }
 }
@@ -1,11 +1,11 @@
 use anyhow::{Context, Result};
 use log::LevelFilter;
-use std::{fs, path::Path, process};
+use std::path::Path;
 use structopt::StructOpt;
 
 use crypto_trader::{
-    bot::spread,
-    cli::{self, Cmd},
+    bot::{arb, spread},
+    cli::{self, Cmd, ConfigDump, SpreadReport, Status},
     config, market, trace,
 };
 
@@ -15,41 +15,86 @@ const CONFIG_FILE: &str = ".config/crypto-trader/config.toml";
 #[tokio::main]
 pub async fn main() -> Result<()> {
     let options = cli::Options::from_args();
+    let format = options.format();
 
-    let config_path = options.config_file.unwrap_or_else(|| {
+    let config_path = options.config_file.clone().unwrap_or_else(|| {
         directories::UserDirs::new()
             .map(|d| d.home_dir().to_path_buf().join(CONFIG_FILE))
             .expect("failed to construct config path")
     });
 
+    let pair = options.pair()?;
+
     if options.dump_config {
-        dump_config(&config_path)?;
-        process::exit(0);
+        let dump = ConfigDump::read(&config_path)?;
+        println!("{}", format.render(&dump)?);
+        return Ok(());
     }
 
-    trace::init_tracing(LevelFilter::Trace)?;
-
-    let config = config::parse(&config_path)
-        .with_context(|| format!("config file: {}", config_path.display()))?;
-    // tracing::debug!("{:?}", config);
-
-    if options.cmd.is_none() {
-        println!("no command supplied, running API tests ...");
-        market::test_ir_api(config.ir.read_only).await;
-        process::exit(0);
-    }
+    // Keep stdout clean for JSON output.
+    let to_stderr = format == cli::Format::Json;
+    trace::init_tracing(LevelFilter::Trace, to_stderr, options.log_format)?;
 
-    match options.cmd.unwrap() {
-        Cmd::Test => market::test_ir_api(config.ir.read_only).await,
-        Cmd::SpreadBot => spread::run(config.ir.read_only).await?,
+    match options.cmd {
+        // Public market data, no API keys required.
+        Some(Cmd::Spread { volume }) => {
+            let public = config::PublicConfig::from_file(&config_path)
+                .with_context(|| format!("config file: {}", config_path.display()))?;
+            let mut market = market::Market::new(pair.clone());
+            if let Some(proxy) = public.proxy {
+                market = market.with_proxy(&proxy)?;
+            }
+            let book = market.order_book().await?;
+            let report = SpreadReport::new(&pair, volume, &book)?;
+            println!("{}", format.render(&report)?);
+        }
+        None | Some(Cmd::Test) => {
+            let config = load_config(&config_path)?;
+            if options.cmd.is_none() && format == cli::Format::Human {
+                println!("no command supplied, running API tests ...");
+            }
+            market::test_ir_api(config.ir.read_only, config.proxy.as_deref()).await;
+            print_test_status(format)?;
+        }
+        Some(Cmd::SpreadBot {
+            bps,
+            sampling,
+            stats_addr,
+            sample_period,
+            log_period,
+            fill_volume,
+        }) => {
+            let config = load_config(&config_path)?;
+            let bot = config
+                .spread_bot
+                .with_overrides(sample_period, log_period, fill_volume);
+            let mut market = market::Market::new(pair);
+            if let Some(proxy) = config.proxy {
+                market = market.with_proxy(&proxy)?;
+            }
+            let market = market.with_read_only(config.ir.read_only);
+            spread::run(market, bot, bps, sampling, format, stats_addr).await?
+        }
+        Some(Cmd::ArbBot) => {
+            let config = load_config(&config_path)?;
+            arb::run(config.kraken.read_only, config.proxy, format).await?
+        }
     }
 
     Ok(())
 }
 
-fn dump_config(path: &Path) -> anyhow::Result<()> {
-    let s = fs::read_to_string(path)?;
-    println!("Read config file: \n\n{}", s);
+fn load_config(path: &Path) -> Result<config::Config> {
+    config::Config::from_env_and_file(path)
+        .with_context(|| format!("config file: {}", path.display()))
+}
+
+fn print_test_status(format: cli::Format) -> Result<()> {
+    let status = Status {
+        command: "test",
+        status: "ok",
+    };
+    println!("{}", format.render(&status)?);
 
     Ok(())
 }
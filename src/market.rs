@@ -1,80 +1,692 @@
 //! This module wraps API access to the Independent Reserve Exchange.
 //!
-//! Here we have convenience functions for accessing the API for trading pair
-//! BTC/AUD. Also logic and data structures for manipulating the raw data
-//! returned from the exchange. The raw API methods are implemented in the `api`
-//! module.
+//! Here we have convenience functions for accessing the API for a single
+//! trading pair, BTC/AUD by default. Also logic and data structures for
+//! manipulating the raw data returned from the exchange. The raw API methods
+//! are implemented in the `api` module.
 
+mod account;
 #[allow(dead_code)] // Don't warn if we do not use all the API methods.
 mod api;
+pub mod binance;
+mod error;
+mod exchange;
 pub mod kraken;
+mod number;
 mod orderbook;
+mod stream;
 
 use self::api::{Private, Public};
-use crate::Key;
-use anyhow::Result;
+use crate::{config, Key};
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{StreamExt, TryStreamExt},
+};
+use reqwest::Client;
+use rust_decimal::Decimal;
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
+// `Order` is an order book entry (see `orderbook`), the API's open order is
+// re-exported as `OpenOrder`.
+pub use self::api::{
+    client_with_proxy, client_with_timeout, Account, AccountStatus, Accounts, AllOrders,
+    BrokerageFees, Candle, DigitalCurrencyDepositAddress, DigitalCurrencyWithdrawal, FxRates,
+    HistorySummary, HoursPast, HttpResponse, HttpTransport, MarketSummary, MockTransport,
+    Order as OpenOrder, OrderDetails, OrderGuid, OrderStatus, OrderTypeKind, Orders, Rate,
+    RecentTrade, RecentTrades, ReqwestTransport, Trade, TradeHistorySummary, DEFAULT_TIMEOUT,
+};
+pub use account::*;
+pub use error::ApiError;
+pub use exchange::{Exchange, Summary};
+pub use number::{decimal_from_str_or_num, option_decimal_from_str_or_num, Number};
 pub use orderbook::*;
+pub use stream::order_book_stream;
 pub use test::*;
 
-/// Primary currency (base).
+/// Default primary currency (base).
 const PRI: &str = "Xbt";
-/// Secondary currency (quote).
+/// Default secondary currency (quote).
 const SEC: &str = "Aud";
 
+/// Default maximum age of a cached order book returned by `order_book`.
+pub const ORDER_BOOK_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// Default maximum number of order books `order_books` fetches at once.
+pub const MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// A trading pair, currency codes as used by the exchange e.g., "Xbt".
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pair {
+    /// Primary currency.
+    pub base: String,
+    /// Secondary currency.
+    pub quote: String,
+}
+
+impl Pair {
+    pub fn new(base: impl Into<String>, quote: impl Into<String>) -> Self {
+        Pair {
+            base: base.into(),
+            quote: quote.into(),
+        }
+    }
+}
+
+impl Default for Pair {
+    fn default() -> Self {
+        Pair::new(PRI, SEC)
+    }
+}
+
+impl FromStr for Pair {
+    type Err = anyhow::Error;
+
+    /// Parse a pair of the form "BASE/QUOTE" e.g., "Xbt/Aud", codes are case
+    /// insensitive.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut codes = s.split('/');
+        match (codes.next(), codes.next(), codes.next()) {
+            (Some(base), Some(quote), None) => {
+                Ok(Pair::new(currency_code(base)?, currency_code(quote)?))
+            }
+            _ => bail!("invalid pair: {} (expected BASE/QUOTE e.g., Xbt/Aud)", s),
+        }
+    }
+}
+
+// Normalise a currency code to the exchange's format e.g., "xbt" -> "Xbt".
+fn currency_code(s: &str) -> Result<String> {
+    let s = s.trim();
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphabetic()) {
+        bail!("invalid currency code: {:?}", s);
+    }
+
+    let lower = s.to_ascii_lowercase();
+    Ok(lower[..1].to_ascii_uppercase() + &lower[1..])
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Market {
+    pair: Pair,
     public: Public,
     private: Option<Private>,
+    /// Brokerage fees, fetched on first use.
+    fees: Option<BrokerageFees>,
+    /// Maximum age of a cached order book.
+    book_max_age: Duration,
+    /// Last order book fetched and when, shared by clones.
+    book: Arc<Mutex<Option<(Instant, OrderBook)>>>,
+    /// Maximum number of order books to fetch at once.
+    max_concurrent_fetches: usize,
 }
 
 impl Market {
+    /// Market for trading `pair`.
+    pub fn new(pair: Pair) -> Self {
+        Market {
+            pair,
+            ..Market::default()
+        }
+    }
+
+    /// The pair this market trades.
+    pub fn pair(&self) -> &Pair {
+        &self.pair
+    }
+
+    /// Check that the exchange supports our pair's currency codes.
+    pub async fn validate_pair(&self) -> Result<()> {
+        self.public
+            .check_currency_codes(&self.pair.base, &self.pair.quote)
+            .await
+    }
+
+    /// Time out API requests after `timeout`, defaults to `DEFAULT_TIMEOUT`.
+    pub fn with_timeout(self, timeout: Duration) -> Result<Self> {
+        let client = api::client_with_timeout(timeout)?;
+        Ok(self.with_client(client))
+    }
+
+    /// Send requests through `proxy` e.g., "socks5://127.0.0.1:1080", with the
+    /// default timeout. Use `with_client` and `client_with_proxy` to set
+    /// both.
+    pub fn with_proxy(self, proxy: &str) -> Result<Self> {
+        let client = api::client_with_proxy(DEFAULT_TIMEOUT, proxy)?;
+        Ok(self.with_client(client))
+    }
+
+    /// Make public and private requests with `client`, they share its
+    /// connection pool.
+    pub fn with_client(self, client: Client) -> Self {
+        self.with_transport(Arc::new(ReqwestTransport::new(client)))
+    }
+
+    /// Make requests using `transport` e.g., a `MockTransport` in tests.
+    pub fn with_transport(self, transport: Arc<dyn HttpTransport>) -> Self {
+        let private = self
+            .private
+            .map(|p| p.with_transport(Arc::clone(&transport)));
+
+        Market {
+            public: Public::with_transport(transport),
+            private,
+            ..self
+        }
+    }
+
+    /// Use the keys configured for the exchange, admin and full access keys
+    /// are optional.
+    pub fn with_keys(self, keys: config::Exchange) -> Self {
+        let mut private = self.private_api(keys.read_only);
+        if let Some(admin) = keys.admin {
+            private = private.with_admin(admin.api_key, admin.api_secret);
+        }
+        if let Some(full) = keys.full_access {
+            private = private.with_full_access(full.api_key, full.api_secret);
+        }
+
+        Market {
+            private: Some(private),
+            ..self
+        }
+    }
+
     pub fn with_read_only(self, read: Key) -> Self {
-        let nonce = crate::nonce();
-        let private = Private::new(nonce, read.api_key, read.api_secret);
+        let private = self.private_api(read);
 
         Market {
-            public: self.public,
             private: Some(private),
+            ..self
         }
     }
 
+    /// Add an admin key, errors if there is no read-only key (see
+    /// `with_read_only`).
+    pub fn with_admin(self, admin: Key) -> Result<Self> {
+        let private = self
+            .private
+            .ok_or_else(|| {
+                anyhow!("an admin key needs a read-only key, call with_read_only first")
+            })?
+            .with_admin(admin.api_key, admin.api_secret);
+
+        Ok(Market {
+            private: Some(private),
+            ..self
+        })
+    }
+
+    /// Add a full access key, errors if there is no read-only key (see
+    /// `with_read_only`).
+    pub fn with_full_access(self, full: Key) -> Result<Self> {
+        let private = self
+            .private
+            .ok_or_else(|| {
+                anyhow!("a full access key needs a read-only key, call with_read_only first")
+            })?
+            .with_full_access(full.api_key, full.api_secret);
+
+        Ok(Market {
+            private: Some(private),
+            ..self
+        })
+    }
+
+    // Private API using the `read` key.
+    fn private_api(&self, read: Key) -> Private {
+        let nonce = crate::nonce();
+        // Share the public transport so we use the same timeout.
+        Private::new(nonce, read.api_key, read.api_secret).with_transport(self.public.transport())
+    }
+
+    /// Return order books up to `max_age` old from `order_book`, defaults to
+    /// `ORDER_BOOK_MAX_AGE`. A zero `max_age` disables caching.
+    pub fn with_order_book_max_age(self, max_age: Duration) -> Self {
+        Market {
+            book_max_age: max_age,
+            ..self
+        }
+    }
+
+    /// Get the order book, a cached book is returned if it is younger than the
+    /// maximum age (see `with_order_book_max_age`).
     pub async fn order_book(&self) -> Result<OrderBook> {
-        let order_book = self.public.get_order_book(PRI, SEC).await?;
-        Ok(order_book.into())
+        if let Some((fetched, book)) = &*self.book.lock().expect("order book lock poisoned") {
+            if fetched.elapsed() < self.book_max_age {
+                return Ok(book.clone());
+            }
+        }
+        self.order_book_fresh().await
+    }
+
+    /// Get the order book from the exchange, bypassing the cache.
+    pub async fn order_book_fresh(&self) -> Result<OrderBook> {
+        let (order_book, _) = self.order_book_with_stats().await?;
+        *self.book.lock().expect("order book lock poisoned") =
+            Some((Instant::now(), order_book.clone()));
+        Ok(order_book)
+    }
+
+    /// Fetch at most `limit` order books at once from `order_books`, defaults
+    /// to `MAX_CONCURRENT_FETCHES`. Use a low limit to stay within the
+    /// exchange's rate limits.
+    pub fn with_max_concurrent_fetches(self, limit: usize) -> Self {
+        Market {
+            max_concurrent_fetches: limit.max(1),
+            ..self
+        }
+    }
+
+    /// Get the order book for each of `pairs`, fetched concurrently. Errors if
+    /// any fetch fails.
+    pub async fn order_books(&self, pairs: &[Pair]) -> Result<HashMap<Pair, OrderBook>> {
+        futures::stream::iter(pairs)
+            .map(|pair| async move {
+                let book = Exchange::order_book(self, pair).await?;
+                Ok((pair.clone(), book))
+            })
+            .buffer_unordered(self.max_concurrent_fetches)
+            .try_collect()
+            .await
+    }
+
+    /// Get the order book along with stats on any invalid orders the exchange
+    /// returned, these are dropped from the book.
+    pub async fn order_book_with_stats(&self) -> Result<(OrderBook, ConversionStats)> {
+        let order_book = self
+            .public
+            .get_order_book(&self.pair.base, &self.pair.quote)
+            .await?;
+        Ok(OrderBook::from_api(order_book))
+    }
+
+    /// Get a snapshot of the market: last price, best bid/offer, and daily
+    /// stats.
+    pub async fn market_summary(&self) -> Result<MarketSummary> {
+        self.public
+            .get_market_summary(&self.pair.base, &self.pair.quote)
+            .await
+    }
+
+    /// Get the exchange's rate to convert currency `a` into currency `b` e.g.,
+    /// ("Usd", "Aud").
+    pub async fn fx_rate(&self, a: &str, b: &str) -> Result<Decimal> {
+        let rates = self.public.get_fx_rates().await?;
+        rates
+            .get(a, b)
+            .ok_or_else(|| anyhow!("no FX rate for {}/{}", a, b))
+    }
+
+    /// Get the deposit address for `currency`. If the exchange reports the
+    /// address as stale we sync it with the blockchain (requires an admin key)
+    /// and fetch it again.
+    pub async fn deposit_address(
+        &mut self,
+        currency: &str,
+    ) -> Result<DigitalCurrencyDepositAddress> {
+        let private = self.private()?;
+
+        let address = private
+            .get_digital_currency_deposit_address(currency)
+            .await?;
+        if !address.is_stale(Utc::now()) {
+            return Ok(address);
+        }
+
+        let _ = private
+            .sync_digital_currency_deposit_address_with_blockchain(
+                address.deposit_address(),
+                currency,
+            )
+            .await?;
+        private.get_digital_currency_deposit_address(currency).await
+    }
+
+    /// Get the full details, including volume filled and average price, of the
+    /// order identified by `guid`.
+    pub async fn order_details(&mut self, guid: &str) -> Result<OrderDetails> {
+        self.private()?.get_order_details(guid).await
+    }
+
+    /// Get the status of the digital currency withdrawal identified by
+    /// `tx_guid`.
+    pub async fn digital_currency_withdrawal(
+        &mut self,
+        tx_guid: &str,
+    ) -> Result<DigitalCurrencyWithdrawal> {
+        self.private()?
+            .get_digital_currency_withdrawal(tx_guid)
+            .await
+    }
+
+    /// Get the brokerage fee (as a fraction) for `currency`. Fees are fetched
+    /// from the exchange on first use and cached thereafter.
+    pub async fn fee_for(&mut self, currency: &str) -> Result<Decimal> {
+        if self.fees.is_none() {
+            let fees = self.private()?.get_brokerage_fees().await?;
+            self.fees = Some(fees);
+        }
+
+        self.fees
+            .as_ref()
+            .and_then(|fees| fees.fee_for(currency))
+            .ok_or_else(|| anyhow!("no brokerage fee for currency: {}", currency))
+    }
+
+    /// Get free, reserved, and total balances for each currency. Reserved
+    /// balances are reconciled against our open orders across all pairs.
+    pub async fn account_overview(&mut self) -> Result<AccountOverview> {
+        Ok(self.portfolio().await?.reserved)
+    }
+
+    /// Get account balances and every page of open orders across all pairs.
+    /// The requests are made one after the other, the exchange rejects a
+    /// nonce that arrives after a higher one.
+    pub async fn portfolio(&mut self) -> Result<Portfolio> {
+        let private = self.private()?;
+        let accounts = private.get_accounts().await?;
+        let open_orders = private.get_all_open_orders_for_all_pairs().await?;
+
+        Ok(Portfolio::new(accounts, open_orders))
+    }
+
+    fn private(&mut self) -> Result<&mut Private> {
+        self.private
+            .as_mut()
+            .ok_or_else(|| anyhow!("no private API key configured"))
+    }
+}
+
+impl Exchange for Market {
+    fn order_book<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<OrderBook>> {
+        async move {
+            let book = self.public.get_order_book(&pair.base, &pair.quote).await?;
+            let (book, _) = OrderBook::from_api(book);
+            Ok(book)
+        }
+        .boxed()
+    }
+
+    fn market_summary<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<Summary>> {
+        async move {
+            let summary = self
+                .public
+                .get_market_summary(&pair.base, &pair.quote)
+                .await?;
+            Ok(Summary::from(&summary))
+        }
+        .boxed()
     }
 }
 
 impl Default for Market {
     fn default() -> Self {
         Market {
+            pair: Pair::default(),
             public: Public::default(),
             private: None,
+            fees: None,
+            book_max_age: ORDER_BOOK_MAX_AGE,
+            book: Arc::default(),
+            max_concurrent_fetches: MAX_CONCURRENT_FETCHES,
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{api::contains_code, *};
+    use spectral::prelude::*;
+
+    #[test]
+    fn market_defaults_to_btc_aud() {
+        assert_that(Market::default().pair()).is_equal_to(Pair::new("Xbt", "Aud"));
+    }
+
+    #[test]
+    fn public_and_private_share_one_client() {
+        let key = Key {
+            api_key: "key".to_string(),
+            api_secret: "secret".to_string(),
+        };
+        let client = api::client_with_timeout(DEFAULT_TIMEOUT).unwrap();
+
+        let markets = [
+            Market::default()
+                .with_client(client.clone())
+                .with_read_only(key.clone()),
+            Market::default().with_read_only(key).with_client(client),
+        ];
+        for market in markets.iter() {
+            let private = market.private.as_ref().unwrap();
+            assert_that(&Arc::ptr_eq(
+                &market.public.transport(),
+                &private.transport(),
+            ))
+            .is_true();
+        }
+    }
+
+    fn key(name: &str) -> Key {
+        Key {
+            api_key: format!("{}-key", name),
+            api_secret: format!("{}-secret", name),
+        }
+    }
+
+    #[test]
+    fn admin_key_needs_read_only_key() {
+        assert_that(&Market::default().with_admin(key("admin")).is_err()).is_true();
+        assert_that(&Market::default().with_full_access(key("full")).is_err()).is_true();
+
+        let market = Market::default()
+            .with_read_only(key("read"))
+            .with_admin(key("admin"))
+            .and_then(|m| m.with_full_access(key("full")));
+        assert_that(&market.is_ok()).is_true();
+    }
+
+    fn deposit_address(next_update: &str) -> String {
+        format!(
+            r#"{{
+                "DepositAddress": "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ",
+                "LastCheckedTimestampUtc": "2020-04-08T01:00:00Z",
+                "NextUpdateTimestampUtc": "{}"
+            }}"#,
+            next_update
+        )
+    }
+
+    #[tokio::test]
+    async fn stale_deposit_address_is_synced_then_refetched() {
+        let fresh = deposit_address("2100-01-01T00:00:00Z");
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_json(
+                    "GetDigitalCurrencyDepositAddress",
+                    deposit_address("2020-04-08T02:00:00Z"),
+                )
+                .with_json("GetDigitalCurrencyDepositAddress", fresh.clone())
+                .with_json("SynchDigitalCurrencyDepositAddressWithBlockchain", fresh),
+        );
+        let mut market = Market::default()
+            .with_transport(mock.clone())
+            .with_read_only(key("read"))
+            .with_admin(key("admin"))
+            .unwrap();
+
+        let address = market.deposit_address("Xbt").await.unwrap();
+
+        assert_that(&address.is_stale(Utc::now())).is_false();
+        let methods: Vec<String> = mock
+            .requests()
+            .iter()
+            .map(|url| url.path().rsplit('/').next().unwrap().to_string())
+            .collect();
+        assert_that(&methods).is_equal_to(vec![
+            "GetDigitalCurrencyDepositAddress".to_string(),
+            "SynchDigitalCurrencyDepositAddressWithBlockchain".to_string(),
+            "GetDigitalCurrencyDepositAddress".to_string(),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn fresh_deposit_address_is_not_synced() {
+        let mock = Arc::new(MockTransport::new().with_json(
+            "GetDigitalCurrencyDepositAddress",
+            deposit_address("2100-01-01T00:00:00Z"),
+        ));
+        let mut market = Market::default()
+            .with_transport(mock.clone())
+            .with_read_only(key("read"));
+
+        market.deposit_address("Xbt").await.unwrap();
+
+        assert_that(&mock.requests()).has_length(1);
+    }
+
+    fn order_book_mock() -> Arc<MockTransport> {
+        Arc::new(MockTransport::new().with_json(
+            "GetOrderBook",
+            include_str!("../testdata/GetOrderBook.json"),
+        ))
+    }
+
+    fn order_book_requests(mock: &MockTransport) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|url| url.path().ends_with("GetOrderBook"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn order_book_is_cached() {
+        let mock = order_book_mock();
+        let market = Market::default()
+            .with_transport(mock.clone())
+            .with_order_book_max_age(Duration::from_secs(60));
+
+        let first = market.order_book().await.unwrap();
+        let second = market.clone().order_book().await.unwrap();
+        assert_that(&order_book_requests(&mock)).is_equal_to(1);
+        assert_that(&second.best_bid()).is_equal_to(first.best_bid());
+
+        market.order_book_fresh().await.unwrap();
+        assert_that(&order_book_requests(&mock)).is_equal_to(2);
+    }
+
+    #[tokio::test]
+    async fn order_books_for_several_pairs() {
+        let mock = order_book_mock();
+        let market = Market::default()
+            .with_transport(mock.clone())
+            .with_max_concurrent_fetches(2);
+        let pairs = vec![
+            Pair::new("Xbt", "Aud"),
+            Pair::new("Eth", "Aud"),
+            Pair::new("Ltc", "Aud"),
+        ];
+
+        let books = market.order_books(&pairs).await.unwrap();
+
+        assert_that(&books.len()).is_equal_to(3);
+        for pair in pairs.iter() {
+            assert_that(&books[pair].best_bid()).is_some();
+            let query = format!(
+                "primaryCurrencyCode={}&secondaryCurrencyCode={}",
+                pair.base, pair.quote
+            );
+            let requested = mock
+                .requests()
+                .iter()
+                .any(|url| url.query() == Some(&query));
+            assert_that(&requested).is_true();
+        }
+    }
+
+    #[tokio::test]
+    async fn zero_max_age_disables_order_book_cache() {
+        let mock = order_book_mock();
+        let market = Market::default()
+            .with_transport(mock.clone())
+            .with_order_book_max_age(Duration::from_secs(0));
+
+        market.order_book().await.unwrap();
+        market.order_book().await.unwrap();
+
+        assert_that(&order_book_requests(&mock)).is_equal_to(2);
+    }
+
+    #[test]
+    fn market_for_other_pair() {
+        let market = Market::new(Pair::new("Eth", "Aud"));
+
+        assert_that(market.pair()).is_equal_to(Pair::new("Eth", "Aud"));
+        assert_that(&market.pair().to_string()).is_equal_to("Eth/Aud".to_string());
+    }
+
+    #[test]
+    fn parse_pair() {
+        let pair = |s: &str| s.parse::<Pair>();
+
+        assert_that(&pair("Xbt/Aud").unwrap()).is_equal_to(Pair::new("Xbt", "Aud"));
+        assert_that(&pair("eth/aud").unwrap()).is_equal_to(Pair::new("Eth", "Aud"));
+        assert_that(&pair("XbtAud")).is_err();
+        assert_that(&pair("Xbt/")).is_err();
+        assert_that(&pair("Xbt/Aud/Usd")).is_err();
+    }
+
+    #[test]
+    fn currency_codes_match_case_insensitively() {
+        let codes = vec!["Xbt".to_string(), "Eth".to_string()];
+
+        assert_that(&contains_code(&codes, "eth")).is_true();
+        assert_that(&contains_code(&codes, "Doge")).is_false();
+    }
+}
+
 mod test {
     use super::*;
     use crate::config::Key;
     use tracing::info;
 
-    /// Test the Independent Reserve API.
-    pub async fn test_ir_api(read_only: Key) {
-        assert_public_api()
+    /// Test the Independent Reserve API, requests go through `proxy` if set.
+    pub async fn test_ir_api(read_only: Key, proxy: Option<&str>) {
+        let client = match proxy {
+            Some(proxy) => client_with_proxy(DEFAULT_TIMEOUT, proxy),
+            None => client_with_timeout(DEFAULT_TIMEOUT),
+        }
+        .expect("failed to build HTTP client");
+        let transport: Arc<dyn HttpTransport> = Arc::new(ReqwestTransport::new(client));
+
+        assert_public_api(Arc::clone(&transport))
             .await
             .expect("public API method assertion failed");
-        assert_private_api_read_only(read_only)
+        assert_private_api_read_only(read_only, transport)
             .await
             .expect("private read-only API assertion failed");
     }
 
     /// Call each of the public API methods.
-    async fn assert_public_api() -> Result<()> {
+    async fn assert_public_api(transport: Arc<dyn HttpTransport>) -> Result<()> {
         let base = "Xbt";
         let quote = "Aud";
 
-        let api = Public::default();
+        let api = Public::with_transport(transport);
 
         info!("Running all public API methods ...");
         let _ = api.get_valid_primary_currency_codes().await?;
@@ -86,7 +698,9 @@ mod test {
         let _ = api.get_market_summary(base, quote).await?;
         let _ = api.get_order_book(base, quote).await?;
         let _ = api.get_all_orders(base, quote).await?;
-        let _ = api.get_trade_history_summary(base, quote, 1).await?;
+        let _ = api
+            .get_trade_history_summary(base, quote, HoursPast::new(1)?)
+            .await?;
         let _ = api.get_recent_trades(base, quote, 10).await?;
         let _ = api.get_fx_rates().await?;
 
@@ -94,18 +708,24 @@ mod test {
     }
 
     /// Call each of the private API methods that require a read-only key.
-    async fn assert_private_api_read_only(read: Key) -> Result<()> {
+    async fn assert_private_api_read_only(
+        read: Key,
+        transport: Arc<dyn HttpTransport>,
+    ) -> Result<()> {
         let base = "Xbt";
         let quote = "Aud";
         let index = 1;
         let nonce = crate::nonce();
 
-        let mut api = Private::new(nonce, read.api_key, read.api_secret);
+        let mut api = Private::new(nonce, read.api_key, read.api_secret).with_transport(transport);
 
         info!("Running [most] private API methods ...");
 
         let _ = api.get_open_orders(base, quote, index).await?;
-        let _ = api.get_closed_orders(base, quote, index).await?;
+        let closed = api.get_closed_orders(base, quote, index).await?;
+        if let Some(order) = closed.iter().next() {
+            let _ = api.get_order_details(order.order_guid()).await?;
+        }
 
         let _ = api.get_closed_filled_orders(base, quote, index).await?;
 
@@ -117,7 +737,6 @@ mod test {
         let _ = api.get_trades(index).await?;
         let _ = api.get_brokerage_fees().await?;
 
-        // TODO: api.get_order_details(order_guuid).await.?;
         // TODO:  api.get_transactions().await.?;
         // TODO: api.get_digital_currency_withdrawal(tx_guid).await.?;
 
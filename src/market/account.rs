@@ -0,0 +1,247 @@
+use crate::market::api;
+use num_traits::identities::Zero;
+use rust_decimal::Decimal;
+use tracing::warn;
+
+/// Per currency balances for the account, see `Market::account_overview`.
+#[derive(Clone, Debug)]
+pub struct AccountOverview {
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Balance {
+    pub currency: String,
+    /// Funds available for trading/withdrawal.
+    pub free: Decimal,
+    /// Funds reserved, as reported by the exchange.
+    pub reserved: Decimal,
+    /// Funds we calculate are reserved by our open orders.
+    pub reserved_by_orders: Decimal,
+    pub total: Decimal,
+}
+
+impl Balance {
+    /// Reserved funds not accounted for by our open orders (e.g., fees or
+    /// pending withdrawals).
+    pub fn unaccounted(&self) -> Decimal {
+        self.reserved - self.reserved_by_orders
+    }
+}
+
+impl AccountOverview {
+    /// Build an overview from account balances and the open orders.
+    pub fn new(accounts: &api::Accounts, open_orders: &[api::Order]) -> Self {
+        let mut balances: Vec<Balance> = accounts
+            .iter()
+            .map(|a| Balance {
+                currency: a.currency_code().to_string(),
+                free: a.available_balance(),
+                reserved: a.total_balance() - a.available_balance(),
+                reserved_by_orders: Decimal::zero(),
+                total: a.total_balance(),
+            })
+            .collect();
+
+        for order in open_orders.iter() {
+            let (currency, amount) = match reserved_by(order) {
+                Some(reserved) => reserved,
+                None => continue,
+            };
+            match balances
+                .iter_mut()
+                .find(|b| b.currency.eq_ignore_ascii_case(currency))
+            {
+                Some(balance) => balance.reserved_by_orders += amount,
+                None => warn!("open order for currency without account: {}", currency),
+            }
+        }
+
+        for balance in balances.iter() {
+            if balance.reserved_by_orders > balance.reserved {
+                warn!(
+                    "{} open orders reserve {} but account only has {} reserved",
+                    balance.currency, balance.reserved_by_orders, balance.reserved
+                );
+            }
+        }
+
+        AccountOverview { balances }
+    }
+
+    /// Balances for `currency`.
+    pub fn balance(&self, currency: &str) -> Option<&Balance> {
+        self.balances
+            .iter()
+            .find(|b| b.currency.eq_ignore_ascii_case(currency))
+    }
+}
+
+/// Balances and open orders for the account, see `Market::portfolio`.
+#[derive(Clone, Debug)]
+pub struct Portfolio {
+    pub balances: api::Accounts,
+    /// Open orders across all pairs.
+    pub open_orders: Vec<api::Order>,
+    /// Free vs reserved funds for each currency.
+    pub reserved: AccountOverview,
+}
+
+impl Portfolio {
+    pub fn new(balances: api::Accounts, open_orders: Vec<api::Order>) -> Self {
+        let reserved = AccountOverview::new(&balances, &open_orders);
+        Portfolio {
+            balances,
+            open_orders,
+            reserved,
+        }
+    }
+}
+
+// The currency and amount of funds an open order reserves. A bid reserves the
+// secondary currency needed to buy the outstanding volume, an offer reserves
+// the outstanding volume of the primary currency.
+fn reserved_by(order: &api::Order) -> Option<(&str, Decimal)> {
+    let order_type = order.order_type();
+    if order_type.is_bid() {
+        let price = order.price()?;
+        Some((order.secondary_currency_code(), order.outstanding() * price))
+    } else if order_type.is_offer() {
+        Some((order.primary_currency_code(), order.outstanding()))
+    } else {
+        warn!("unknown order type: {}", order_type);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        market::{Market, MockTransport},
+        Key,
+    };
+    use spectral::prelude::*;
+    use std::sync::Arc;
+
+    const ACCOUNTS: &str = r#"[
+        {
+            "AccountGuid": "66dcac65-bf07-4e68-ad46-838f51100424",
+            "AccountStatus": "Active",
+            "AvailableBalance": 0.5,
+            "CurrencyCode": "Xbt",
+            "TotalBalance": 1.5
+        },
+        {
+            "AccountGuid": "49994921-60ec-411e-8a78-d0eba078d5e9",
+            "AccountStatus": "Active",
+            "AvailableBalance": 1000.0,
+            "CurrencyCode": "Aud",
+            "TotalBalance": 3000.0
+        }
+    ]"#;
+
+    const OPEN_ORDERS: &str = r#"{
+        "Data": [
+            {
+                "AvgPrice": 0.0,
+                "CreatedTimestampUtc": "2020-04-08T01:47:33.5718327Z",
+                "FeePercent": 0.005,
+                "OrderGuid": "c7347e4c-b865-4c94-8f74-d934d4b0b177",
+                "OrderType": "LimitOffer",
+                "Outstanding": 1.0,
+                "Price": 12000.0,
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Aud",
+                "Status": "Open",
+                "Value": 12000.0,
+                "Volume": 1.0
+            },
+            {
+                "AvgPrice": 0.0,
+                "CreatedTimestampUtc": "2020-04-08T01:48:33.5718327Z",
+                "FeePercent": 0.005,
+                "OrderGuid": "b8a3d2f4-38d5-4e1d-8a6c-3b9f54a7f2a1",
+                "OrderType": "LimitBid",
+                "Outstanding": 0.2,
+                "Price": 10000.0,
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Aud",
+                "Status": "PartiallyFilled",
+                "Value": 2000.0,
+                "Volume": 0.2
+            }
+        ],
+        "PageSize": 25,
+        "TotalItems": 2,
+        "TotalPages": 1
+    }"#;
+
+    #[test]
+    fn overview_reconciles_open_orders_against_balances() {
+        let accounts: api::Accounts = serde_json::from_str(ACCOUNTS).unwrap();
+        let orders: api::Orders = serde_json::from_str(OPEN_ORDERS).unwrap();
+
+        let orders: Vec<api::Order> = orders.iter().cloned().collect();
+
+        let overview = AccountOverview::new(&accounts, &orders);
+
+        let xbt = overview.balance("Xbt").expect("Xbt balance");
+        assert_that(&xbt.free).is_equal_to(Decimal::new(5, 1));
+        assert_that(&xbt.reserved).is_equal_to(Decimal::from(1));
+        assert_that(&xbt.reserved_by_orders).is_equal_to(Decimal::from(1));
+        assert_that(&xbt.unaccounted()).is_equal_to(Decimal::zero());
+
+        let aud = overview.balance("Aud").expect("Aud balance");
+        assert_that(&aud.total).is_equal_to(Decimal::from(3000));
+        assert_that(&aud.reserved).is_equal_to(Decimal::from(2000));
+        assert_that(&aud.reserved_by_orders).is_equal_to(Decimal::from(2000));
+    }
+
+    #[tokio::test]
+    async fn portfolio_from_mock() {
+        // Two pages of open orders.
+        let page = OPEN_ORDERS.replace("\"TotalPages\": 1", "\"TotalPages\": 2");
+        let mock = MockTransport::new()
+            .with_json("GetAccounts", ACCOUNTS)
+            .with_json("GetOpenOrders", page);
+        let mock = Arc::new(mock);
+        let key = Key {
+            api_key: "key".to_string(),
+            api_secret: "secret".to_string(),
+        };
+        let mut market = Market::default()
+            .with_transport(mock.clone())
+            .with_read_only(key);
+
+        let portfolio = market.portfolio().await.unwrap();
+
+        assert_that(&portfolio.balances.available_in("Aud")).is_equal_to(Some(Decimal::from(1000)));
+        assert_that(&portfolio.open_orders).has_length(4);
+        let aud = portfolio.reserved.balance("Aud").expect("Aud balance");
+        assert_that(&aud.reserved_by_orders).is_equal_to(Decimal::from(4000));
+
+        let methods: Vec<String> = mock
+            .requests()
+            .iter()
+            .map(|url| url.path().rsplit('/').next().unwrap().to_string())
+            .collect();
+        assert_that(&methods).is_equal_to(vec![
+            "GetAccounts".to_string(),
+            "GetOpenOrders".to_string(),
+            "GetOpenOrders".to_string(),
+        ]);
+        // Open orders for all pairs, one request at a time with rising nonces.
+        let bodies = mock.bodies();
+        assert_that(&bodies.iter().any(|b| b.contains("primaryCurrencyCode"))).is_false();
+        let nonces: Vec<u64> = bodies
+            .iter()
+            .map(|b| {
+                serde_json::from_str::<serde_json::Value>(b).unwrap()["nonce"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_that(&nonces.windows(2).all(|w| w[0] < w[1])).is_true();
+    }
+}
@@ -3,11 +3,183 @@
 //! Private methods require authentication using an API key, signature, and a
 //! nonce.
 
+mod limiter;
+mod nonce;
 mod private;
 mod public;
+mod rate_limit;
+#[cfg(test)]
+mod recorded;
+mod timestamp;
+mod transport;
 
+pub use limiter::RateLimiter;
+pub use nonce::*;
 pub use private::*;
 pub use public::*;
+// Both modules define a `Trade`, disambiguate the recent (public) trades.
+pub use private::Trade;
+pub use public::Trade as RecentTrade;
+pub use rate_limit::RateLimit;
+pub use transport::{HttpResponse, HttpTransport, MockTransport, ReqwestTransport};
+
+use super::ApiError;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Client, Proxy, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use std::{sync::Arc, time::Duration};
+use tracing::warn;
+use url::Url;
+
+/// Maximum number of characters of a response body to include in errors.
+const BODY_SNIPPET_LEN: usize = 128;
+
+/// Default timeout for API requests, so a hung connection can't stall a caller
+/// forever.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Build an HTTP client with requests timing out after `timeout`. Requests
+/// go through the proxy in the standard `HTTPS_PROXY`/`HTTP_PROXY` environment
+/// variables, if set.
+pub fn client_with_timeout(timeout: Duration) -> Result<Client> {
+    Client::builder()
+        .timeout(timeout)
+        .build()
+        .context("failed to build HTTP client")
+}
+
+/// Build an HTTP client with requests timing out after `timeout` and sent
+/// through `proxy` e.g., "http://proxy:8080" or "socks5://127.0.0.1:1080".
+/// Takes precedence over any proxy set in the environment.
+pub fn client_with_proxy(timeout: Duration, proxy: &str) -> Result<Client> {
+    let proxy = Proxy::all(proxy).with_context(|| format!("invalid proxy: {}", proxy))?;
+    Client::builder()
+        .timeout(timeout)
+        .proxy(proxy)
+        .build()
+        .context("failed to build HTTP client")
+}
+
+// Transport used when none is provided.
+fn default_transport() -> Arc<dyn HttpTransport> {
+    let client = client_with_timeout(DEFAULT_TIMEOUT).expect("failed to build default HTTP client");
+    Arc::new(ReqwestTransport::new(client))
+}
+
+// A request to be sent by `send_with_retry`.
+enum Request {
+    Get(Url),
+    /// URL and serialized JSON body. Kept as bytes, serializing via a
+    /// `serde_json::Value` would sort the keys.
+    Post(Url, Vec<u8>),
+}
+
+/// How API requests that fail with a transient error (HTTP status 429, 502,
+/// 503, or 504) are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first.
+    pub max_attempts: usize,
+    /// Delay before the first retry, doubled for each retry thereafter. A
+    /// Retry-After header in the response takes precedence.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+// Send the request built by `build` using `transport`, retrying transient
+// errors according to `policy`. `build` is called for every attempt. If the
+// request is not `idempotent` we only retry when rate limited (429). Each
+// attempt waits on `limiter`, if given. Errors if the final response status is
+// not 200, the error wraps an `ApiError`.
+async fn send_with_retry<F>(
+    transport: &dyn HttpTransport,
+    policy: RetryPolicy,
+    limiter: Option<&RateLimiter>,
+    idempotent: bool,
+    mut build: F,
+) -> Result<HttpResponse>
+where
+    F: FnMut() -> Result<Request>,
+{
+    let mut delay = policy.backoff;
+    let mut attempt = 1;
+    loop {
+        if let Some(limiter) = limiter {
+            limiter.acquire().await;
+        }
+        let res = match build()? {
+            Request::Get(url) => transport.get(url).await?,
+            Request::Post(url, body) => transport.post(url, body).await?,
+        };
+        let status = res.status;
+        if status == StatusCode::OK {
+            return Ok(res);
+        }
+        let snippet: String = res.body.chars().take(BODY_SNIPPET_LEN).collect();
+        warn!("request returned status: {}, body: {:?}", status, snippet);
+
+        let transient = match status {
+            StatusCode::TOO_MANY_REQUESTS => true,
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => idempotent,
+            _ => false,
+        };
+        if !transient || attempt >= policy.max_attempts {
+            let body = res.body;
+            return Err(ApiError::Http { status, body }.into());
+        }
+
+        let wait = retry_after(&res.headers).unwrap_or(delay);
+        warn!(
+            "attempt {} of {} failed, retrying in {:?}",
+            attempt, policy.max_attempts, wait
+        );
+        tokio::time::delay_for(wait).await;
+
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+// The delay requested by a Retry-After header, if given in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    secs.trim().parse().ok().map(Duration::from_secs)
+}
+
+// Deserialize the response `body` returned by API method `endpoint`.
+pub(super) fn from_json<T: DeserializeOwned>(endpoint: &str, body: &str) -> Result<T> {
+    serde_json::from_str(body)
+        .map_err(ApiError::Decode)
+        .with_context(|| {
+            let snippet: String = body.chars().take(BODY_SNIPPET_LEN).collect();
+            format!("deserializing {} response: {:?}", endpoint, snippet)
+        })
+}
+
+// Parses an ISO 8601 timestamp as returned by the exchange. Seconds may have
+// a fractional part and some endpoints omit the UTC offset, we assume UTC.
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    let t = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .with_context(|| format!("invalid timestamp: {}", s))?;
+    Ok(DateTime::from_naive_utc_and_offset(t, Utc))
+}
 
 // Authentication
 //
@@ -46,3 +218,264 @@ pub use public::*;
 // be generated using the API Secret that was generated with your API
 // key. This code must be converted to it's hexadecimal
 // representation.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn malformed_body_error_names_endpoint() {
+        let body = "<html>Service Unavailable</html>";
+        let err = from_json::<Vec<String>>("GetValidPrimaryCurrencyCodes", body).unwrap_err();
+        let msg = format!("{:#}", err);
+
+        assert_that(&msg).contains("GetValidPrimaryCurrencyCodes");
+        assert_that(&msg).contains("Service Unavailable");
+        assert_that(&matches!(err.downcast_ref(), Some(ApiError::Decode(_)))).is_true();
+    }
+
+    #[test]
+    fn error_body_snippet_is_truncated() {
+        let body = "x".repeat(BODY_SNIPPET_LEN * 2);
+        let err = from_json::<Vec<String>>("GetFxRates", &body).unwrap_err();
+        let msg = format!("{}", err);
+
+        assert_that(&msg.contains(&body)).is_false();
+    }
+
+    #[tokio::test]
+    async fn client_times_out_on_hung_connection() {
+        // Accepts connections but never responds.
+        let addr: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let client = client_with_timeout(Duration::from_millis(100)).unwrap();
+        let err = client
+            .get(&format!("http://{}/", addr))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_that(&err.is_timeout()).is_true();
+    }
+
+    #[tokio::test]
+    async fn client_sends_requests_through_proxy() {
+        let proxy = mock_server(vec![ok_json("\"proxied\"")]).await;
+
+        let client = client_with_proxy(DEFAULT_TIMEOUT, &format!("http://{}", proxy)).unwrap();
+        // Nothing listens on the target, the response can only come from the proxy.
+        let body = client
+            .get("http://exchange.invalid/Public/GetFxRates")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_that(&body).is_equal_to("\"proxied\"".to_string());
+    }
+
+    #[test]
+    fn invalid_proxy_is_an_error() {
+        assert_that(&client_with_proxy(DEFAULT_TIMEOUT, "not a proxy").is_err()).is_true();
+    }
+
+    // Serves `responses`, one per connection, returning the server address.
+    pub(super) async fn mock_server<S>(responses: Vec<S>) -> std::net::SocketAddr
+    where
+        S: AsRef<str> + Send + 'static,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let addr: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                read_request(&mut stream).await;
+                stream
+                    .write_all(response.as_ref().as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        addr
+    }
+
+    // Read a whole HTTP request, headers and body, from `stream`.
+    async fn read_request(stream: &mut tokio::net::TcpStream) {
+        use tokio::io::AsyncReadExt;
+
+        let mut req = vec![];
+        let mut buf = [0; 1024];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            req.extend_from_slice(&buf[..n]);
+
+            let text = String::from_utf8_lossy(&req);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let len = text[..end]
+                    .lines()
+                    .filter_map(|l| l.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, v)| v.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if req.len() >= end + 4 + len {
+                    return;
+                }
+            }
+        }
+    }
+
+    // A 200 OK response with a JSON `body`.
+    pub(super) fn ok_json(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    const UNAVAILABLE: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+
+    #[tokio::test]
+    async fn retries_transient_errors_with_fresh_requests() {
+        let addr = mock_server(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let transport = ReqwestTransport::new(Client::new());
+        let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+        let mut built = 0;
+
+        let res = send_with_retry(&transport, RetryPolicy::default(), None, true, || {
+            built += 1; // Private methods take a new nonce here.
+            Ok(Request::Get(url.clone()))
+        })
+        .await
+        .unwrap();
+
+        assert_that(&res.body).is_equal_to("ok".to_string());
+        assert_that(&built).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_requests_on_server_error() {
+        let addr = mock_server(vec![UNAVAILABLE, OK]).await;
+        let transport = ReqwestTransport::new(Client::new());
+        let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+
+        let res = send_with_retry(&transport, RetryPolicy::default(), None, false, || {
+            Ok(Request::Get(url.clone()))
+        })
+        .await;
+
+        assert_that(&res.is_err()).is_true();
+    }
+
+    #[tokio::test]
+    async fn error_includes_exchange_message() {
+        const BAD_REQUEST: &str = "HTTP/1.1 400 Bad Request\r\nContent-Length: 29\r\nConnection: close\r\n\r\n{\"Message\":\"Invalid volume.\"}";
+        let addr = mock_server(vec![BAD_REQUEST]).await;
+        let transport = ReqwestTransport::new(Client::new());
+        let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+
+        let err = send_with_retry(&transport, RetryPolicy::default(), None, true, || {
+            Ok(Request::Get(url.clone()))
+        })
+        .await
+        .unwrap_err();
+        let api = err.downcast_ref::<ApiError>().unwrap();
+
+        assert_that(&api.is_validation()).is_true();
+        assert_that(&api.message()).is_equal_to(Some("Invalid volume.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let addr = mock_server(vec![UNAVAILABLE, UNAVAILABLE]).await;
+        let transport = ReqwestTransport::new(Client::new());
+        let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::from_millis(1),
+        };
+
+        let err = send_with_retry(&transport, policy, None, true, || {
+            Ok(Request::Get(url.clone()))
+        })
+        .await
+        .unwrap_err();
+
+        assert_that(&err.to_string().contains("503")).is_true();
+    }
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(1),
+        }
+    }
+
+    fn unavailable() -> HttpResponse {
+        HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, "")
+    }
+
+    #[tokio::test]
+    async fn public_endpoint_retries_server_error() {
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_response("GetMarketSummary", unavailable())
+                .with_json(
+                    "GetMarketSummary",
+                    include_str!("../../testdata/GetMarketSummary.json"),
+                ),
+        );
+        let api = Public::with_transport(mock.clone()).with_retry(fast_retry());
+
+        let summary = api.get_market_summary("Xbt", "Aud").await;
+
+        assert_that(&summary).is_ok();
+        assert_that(&mock.requests().len()).is_equal_to(2);
+    }
+
+    #[tokio::test]
+    async fn private_post_retries_only_idempotent_methods() {
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_response("CancelOrder", unavailable())
+                .with_json("CancelOrder", "{}")
+                .with_response("GetAccounts", unavailable())
+                .with_json("GetAccounts", "[]"),
+        );
+        let mut api = Private::new(1, "read-key", "read-secret")
+            .with_admin("admin-key", "admin-secret")
+            .with_transport(mock.clone())
+            .with_retry(fast_retry());
+
+        // Cancelling may have reached the exchange, we must not resend it.
+        let res = api
+            .cancel_order("c7347e4c-b865-4c94-8f74-d934d4b0b177")
+            .await;
+        assert_that(&res.is_err()).is_true();
+        assert_that(&mock.requests().len()).is_equal_to(1);
+
+        // Reading accounts is safe to resend.
+        let res = api.get_accounts().await;
+        assert_that(&res).is_ok();
+        assert_that(&mock.requests().len()).is_equal_to(3);
+    }
+}
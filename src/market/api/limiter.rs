@@ -0,0 +1,109 @@
+//! Client side rate limiting.
+//!
+//! A token bucket that refills at a fixed number of requests per second. Each
+//! request takes a token, waiting for one to become available if the bucket is
+//! empty. Clones share the same bucket so a single limiter can be used by both
+//! `Public` and `Private`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Limits requests to a configurable rate, see module docs.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available, at most one so requests are evenly spaced.
+    tokens: f64,
+    /// Tokens added per second.
+    rate: f64,
+    /// When `tokens` was last refilled.
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// Allow at most `requests_per_second` requests per second.
+    ///
+    /// # Panics
+    ///
+    /// If `requests_per_second` is not positive.
+    pub fn new(requests_per_second: f64) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "rate limit must be positive: {}",
+            requests_per_second
+        );
+        let bucket = Bucket {
+            tokens: 1.0,
+            rate: requests_per_second,
+            refilled: Instant::now(),
+        };
+
+        RateLimiter {
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+
+    /// Requests allowed per second.
+    pub fn rate(&self) -> f64 {
+        self.bucket.lock().expect("rate limiter lock poisoned").rate
+    }
+
+    /// Wait until a request is allowed, taking a token.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::delay_for(wait).await;
+        }
+    }
+
+    // Take a token if one is available at `now`, otherwise return how long
+    // until one will be.
+    fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().expect("rate limiter lock poisoned");
+
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(1.0);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[tokio::test]
+    async fn rapid_calls_are_spaced_out() {
+        let limiter = RateLimiter::new(50.0);
+        let start = Instant::now();
+
+        for _ in 0..6 {
+            limiter.acquire().await;
+        }
+
+        // The first call is immediate, the other five wait 20ms each.
+        assert_that(&start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(100));
+    }
+
+    #[test]
+    fn clones_share_tokens() {
+        let limiter = RateLimiter::new(1.0);
+        let other = limiter.clone();
+        let now = Instant::now();
+
+        assert_that(&limiter.try_acquire(now)).is_none();
+        assert_that(&other.try_acquire(now)).is_some();
+    }
+}
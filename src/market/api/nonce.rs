@@ -0,0 +1,104 @@
+//! Persistence of the private API nonce.
+//!
+//! The exchange rejects any request whose nonce is not greater than the last
+//! one it saw. Seeding the nonce from the clock is not enough if we restart
+//! within the same second, so we persist a nonce at or above the last used
+//! one. Callers may save a nonce ahead of the one they are using to reserve a
+//! block of nonces and avoid a write per request.
+
+use anyhow::{Context, Result};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Stores the last nonce used so that nonces keep increasing across process
+/// restarts.
+pub trait NonceStore: fmt::Debug + Send + Sync {
+    /// The last nonce saved, `None` if we have never saved one.
+    fn load(&self) -> Result<Option<u64>>;
+
+    /// Record that nonces up to `nonce` may have been used. Saving a nonce
+    /// lower than one already saved has no effect.
+    fn save(&self, nonce: u64) -> Result<()>;
+}
+
+/// Nonce store backed by a file containing the last used nonce.
+#[derive(Debug)]
+pub struct FileNonceStore {
+    path: PathBuf,
+    /// Highest nonce written to `path`.
+    last: Mutex<Option<u64>>,
+}
+
+impl FileNonceStore {
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let last = read_nonce(&path)?;
+
+        Ok(FileNonceStore {
+            path,
+            last: Mutex::new(last),
+        })
+    }
+}
+
+impl NonceStore for FileNonceStore {
+    fn load(&self) -> Result<Option<u64>> {
+        Ok(*self.last.lock().expect("nonce store lock poisoned"))
+    }
+
+    fn save(&self, nonce: u64) -> Result<()> {
+        let mut last = self.last.lock().expect("nonce store lock poisoned");
+        if matches!(*last, Some(n) if n >= nonce) {
+            return Ok(());
+        }
+
+        // Write then rename so a crash can't leave a truncated file.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, nonce.to_string())
+            .with_context(|| format!("failed to write nonce file: {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write nonce file: {}", self.path.display()))?;
+
+        *last = Some(nonce);
+        Ok(())
+    }
+}
+
+fn read_nonce(path: &Path) -> Result<Option<u64>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let s = fs::read_to_string(path)
+        .with_context(|| format!("failed to read nonce file: {}", path.display()))?;
+    let nonce = s
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid nonce file: {}", path.display()))?;
+
+    Ok(Some(nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn saved_nonce_only_increases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nonce");
+
+        let store = FileNonceStore::new(&path).unwrap();
+        assert_that(&store.load().unwrap()).is_none();
+
+        store.save(10).unwrap();
+        store.save(5).unwrap();
+
+        let reopened = FileNonceStore::new(&path).unwrap();
+        assert_that(&reopened.load().unwrap()).is_equal_to(Some(10));
+    }
+}
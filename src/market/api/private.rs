@@ -1,13 +1,43 @@
-use anyhow::{bail, Context, Result};
+use super::{
+    default_transport, from_json, send_with_retry, timestamp, FileNonceStore, HttpResponse,
+    HttpTransport, NonceStore, RateLimit, RateLimiter, Request, ReqwestTransport, RetryPolicy,
+};
+use crate::{config, market::ApiError};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{self, Stream},
+};
 use hmac::{Hmac, Mac, NewMac};
-use reqwest::{Client, StatusCode};
+use reqwest::Client;
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::Value;
 use sha2::Sha256;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{instrument, warn};
 use url::Url;
+use uuid::Uuid;
 
 const PAGE_SIZE: usize = 25;
 
+/// Default limit on the number of pages fetched by the `get_all_*` methods.
+pub const DEFAULT_MAX_PAGES: usize = 100;
+
+/// Number of nonces reserved with each write to the nonce store.
+const NONCE_BLOCK: u64 = 100;
+
 // Independent Reserve Private API methods
 //
 // Read-only Key:
@@ -28,114 +58,298 @@ const PAGE_SIZE: usize = 25;
 // PlaceLimitOrder
 // PlaceMarketOrder
 // CancelOrder
-// WithdrawDigitalCurrency
 //
 // Full access Key:
+// WithdrawDigitalCurrency
 // RequestFiatWithdrawal
 
 /// Implements the private methods for Inedependent Reserve crypto exchange API.
+///
+/// Clones share the nonce, so a clone can make requests concurrently with the
+/// original and every request still gets a unique, increasing nonce.
+///
+/// Every attempt at a request uses a new nonce, even if the request fails. If
+/// the exchange rejects a nonce as too low (e.g., another process used the
+/// same key) we resync to one above the nonce the exchange reports and send
+/// the request once more. A rejected nonce means the exchange did not act on
+/// the request, so this is safe for requests that place or cancel orders.
 #[derive(Clone, Debug)]
 pub struct Private {
-    client: Client,
+    transport: Arc<dyn HttpTransport>,
     keys: Keys,
-    nonce: u64,
+    /// Next nonce to use.
+    nonce: Arc<AtomicU64>,
+    /// Persists used nonces, if set.
+    nonce_store: Option<Arc<dyn NonceStore>>,
+    /// Highest nonce saved to `nonce_store`, we may use nonces up to this one
+    /// without writing to the store.
+    nonce_reserved: Arc<Mutex<u64>>,
+    retry: RetryPolicy,
+    limiter: Option<RateLimiter>,
+    /// Base URL of the private API.
+    url: String,
+    /// Most pages fetched by the `get_all_*` methods.
+    max_pages: usize,
+    /// Rate limit reported by the last response, if any.
+    rate_limit: Option<RateLimit>,
 }
 
 #[derive(Clone, Debug)]
 struct Keys {
     /// API key with read-only access.
     read: Key,
+    /// API key with admin access.
+    admin: Option<Key>,
+    /// API key with full access.
+    full: Option<Key>,
 }
 
-#[derive(Clone, Debug)]
+/// Access level of an API key, each tier can call all the methods of the tiers
+/// below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyTier {
+    ReadOnly,
+    Admin,
+    FullAccess,
+}
+
+impl fmt::Display for KeyTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            KeyTier::ReadOnly => "a read-only",
+            KeyTier::Admin => "an admin",
+            KeyTier::FullAccess => "a full-access",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Returned when calling an API method without a key of the required tier.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("this operation requires {required} key")]
+pub struct MissingKey {
+    pub required: KeyTier,
+}
+
+// Key tier required for each of the private API methods, as listed at the top
+// of this file.
+fn required_tier(method: &str) -> KeyTier {
+    match method {
+        "SynchDigitalCurrencyDepositAddressWithBlockchain"
+        | "PlaceLimitOrder"
+        | "PlaceMarketOrder"
+        | "CancelOrder" => KeyTier::Admin,
+        "WithdrawDigitalCurrency" | "RequestFiatWithdrawal" => KeyTier::FullAccess,
+        _ => KeyTier::ReadOnly,
+    }
+}
+
+#[derive(Clone)]
 struct Key {
     key: String,
     secret: String,
 }
 
+// Manual impl so the secret never ends up in the logs, `Keys` and `Private`
+// derive `Debug` and so use this.
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("key", &self.key)
+            .field("secret", &config::redact(&self.secret))
+            .finish()
+    }
+}
+
 impl Private {
     /// Private API URL
     const URL: &'static str = "https://api.independentreserve.com/Private";
 
     pub fn new(nonce: u64, read_key: impl ToString, read_secret: impl ToString) -> Self {
         Self {
-            client: Client::new(),
+            transport: default_transport(),
             keys: Keys {
                 read: Key {
                     key: read_key.to_string(),
                     secret: read_secret.to_string(),
                 },
+                admin: None,
+                full: None,
             },
-            nonce,
+            nonce: Arc::new(AtomicU64::new(nonce)),
+            nonce_store: None,
+            nonce_reserved: Arc::new(Mutex::new(0)),
+            retry: RetryPolicy::default(),
+            limiter: None,
+            url: Self::URL.to_string(),
+            max_pages: DEFAULT_MAX_PAGES,
+            rate_limit: None,
+        }
+    }
+
+    /// Add an admin API key, required by the admin API methods.
+    pub fn with_admin(mut self, admin_key: impl ToString, admin_secret: impl ToString) -> Self {
+        self.keys.admin = Some(Key {
+            key: admin_key.to_string(),
+            secret: admin_secret.to_string(),
+        });
+        self
+    }
+
+    /// Use `client` to make requests e.g., to set a custom timeout.
+    pub fn with_client(self, client: Client) -> Self {
+        self.with_transport(Arc::new(ReqwestTransport::new(client)))
+    }
+
+    /// Make requests using `transport` e.g., a `MockTransport` in tests.
+    pub fn with_transport(self, transport: Arc<dyn HttpTransport>) -> Self {
+        Private { transport, ..self }
+    }
+
+    /// The transport used to make requests.
+    pub fn transport(&self) -> Arc<dyn HttpTransport> {
+        Arc::clone(&self.transport)
+    }
+
+    /// Persist used nonces to the file at `path`. If the file records a nonce
+    /// at or above our current one we continue on from it, this guarantees
+    /// nonces increase across process restarts.
+    pub fn with_nonce_file(self, path: impl Into<PathBuf>) -> Result<Self> {
+        let store = FileNonceStore::new(path)?;
+        self.with_nonce_store(Arc::new(store))
+    }
+
+    /// Persist used nonces to `store`, see `with_nonce_file`.
+    pub fn with_nonce_store(self, store: Arc<dyn NonceStore>) -> Result<Self> {
+        let seed = self.nonce.load(Ordering::SeqCst);
+        let nonce = match store.load()? {
+            Some(last) if last >= seed => last + 1,
+            _ => seed,
+        };
+
+        Ok(Private {
+            nonce: Arc::new(AtomicU64::new(nonce)),
+            nonce_store: Some(store),
+            nonce_reserved: Arc::new(Mutex::new(0)),
+            ..self
+        })
+    }
+
+    /// Retry transient API errors according to `retry`.
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Private { retry, ..self }
+    }
+
+    /// Wait on `limiter` before each request, see `Public::with_rate_limiter`.
+    pub fn with_rate_limiter(self, limiter: RateLimiter) -> Self {
+        Private {
+            limiter: Some(limiter),
+            ..self
         }
     }
 
+    /// Send requests to `url` instead of the Independent Reserve private API
+    /// e.g., a staging endpoint or a local mock server.
+    pub fn with_url(self, url: impl ToString) -> Self {
+        Private {
+            url: url.to_string(),
+            ..self
+        }
+    }
+
+    /// Fetch at most `max_pages` pages in the `get_all_*` methods, these
+    /// error if there are more pages.
+    pub fn with_max_pages(self, max_pages: usize) -> Self {
+        Private { max_pages, ..self }
+    }
+
+    /// Add a full access API key, required for fiat withdrawals.
+    pub fn with_full_access(mut self, key: impl ToString, secret: impl ToString) -> Self {
+        self.keys.full = Some(Key {
+            key: key.to_string(),
+            secret: secret.to_string(),
+        });
+        self
+    }
+
+    /// Rate limit reported by the exchange in the last response, `None` if
+    /// it did not include rate limit headers.
+    pub fn last_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+    }
+
     /// API call: GetOpenOrders
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_open_orders(
         &mut self,
         base: &str,
         quote: &str,
         page_index: usize,
     ) -> Result<Orders> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetOpenOrders")?;
-        let body = self.orders_body(url.clone(), nonce, base, quote, page_index);
+        let res = self
+            .post("GetOpenOrders", |api, url, nonce| {
+                api.orders_body(url, nonce, base, quote, page_index)
+            })
+            .await?;
 
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let body = res.body;
+        let orders: Orders = from_json("GetOpenOrders", &body)?;
+
+        Ok(orders)
+    }
+
+    /// API call: GetOpenOrders, omitting the currency codes to get open orders
+    /// for all currency pairs.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_open_orders_for_all_pairs(&mut self, page_index: usize) -> Result<Orders> {
+        let res = self
+            .post("GetOpenOrders", |api, url, nonce| {
+                api.page_index_body(url, nonce, page_index)
+            })
+            .await?;
 
-        let body = res.text().await?;
-        let orders: Orders = serde_json::from_str(&body)?;
+        let body = res.body;
+        let orders: Orders = from_json("GetOpenOrders", &body)?;
 
         Ok(orders)
     }
 
     /// API call: GetClosedOrders
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_closed_orders(
         &mut self,
         base: &str,
         quote: &str,
         page_index: usize,
     ) -> Result<Orders> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetClosedOrders")?;
-        let body = self.orders_body(url.clone(), nonce, base, quote, page_index);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetClosedOrders", |api, url, nonce| {
+                api.orders_body(url, nonce, base, quote, page_index)
+            })
+            .await?;
 
-        let body = res
-            .text()
-            .await
-            .with_context(|| format!("no text: {:?}", body))?;
-        let orders: Orders = serde_json::from_str(&body)
-            .with_context(|| format!("serde failed for body: {:?}", body))?;
+        let body = res.body;
+        let orders: Orders = from_json("GetClosedOrders", &body)?;
 
         Ok(orders)
     }
 
     /// API call: GetClosedFilledOrders
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_closed_filled_orders(
         &mut self,
         base: &str,
         quote: &str,
         page_index: usize,
     ) -> Result<Orders> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetClosedFilledOrders")?;
-        let body = self.orders_body(url.clone(), nonce, base, quote, page_index);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetClosedFilledOrders", |api, url, nonce| {
+                api.orders_body(url, nonce, base, quote, page_index)
+            })
+            .await?;
 
-        let body = res.text().await?;
-        let orders: Orders = serde_json::from_str(&body)?;
+        let body = res.body;
+        let orders: Orders = from_json("GetClosedFilledOrders", &body)?;
 
         Ok(orders)
     }
@@ -145,59 +359,75 @@ impl Private {
         &mut self,
         order_guid: &str, // "c7347e4c-b865-4c94-8f74-d934d4b0b177"
     ) -> Result<OrderDetails> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetOrderDetails")?;
-        let body = self.order_guid_body(url.clone(), nonce, order_guid);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetOrderDetails", |api, url, nonce| {
+                api.order_guid_body(url, nonce, order_guid, KeyTier::ReadOnly)
+            })
+            .await?;
 
-        let body = res.text().await?;
-        let details: OrderDetails = serde_json::from_str(&body)?;
+        let body = res.body;
+        let details: OrderDetails = from_json("GetOrderDetails", &body)?;
 
         Ok(details)
     }
 
+    /// Poll GetOrderDetails every `poll` until the order reaches a terminal
+    /// state (filled, cancelled, or expired). Errors if the order is still live
+    /// after `timeout`.
+    pub async fn await_order(
+        &mut self,
+        order_guid: &str,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<OrderDetails> {
+        let guid = order_guid.to_string();
+        poll_order(poll, timeout, || {
+            // Clones share our nonce, each poll uses the next one.
+            let mut api = self.clone();
+            let guid = guid.clone();
+            async move { api.get_order_details(&guid).await }
+        })
+        .await
+        .with_context(|| format!("awaiting order: {}", order_guid))
+    }
+
     /// API call: GetAccounts
     pub async fn get_accounts(&mut self) -> Result<Accounts> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetAccounts")?;
-        let body = self.simple_body(url.clone(), nonce);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetAccounts", |api, url, nonce| api.simple_body(url, nonce))
+            .await?;
 
-        let body = res.text().await?;
-        let accounts: Accounts = serde_json::from_str(&body)?;
+        let body = res.body;
+        let accounts: Accounts = from_json("GetAccounts", &body)?;
 
         Ok(accounts)
     }
 
     /// API call: GetTransactions
+    ///
+    /// `from` and `to` are sent as JSON null when not given, as is `tx_types`.
+    /// All parameters are included in the signature message, see
+    /// `signature_message` for how null and list values are signed.
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_transactions(
         &mut self,
-        _a_ccount_guuid: &str,        // "49994921-60ec-411e-8a78-d0eba078d5e9"
-        _f_rom: Option<&str>,         // "2014-08-01T08:00:00Z", ISO 8601 standard
-        _t_o: Option<&str>,           // Same format as `from`
-        _tx_types: Option<Vec<&str>>, // ["Brokerage","Trade"]
-        _page_index: usize,
+        account_guid: &str, // "49994921-60ec-411e-8a78-d0eba078d5e9"
+        from: Option<&str>, // "2014-08-01T08:00:00Z", ISO 8601 standard
+        to: Option<&str>,   // Same format as `from`
+        tx_types: Option<Vec<TransactionType>>,
+        page_index: usize,
     ) -> Result<Transactions> {
-        // {
-        //     "apiKey":"{api-key}",
-        //     "nonce":{nonce},
-        //     "signature":"{signature}",
-        //     "accountGuid":
-        //     "fromTimestampUtc":"2014-08-01T09:00:00Z",
-        //     "toTimestampUtc":null,
-        //     "txTypes":
-        //     "pageIndex":1,
-        //     "pageSize":25
-        // }
-        unimplemented!()
+        let res = self
+            .post("GetTransactions", |api, url, nonce| {
+                let tx_types = tx_types.as_deref();
+                api.transactions_body(url, nonce, account_guid, from, to, tx_types, page_index)
+            })
+            .await?;
+
+        let body = res.body;
+        let txs: Transactions = from_json("GetTransactions", &body)?;
+
+        Ok(txs)
     }
 
     /// API call: GetDigitalCurrencyDepositAddress
@@ -205,72 +435,207 @@ impl Private {
         &mut self,
         primary_currency_code: &str, // "Xbt"
     ) -> Result<DigitalCurrencyDepositAddress> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetDigitalCurrencyDepositAddress")?;
-        let body = self.currency_body(url.clone(), nonce, primary_currency_code);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetDigitalCurrencyDepositAddress", |api, url, nonce| {
+                api.currency_body(url, nonce, primary_currency_code)
+            })
+            .await?;
 
-        let body = res.text().await?;
-        let address: DigitalCurrencyDepositAddress = serde_json::from_str(&body)?;
+        let body = res.body;
+        let address: DigitalCurrencyDepositAddress =
+            from_json("GetDigitalCurrencyDepositAddress", &body)?;
 
         Ok(address)
     }
 
     /// API call: GetDigitalCurrencyDepositAddresses
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_digital_currency_deposit_addresses(
         &mut self,
         currency: &str, // "Xbt"
         page_index: usize,
     ) -> Result<DigitalCurrencyDepositAddresses> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetDigitalCurrencyDepositAddresses")?;
-        let body = self.currency_page_index_body(url.clone(), nonce, currency, page_index);
+        let res = self
+            .post("GetDigitalCurrencyDepositAddresses", |api, url, nonce| {
+                api.currency_page_index_body(url, nonce, currency, page_index)
+            })
+            .await?;
 
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
-
-        let body = res.text().await?;
-        let addresses: DigitalCurrencyDepositAddresses = serde_json::from_str(&body)?;
+        let body = res.body;
+        let addresses: DigitalCurrencyDepositAddresses =
+            from_json("GetDigitalCurrencyDepositAddresses", &body)?;
 
         Ok(addresses)
     }
 
     /// API call: GetTrades
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_trades(&mut self, page_index: usize) -> Result<Trades> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetTrades")?;
-        let body = self.page_index_body(url.clone(), nonce, page_index);
-
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetTrades", |api, url, nonce| {
+                api.page_index_body(url, nonce, page_index)
+            })
+            .await?;
 
-        let body = res.text().await?;
-        let trades: Trades = serde_json::from_str(&body)?;
+        let body = res.body;
+        let trades: Trades = from_json("GetTrades", &body)?;
 
         Ok(trades)
     }
 
-    /// API call: GetBrokerageFees
-    pub async fn get_brokerage_fees(&mut self) -> Result<BrokerageFees> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetBrokerageFees")?;
-        let body = self.simple_body(url.clone(), nonce);
+    /// Fetch all pages of GetOpenOrders.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_all_open_orders(&mut self, base: &str, quote: &str) -> Result<Vec<Order>> {
+        let (base, quote) = (base.to_string(), quote.to_string());
+        self.all_pages(|api, page_index| {
+            let (base, quote) = (base.clone(), quote.clone());
+            async move { api.get_open_orders(&base, &quote, page_index).await }.boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetOpenOrders for all currency pairs.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_all_open_orders_for_all_pairs(&mut self) -> Result<Vec<Order>> {
+        self.all_pages(|api, page_index| {
+            async move { api.get_open_orders_for_all_pairs(page_index).await }.boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetClosedOrders.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_all_closed_orders(&mut self, base: &str, quote: &str) -> Result<Vec<Order>> {
+        let (base, quote) = (base.to_string(), quote.to_string());
+        self.all_pages(|api, page_index| {
+            let (base, quote) = (base.clone(), quote.clone());
+            async move { api.get_closed_orders(&base, &quote, page_index).await }.boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetClosedFilledOrders.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_all_closed_filled_orders(
+        &mut self,
+        base: &str,
+        quote: &str,
+    ) -> Result<Vec<Order>> {
+        let (base, quote) = (base.to_string(), quote.to_string());
+        self.all_pages(|api, page_index| {
+            let (base, quote) = (base.clone(), quote.clone());
+            async move {
+                api.get_closed_filled_orders(&base, &quote, page_index)
+                    .await
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetTransactions, see `get_transactions`.
+    pub async fn get_all_transactions(
+        &mut self,
+        account_guid: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+        tx_types: Option<Vec<TransactionType>>,
+    ) -> Result<Vec<Transaction>> {
+        let account_guid = account_guid.to_string();
+        let from = from.map(str::to_string);
+        let to = to.map(str::to_string);
+        self.all_pages(|api, page_index| {
+            let (account_guid, from, to) = (account_guid.clone(), from.clone(), to.clone());
+            let tx_types = tx_types.clone();
+            async move {
+                api.get_transactions(
+                    &account_guid,
+                    from.as_deref(),
+                    to.as_deref(),
+                    tx_types,
+                    page_index,
+                )
+                .await
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetDigitalCurrencyDepositAddresses.
+    pub async fn get_all_digital_currency_deposit_addresses(
+        &mut self,
+        currency: &str,
+    ) -> Result<Vec<DigitalCurrencyDepositAddress>> {
+        let currency = currency.to_string();
+        self.all_pages(|api, page_index| {
+            let currency = currency.clone();
+            async move {
+                api.get_digital_currency_deposit_addresses(&currency, page_index)
+                    .await
+            }
+            .boxed()
+        })
+        .await
+    }
+
+    /// Fetch all pages of GetTrades.
+    pub async fn get_all_trades(&mut self) -> Result<Vec<Trade>> {
+        self.all_pages(|api, page_index| api.get_trades(page_index).boxed())
+            .await
+    }
 
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
+    /// Stream all trades, one at a time, fetching the next page of GetTrades
+    /// only once the current one is exhausted. The stream ends after the last
+    /// page or the first error.
+    pub fn trades_stream(&mut self) -> impl Stream<Item = Result<Trade>> + '_ {
+        struct State<'a> {
+            api: &'a mut Private,
+            page_index: usize,
+            total_pages: Option<usize>,
+            trades: VecDeque<Trade>,
         }
 
-        let body = res.text().await?;
-        let fees: BrokerageFees = serde_json::from_str(&body)?;
+        let state = State {
+            api: self,
+            page_index: 1,
+            total_pages: None,
+            trades: VecDeque::new(),
+        };
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(trade) = state.trades.pop_front() {
+                    return Some((Ok(trade), state));
+                }
+                if matches!(state.total_pages, Some(total) if state.page_index > total) {
+                    return None;
+                }
+                match state.api.get_trades(state.page_index).await {
+                    Ok(page) => {
+                        state.total_pages = Some(page.total_pages);
+                        state.trades.extend(page.data);
+                        state.page_index += 1;
+                    }
+                    Err(e) => {
+                        // Don't fetch again after an error.
+                        state.total_pages = Some(0);
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+        })
+    }
+
+    /// API call: GetBrokerageFees
+    pub async fn get_brokerage_fees(&mut self) -> Result<BrokerageFees> {
+        let res = self
+            .post("GetBrokerageFees", |api, url, nonce| {
+                api.simple_body(url, nonce)
+            })
+            .await?;
+
+        let body = res.body;
+        let fees: BrokerageFees = from_json("GetBrokerageFees", &body)?;
 
         Ok(fees)
     }
@@ -280,52 +645,261 @@ impl Private {
         &mut self,
         tx_guid: &str, // "2a93732f-3f40-4685-b3bc-ff3ec326090d",
     ) -> Result<DigitalCurrencyWithdrawal> {
-        let nonce = self.inc_nonce();
-        let url = self.build_url("GetDigitalCurrencyWithdrawal")?;
-        let body = self.tx_guid_body(url.clone(), nonce, tx_guid);
+        validate_guid("transaction", tx_guid)?;
 
-        let res = self.client.post(url).json(&body).send().await?;
-        if res.status() != StatusCode::OK {
-            bail!("api call returned status: {}", res.status())
-        }
+        let res = self
+            .post("GetDigitalCurrencyWithdrawal", |api, url, nonce| {
+                api.tx_guid_body(url, nonce, tx_guid)
+            })
+            .await?;
+
+        let body = res.body;
+        let withdrawal: DigitalCurrencyWithdrawal =
+            from_json("GetDigitalCurrencyWithdrawal", &body)?;
+
+        Ok(withdrawal)
+    }
 
-        let body = res.text().await?;
-        let withdrawal: DigitalCurrencyWithdrawal = serde_json::from_str(&body)?;
+    /// API call: WithdrawDigitalCurrency
+    ///
+    /// Requires a full access key. `comment` and `destination_tag` are sent as
+    /// JSON null, and signed as empty values, when not given.
+    pub async fn withdraw_digital_currency(
+        &mut self,
+        currency: &str, // "Xbt"
+        amount: Decimal,
+        withdrawal_address: &str, // "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ"
+        comment: Option<&str>,    // "Withdrawal to cold storage"
+        destination_tag: Option<&str>, // XRP only
+    ) -> Result<DigitalCurrencyWithdrawal> {
+        self.check_key_tier("WithdrawDigitalCurrency")?;
+
+        let res = self
+            .post("WithdrawDigitalCurrency", |api, url, nonce| {
+                api.withdrawal_body(
+                    url,
+                    nonce,
+                    currency,
+                    amount,
+                    withdrawal_address,
+                    comment,
+                    destination_tag,
+                )
+            })
+            .await?;
+
+        let body = res.body;
+        let withdrawal: DigitalCurrencyWithdrawal = from_json("WithdrawDigitalCurrency", &body)?;
 
         Ok(withdrawal)
     }
 
-    /// API call: SyncDigitalCurrencyDepositAddressWithBlockchain
+    /// API call: SynchDigitalCurrencyDepositAddressWithBlockchain
     pub async fn sync_digital_currency_deposit_address_with_blockchain(
         &mut self,
-        _tx_guuid: &str,
+        deposit_address: &str,       // "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ"
+        primary_currency_code: &str, // "Bch"
     ) -> Result<DigitalCurrencyDepositAddress> {
-        // {
-        //     "apiKey":"{api-key}",
-        //     "nonce":{nonce},
-        //     "signature":"{signature}",
-        //     "depositAddress":"12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ",
-        //     "primaryCurrencyCode":"Bch"
-        // }
-        // let nonce = self.inc_nonce();
-        // let url = self.build_url("SyncDigitalCurrencyDepositAddressWithBlockchain")?;
-        // let body = self.currency_body(url.clone(), nonce, primary_currency_code);
-
-        // let res = self.client.post(url).json(&body).send().await?;
-        // if res.status() != StatusCode::OK {
-        //     bail!("api call returned status: {}", res.status())
-        // }
-
-        // let body = res.text().await?;
-        // let address: DigitalCurrencyDepositAddress = serde_json::from_str(&body)?;
-
-        // Ok(address)
-        unimplemented!()
-    }
-
-    // Build a URL from the Public API URL plus given path.
+        self.check_key_tier("SynchDigitalCurrencyDepositAddressWithBlockchain")?;
+
+        let res = self
+            .post(
+                "SynchDigitalCurrencyDepositAddressWithBlockchain",
+                |api, url, nonce| {
+                    api.deposit_address_body(url, nonce, deposit_address, primary_currency_code)
+                },
+            )
+            .await?;
+
+        let body = res.body;
+        let address: DigitalCurrencyDepositAddress =
+            from_json("SynchDigitalCurrencyDepositAddressWithBlockchain", &body)?;
+
+        Ok(address)
+    }
+
+    /// API call: PlaceLimitOrder
+    ///
+    /// If `reduce_only` is set the order is rejected, before being sent, unless
+    /// it reduces our current position in `base`, see `check_reduce_only`.
+    pub async fn place_limit_order(
+        &mut self,
+        base: &str,  // "Xbt"
+        quote: &str, // "Aud"
+        side: Side,
+        price: Decimal,
+        volume: Decimal,
+        reduce_only: bool,
+    ) -> Result<PlaceLimitOrder> {
+        self.check_key_tier("PlaceLimitOrder")?;
+        if reduce_only {
+            self.check_reduce_only(base, side, volume).await?;
+        }
+
+        let res = self
+            .post("PlaceLimitOrder", |api, url, nonce| {
+                api.limit_order_body(url, nonce, base, quote, side, price, volume)
+            })
+            .await?;
+
+        let body = res.body;
+        let order: PlaceLimitOrder = from_json("PlaceLimitOrder", &body)?;
+
+        Ok(order)
+    }
+
+    /// API call: PlaceMarketOrder
+    ///
+    /// If `reduce_only` is set the order is rejected, before being sent, unless
+    /// it reduces our current position in `base`, see `check_reduce_only`.
+    pub async fn place_market_order(
+        &mut self,
+        base: &str,  // "Xbt"
+        quote: &str, // "Aud"
+        side: Side,
+        volume: Decimal,
+        reduce_only: bool,
+    ) -> Result<PlaceMarketOrder> {
+        self.check_key_tier("PlaceMarketOrder")?;
+        if reduce_only {
+            self.check_reduce_only(base, side, volume).await?;
+        }
+
+        let res = self
+            .post("PlaceMarketOrder", |api, url, nonce| {
+                api.market_order_body(url, nonce, base, quote, side, volume)
+            })
+            .await?;
+
+        let body = res.body;
+        let order: PlaceMarketOrder = from_json("PlaceMarketOrder", &body)?;
+
+        Ok(order)
+    }
+
+    /// API call: CancelOrder
+    pub async fn cancel_order(
+        &mut self,
+        order_guid: &str, // "c7347e4c-b865-4c94-8f74-d934d4b0b177"
+    ) -> Result<CancelOrder> {
+        self.check_key_tier("CancelOrder")?;
+        validate_guid("order", order_guid)?;
+
+        let res = self
+            .post("CancelOrder", |api, url, nonce| {
+                api.order_guid_body(url, nonce, order_guid, KeyTier::Admin)
+            })
+            .await?;
+
+        let body = res.body;
+        let order: CancelOrder = from_json("CancelOrder", &body)?;
+
+        Ok(order)
+    }
+
+    // Accounts are spot, so our position in `currency` is never short and only
+    // a sell can reduce it. We sell at most our available balance, fetched via
+    // GetAccounts, which excludes funds already reserved by open orders.
+    async fn check_reduce_only(
+        &mut self,
+        currency: &str,
+        side: Side,
+        volume: Decimal,
+    ) -> Result<()> {
+        if side == Side::Bid {
+            return check_reduce_only(Decimal::default(), side, volume);
+        }
+
+        let accounts = self.get_accounts().await?;
+        let available = accounts
+            .iter()
+            .find(|a| a.currency_code().eq_ignore_ascii_case(currency))
+            .map(|a| a.available_balance())
+            .unwrap_or_default();
+
+        check_reduce_only(available, side, volume)
+    }
+
+    // POST to API `method` with the signed body built by `body`. Each attempt
+    // uses a fresh nonce since the exchange rejects reused nonces. Methods that
+    // change state (i.e., require more than a read-only key) are only retried
+    // if rate limited, any other error may have come after the exchange acted
+    // on the request. If the exchange rejects our nonce we resync it and try
+    // once more.
+    #[instrument(level = "debug", skip(self, method, body), fields(endpoint = method))]
+    async fn post<F>(&mut self, method: &str, body: F) -> Result<HttpResponse>
+    where
+        F: Fn(&Private, Url, u64) -> Result<SignedBody>,
+    {
+        let url = self.build_url(method)?;
+        let idempotent = required_tier(method) == KeyTier::ReadOnly;
+        let retry = self.retry;
+        let limiter = self.limiter.clone();
+        let transport = Arc::clone(&self.transport);
+
+        let mut resynced = false;
+        let res = loop {
+            let res = send_with_retry(
+                transport.as_ref(),
+                retry,
+                limiter.as_ref(),
+                idempotent,
+                || {
+                    let nonce = self.inc_nonce()?;
+                    let body = serde_json::to_vec(&body(self, url.clone(), nonce)?)?;
+                    Ok(Request::Post(url.clone(), body))
+                },
+            )
+            .await;
+
+            let min = match &res {
+                Err(e) if !resynced => e.downcast_ref::<ApiError>().and_then(ApiError::min_nonce),
+                _ => None,
+            };
+            match min {
+                Some(min) => {
+                    self.resync_nonce(min);
+                    resynced = true;
+                }
+                None => break res?,
+            }
+        };
+        self.rate_limit = RateLimit::from_headers(&res.headers);
+
+        Ok(res)
+    }
+
+    // Fetch every page, starting from page 1, using `fetch` and concatenate
+    // the results. Each page is a separate request and uses a new nonce.
+    async fn all_pages<P, F>(&mut self, mut fetch: F) -> Result<Vec<P::Item>>
+    where
+        P: Paged,
+        F: for<'a> FnMut(&'a mut Private, usize) -> BoxFuture<'a, Result<P>>,
+    {
+        let mut items = vec![];
+        let mut page_index = 1;
+        loop {
+            let page = fetch(self, page_index).await?;
+            let total_pages = page.total_pages();
+            items.extend(page.into_data());
+
+            if page_index >= total_pages {
+                return Ok(items);
+            }
+            if page_index >= self.max_pages {
+                bail!(
+                    "result has {} pages, more than the maximum of {}",
+                    total_pages,
+                    self.max_pages
+                );
+            }
+            page_index += 1;
+        }
+    }
+
+    // Build a URL from the Private API URL plus given path.
     fn build_url(&self, path: &str) -> Result<Url> {
-        let s = format!("{}/{}", Self::URL, path);
+        let s = format!("{}/{}", self.url, path);
         let url = Url::parse(&s)?;
 
         Ok(url)
@@ -338,139 +912,306 @@ impl Private {
         base: &str,
         quote: &str,
         page_index: usize,
-    ) -> OrdersBody {
-        let api_key = self.keys.read.key.clone();
+    ) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, ORDERS_PARAMS, &[
+            ("primaryCurrencyCode", base.into()),
+            ("secondaryCurrencyCode", quote.into()),
+            ("pageIndex", page_index.into()),
+            ("pageSize", PAGE_SIZE.into()),
+        ])
+    }
 
-        let msg = format!("{},apiKey={},nonce={},primaryCurrencyCode={},secondaryCurrencyCode={},pageIndex={},pageSize={}", url, api_key, nonce, base, quote, page_index, PAGE_SIZE);
-        let signature = self.sign_read_only(&msg);
+    fn simple_body(&self, url: Url, nonce: u64) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, SIMPLE_PARAMS, &[])
+    }
 
-        OrdersBody {
-            api_key,
-            nonce,
-            signature,
-            primary_currency_code: base.to_string(),
-            secondary_currency_code: quote.to_string(),
-            page_index,
-            page_size: 25,
-        }
+    // Used by both read-only and admin methods, hence `tier`.
+    fn order_guid_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        guid: &str,
+        tier: KeyTier,
+    ) -> Result<SignedBody> {
+        SignedBody::new(self.key(tier)?, &url, nonce, ORDER_GUID_PARAMS, &[(
+            "orderGuid",
+            guid.into(),
+        )])
     }
 
-    fn simple_body(&self, url: Url, nonce: u64) -> SimpleBody {
-        let api_key = self.keys.read.key.clone();
+    #[allow(clippy::too_many_arguments)]
+    fn transactions_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        account_guid: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+        tx_types: Option<&[TransactionType]>,
+        page_index: usize,
+    ) -> Result<SignedBody> {
+        let tx_types = serde_json::to_value(tx_types)?;
+        SignedBody::new(&self.keys.read, &url, nonce, TRANSACTIONS_PARAMS, &[
+            ("accountGuid", account_guid.into()),
+            ("fromTimestampUtc", from.into()),
+            ("toTimestampUtc", to.into()),
+            ("txTypes", tx_types),
+            ("pageIndex", page_index.into()),
+            ("pageSize", PAGE_SIZE.into()),
+        ])
+    }
 
-        let msg = format!("{},apiKey={},nonce={}", url, api_key, nonce);
-        let signature = self.sign_read_only(&msg);
+    fn currency_body(&self, url: Url, nonce: u64, currency: &str) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, CURRENCY_PARAMS, &[(
+            "primaryCurrencyCode",
+            currency.into(),
+        )])
+    }
 
-        SimpleBody {
-            api_key,
-            nonce,
-            signature,
-        }
+    fn tx_guid_body(&self, url: Url, nonce: u64, guid: &str) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, TX_GUID_PARAMS, &[(
+            "transactionGuid",
+            guid.into(),
+        )])
     }
 
-    fn order_guid_body(&self, url: Url, nonce: u64, guid: &str) -> OrderGuidBody {
-        let api_key = self.keys.read.key.clone();
+    fn page_index_body(&self, url: Url, nonce: u64, page_index: usize) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, PAGE_INDEX_PARAMS, &[
+            ("pageIndex", page_index.into()),
+            ("pageSize", PAGE_SIZE.into()),
+        ])
+    }
 
-        let msg = format!(
-            "{},apiKey={},nonce={},orderGuid={}",
-            url, api_key, nonce, guid
-        );
-        let signature = self.sign_read_only(&msg);
+    fn currency_page_index_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        currency: &str,
+        page_index: usize,
+    ) -> Result<SignedBody> {
+        SignedBody::new(&self.keys.read, &url, nonce, CURRENCY_PAGE_INDEX_PARAMS, &[
+            ("primaryCurrencyCode", currency.into()),
+            ("pageIndex", page_index.into()),
+            ("pageSize", PAGE_SIZE.into()),
+        ])
+    }
 
-        OrderGuidBody {
-            api_key,
+    fn deposit_address_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        address: &str,
+        currency: &str,
+    ) -> Result<SignedBody> {
+        SignedBody::new(
+            self.key(KeyTier::Admin)?,
+            &url,
             nonce,
-            order_guid: guid.to_string(),
-            signature,
-        }
+            DEPOSIT_ADDRESS_PARAMS,
+            &[
+                ("depositAddress", address.into()),
+                ("primaryCurrencyCode", currency.into()),
+            ],
+        )
     }
 
-    fn currency_body(&self, url: Url, nonce: u64, currency: &str) -> CurrencyBody {
-        let api_key = self.keys.read.key.clone();
+    #[allow(clippy::too_many_arguments)]
+    fn limit_order_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        base: &str,
+        quote: &str,
+        side: Side,
+        price: Decimal,
+        volume: Decimal,
+    ) -> Result<SignedBody> {
+        SignedBody::new(
+            self.key(KeyTier::Admin)?,
+            &url,
+            nonce,
+            LIMIT_ORDER_PARAMS,
+            &[
+                ("primaryCurrencyCode", base.into()),
+                ("secondaryCurrencyCode", quote.into()),
+                ("orderType", side.limit_order_type().into()),
+                ("price", price.to_string().into()),
+                ("volume", volume.to_string().into()),
+            ],
+        )
+    }
 
-        let msg = format!(
-            "{},apiKey={},nonce={},primaryCurrencyCode={}",
-            url, api_key, nonce, currency,
-        );
-        let signature = self.sign_read_only(&msg);
+    fn market_order_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        base: &str,
+        quote: &str,
+        side: Side,
+        volume: Decimal,
+    ) -> Result<SignedBody> {
+        SignedBody::new(
+            self.key(KeyTier::Admin)?,
+            &url,
+            nonce,
+            MARKET_ORDER_PARAMS,
+            &[
+                ("orderType", side.market_order_type().into()),
+                ("volume", volume.to_string().into()),
+                ("primaryCurrencyCode", base.into()),
+                ("secondaryCurrencyCode", quote.into()),
+            ],
+        )
+    }
 
-        CurrencyBody {
-            api_key,
+    #[allow(clippy::too_many_arguments)]
+    fn withdrawal_body(
+        &self,
+        url: Url,
+        nonce: u64,
+        currency: &str,
+        amount: Decimal,
+        address: &str,
+        comment: Option<&str>,
+        destination_tag: Option<&str>,
+    ) -> Result<SignedBody> {
+        SignedBody::new(
+            self.key(KeyTier::FullAccess)?,
+            &url,
             nonce,
-            primary_currency_code: currency.to_string(),
-            signature,
-        }
+            WITHDRAWAL_PARAMS,
+            &[
+                ("amount", amount.to_string().into()),
+                ("withdrawalAddress", address.into()),
+                ("comment", comment.into()),
+                ("primaryCurrencyCode", currency.into()),
+                ("destinationTag", destination_tag.into()),
+            ],
+        )
+    }
+
+    // Fail fast, before using a nonce, if we lack the key `method` requires.
+    fn check_key_tier(&self, method: &str) -> Result<()> {
+        self.key(required_tier(method)).map(|_| ())
     }
 
-    fn tx_guid_body(&self, url: Url, nonce: u64, guid: &str) -> TxGuidBody {
-        let api_key = self.keys.read.key.clone();
+    // The key to sign requests requiring `tier` with, we use the lowest tier
+    // key we have that is sufficient.
+    fn key(&self, tier: KeyTier) -> Result<&Key> {
+        let key = match tier {
+            KeyTier::ReadOnly => Some(&self.keys.read),
+            KeyTier::Admin => self.keys.admin.as_ref().or(self.keys.full.as_ref()),
+            KeyTier::FullAccess => self.keys.full.as_ref(),
+        };
+        key.ok_or_else(|| MissingKey { required: tier }.into())
+    }
 
-        let msg = format!(
-            "{},apiKey={},nonce={},transactionGuid={}",
-            url, api_key, nonce, guid
-        );
-        let signature = self.sign_read_only(&msg);
+    // Take the next nonce. Errors, so the request is not sent, if the nonce
+    // can't be persisted.
+    fn inc_nonce(&self) -> Result<u64> {
+        let nonce = self.nonce.fetch_add(1, Ordering::SeqCst);
+        self.reserve_nonce(nonce)?;
+        Ok(nonce)
+    }
 
-        TxGuidBody {
-            api_key,
-            nonce,
-            transaction_guid: guid.to_string(),
-            signature,
+    // Move our nonce past `min`, the nonce the exchange requires ours to be
+    // greater than. Never moves the nonce backwards.
+    fn resync_nonce(&self, min: u64) {
+        let next = min.saturating_add(1);
+        let prev = self.nonce.fetch_max(next, Ordering::SeqCst);
+        if prev < next {
+            warn!("nonce out of sync, resyncing from {} to {}", prev, next);
         }
     }
 
-    fn page_index_body(&self, url: Url, nonce: u64, page_index: usize) -> PageIndexBody {
-        let api_key = self.keys.read.key.clone();
+    // Make sure the nonce store covers `nonce` before we use it. Saves a block
+    // of nonces at a time so we only block on the store once per block, not
+    // once per request.
+    fn reserve_nonce(&self, nonce: u64) -> Result<()> {
+        let store = match &self.nonce_store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+
+        // Held while saving so no clone uses a nonce the store doesn't cover.
+        let mut reserved = self.nonce_reserved.lock().expect("nonce lock poisoned");
+        if nonce <= *reserved {
+            return Ok(());
+        }
 
-        let msg = format!(
-            "{},apiKey={},nonce={},pageIndex={},pageSize={}",
-            url, api_key, nonce, page_index, PAGE_SIZE
-        );
-        let signature = self.sign_read_only(&msg);
+        let block = nonce.saturating_add(NONCE_BLOCK);
+        store.save(block).context("failed to persist nonce")?;
+        *reserved = block;
+        Ok(())
+    }
+}
 
-        PageIndexBody {
-            api_key,
-            nonce,
-            page_index,
-            page_size: PAGE_SIZE,
-            signature,
+// Call `fetch` every `poll` until the returned order is in a terminal state or
+// `timeout` elapses.
+async fn poll_order<F, Fut>(poll: Duration, timeout: Duration, mut fetch: F) -> Result<OrderDetails>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<OrderDetails>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let details = fetch().await?;
+        if details.is_terminal() {
+            return Ok(details);
         }
+        if Instant::now() + poll > deadline {
+            bail!(
+                "timed out after {:?}, order status: {}",
+                timeout,
+                details.status
+            );
+        }
+        tokio::time::delay_for(poll).await;
     }
+}
 
-    fn currency_page_index_body(
-        &self,
-        url: Url,
-        nonce: u64,
-        currency: &str,
-        page_index: usize,
-    ) -> CurrencyPageIndexBody {
-        let api_key = self.keys.read.key.clone();
-
-        let msg = format!(
-            "{},apiKey={},nonce={},primaryCurrencyCode={},pageIndex={},pageSize={}",
-            url, api_key, nonce, currency, page_index, PAGE_SIZE,
-        );
-        let signature = self.sign_read_only(&msg);
+/// Order side, bid to buy and offer to sell the primary currency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Offer,
+}
 
-        CurrencyPageIndexBody {
-            api_key,
-            nonce,
-            primary_currency_code: currency.to_string(),
-            page_index,
-            page_size: PAGE_SIZE,
-            signature,
+impl Side {
+    fn limit_order_type(self) -> &'static str {
+        match self {
+            Side::Bid => "LimitBid",
+            Side::Offer => "LimitOffer",
         }
     }
 
-    // Signs a message with the read only API secret key.
-    fn sign_read_only(&self, msg: &str) -> String {
-        sign(msg, &self.keys.read.secret)
+    fn market_order_type(self) -> &'static str {
+        match self {
+            Side::Bid => "MarketBid",
+            Side::Offer => "MarketOffer",
+        }
     }
+}
+
+// Guids are always of the form c7347e4c-b865-4c94-8f74-d934d4b0b177, check
+// before making a request so we don't waste a nonce on a request the exchange
+// will reject.
+fn validate_guid(kind: &str, guid: &str) -> Result<()> {
+    Uuid::parse_str(guid).with_context(|| format!("invalid {} guid: {:?}", kind, guid))?;
+    Ok(())
+}
 
-    fn inc_nonce(&mut self) -> u64 {
-        let nonce = self.nonce;
-        self.nonce += 1;
-        nonce
+// A reduce-only order must sell no more than the `available` balance. We trade
+// spot, there is no short position for a buy to reduce.
+fn check_reduce_only(available: Decimal, side: Side, volume: Decimal) -> Result<()> {
+    match side {
+        Side::Bid => bail!("reduce-only bids are not supported, spot positions can't be short"),
+        Side::Offer if available.is_zero() || volume > available => bail!(
+            "reduce-only offer for {} exceeds available balance: {}",
+            volume,
+            available
+        ),
+        Side::Offer => Ok(()),
     }
 }
 
@@ -488,77 +1229,218 @@ fn sign(msg: &str, key: &str) -> String {
     hex::encode(code_bytes)
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct OrdersBody {
+/// Request body for the private API methods.
+///
+/// The parameters are signed, and serialized, in the order given by the API
+/// method's parameter spec (e.g. `ORDERS_PARAMS`) so the signature message and
+/// the body can not drift apart. `apiKey` and `nonce` always come first.
+#[derive(Clone, Debug)]
+pub struct SignedBody {
+    params: Vec<(&'static str, Value)>,
     signature: String,
-    api_key: String,
-    nonce: u64,
-    primary_currency_code: String,
-    secondary_currency_code: String,
-    page_index: usize,
-    page_size: usize,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SimpleBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-}
+impl SignedBody {
+    fn new(
+        key: &Key,
+        url: &Url,
+        nonce: u64,
+        spec: &[&'static str],
+        values: &[(&str, Value)],
+    ) -> Result<Self> {
+        if values.len() != spec.len() {
+            bail!(
+                "expected parameters {:?}, got {} values",
+                spec,
+                values.len()
+            );
+        }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct OrderGuidBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-    order_guid: String,
-}
+        let mut params = Vec::with_capacity(spec.len() + 2);
+        params.push(("apiKey", Value::from(key.key.as_str())));
+        params.push(("nonce", Value::from(nonce)));
+
+        for name in spec.iter() {
+            let value = values
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .ok_or_else(|| anyhow!("missing value for parameter: {}", name))?;
+            params.push((name, value));
+        }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CurrencyBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-    primary_currency_code: String,
+        let msg = signature_message(url, &params);
+        let signature = sign(&msg, &key.secret);
+
+        Ok(SignedBody { params, signature })
+    }
+
+    /// Get the value of parameter `name`.
+    fn param(&self, name: &str) -> Option<&Value> {
+        self.params.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TxGuidBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-    transaction_guid: String,
+impl Serialize for SignedBody {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.params.len() + 1))?;
+        for (name, value) in self.params.iter() {
+            map.serialize_entry(name, value)?;
+        }
+        map.serialize_entry("signature", &self.signature)?;
+        map.end()
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PageIndexBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-    page_index: usize,
-    page_size: usize,
+// The message to sign: the URL followed by each parameter as name=value, all
+// comma separated. Optional parameters are always included, a null value signs
+// as the empty string (e.g. `toTimestampUtc=`) and a list signs as its comma
+// separated elements (e.g. `txTypes=Brokerage,Trade`).
+fn signature_message(url: &Url, params: &[(&str, Value)]) -> String {
+    let mut msg = url.to_string();
+    for (name, value) in params.iter() {
+        msg.push_str(&format!(",{}={}", name, signature_value(value)));
+    }
+    msg
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CurrencyPageIndexBody {
-    signature: String,
-    api_key: String,
-    nonce: u64,
-    primary_currency_code: String,
-    page_index: usize,
-    page_size: usize,
+fn signature_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(v) => v
+            .iter()
+            .map(signature_value)
+            .collect::<Vec<String>>()
+            .join(","),
+        v => v.to_string(),
+    }
+}
+
+// Parameter specs for each of the signed request bodies, excluding `apiKey`
+// and `nonce`. The order here is the order the exchange expects the parameters
+// in the signature message.
+
+/// GetAccounts, GetBrokerageFees
+const SIMPLE_PARAMS: &[&str] = &[];
+/// GetOpenOrders, GetClosedOrders, GetClosedFilledOrders
+const ORDERS_PARAMS: &[&str] = &[
+    "primaryCurrencyCode",
+    "secondaryCurrencyCode",
+    "pageIndex",
+    "pageSize",
+];
+/// GetOrderDetails
+const ORDER_GUID_PARAMS: &[&str] = &["orderGuid"];
+/// GetTransactions
+const TRANSACTIONS_PARAMS: &[&str] = &[
+    "accountGuid",
+    "fromTimestampUtc",
+    "toTimestampUtc",
+    "txTypes",
+    "pageIndex",
+    "pageSize",
+];
+/// GetDigitalCurrencyDepositAddress
+const CURRENCY_PARAMS: &[&str] = &["primaryCurrencyCode"];
+/// GetDigitalCurrencyWithdrawal
+const TX_GUID_PARAMS: &[&str] = &["transactionGuid"];
+/// GetTrades, GetOpenOrders (all currency pairs)
+const PAGE_INDEX_PARAMS: &[&str] = &["pageIndex", "pageSize"];
+/// GetDigitalCurrencyDepositAddresses
+const CURRENCY_PAGE_INDEX_PARAMS: &[&str] = &["primaryCurrencyCode", "pageIndex", "pageSize"];
+/// SynchDigitalCurrencyDepositAddressWithBlockchain
+const DEPOSIT_ADDRESS_PARAMS: &[&str] = &["depositAddress", "primaryCurrencyCode"];
+/// PlaceLimitOrder
+const LIMIT_ORDER_PARAMS: &[&str] = &[
+    "primaryCurrencyCode",
+    "secondaryCurrencyCode",
+    "orderType",
+    "price",
+    "volume",
+];
+/// WithdrawDigitalCurrency
+const WITHDRAWAL_PARAMS: &[&str] = &[
+    "amount",
+    "withdrawalAddress",
+    "comment",
+    "primaryCurrencyCode",
+    "destinationTag",
+];
+/// PlaceMarketOrder, note there is no price.
+const MARKET_ORDER_PARAMS: &[&str] = &[
+    "orderType",
+    "volume",
+    "primaryCurrencyCode",
+    "secondaryCurrencyCode",
+];
+
+/// A single page of results from one of the paginated API methods.
+trait Paged {
+    type Item;
+
+    /// Total number of pages available.
+    fn total_pages(&self) -> usize;
+
+    /// Consume the page returning its items.
+    fn into_data(self) -> Vec<Self::Item>;
+}
+
+impl Paged for Orders {
+    type Item = Order;
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn into_data(self) -> Vec<Order> {
+        self.data
+    }
+}
+
+impl Paged for Transactions {
+    type Item = Transaction;
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn into_data(self) -> Vec<Transaction> {
+        self.data
+    }
+}
+
+impl Paged for DigitalCurrencyDepositAddresses {
+    type Item = DigitalCurrencyDepositAddress;
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn into_data(self) -> Vec<DigitalCurrencyDepositAddress> {
+        self.data
+    }
+}
+
+impl Paged for Trades {
+    type Item = Trade;
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn into_data(self) -> Vec<Trade> {
+        self.data
+    }
 }
 
 /// Returned by GetOpenOrders, GetClosedOrders, GetClosedFilledOrders
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Orders {
     total_items: usize,
     page_size: usize,
@@ -566,59 +1448,397 @@ pub struct Orders {
     data: Vec<Order>,
 }
 
+impl Orders {
+    /// Iterate the orders on this page.
+    pub fn iter(&self) -> impl Iterator<Item = &Order> {
+        self.data.iter()
+    }
+
+    /// Number of orders across all pages.
+    pub fn total_items(&self) -> usize {
+        self.total_items
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Order {
     avg_price: Decimal,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     fee_percent: Decimal,
     order_guid: String,
-    order_type: String,
+    order_type: OrderTypeKind,
     outstanding: Decimal,
     price: Option<Decimal>,
     primary_currency_code: String,
     secondary_currency_code: String,
-    status: String,
+    status: OrderStatus,
     value: Decimal,
     volume: Decimal,
 }
 
+impl Order {
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn order_type(&self) -> &OrderTypeKind {
+        &self.order_type
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn status(&self) -> &OrderStatus {
+        &self.status
+    }
+
+    /// Volume ordered.
+    pub fn volume(&self) -> Decimal {
+        self.volume
+    }
+
+    /// Average price of the filled volume.
+    pub fn avg_price(&self) -> Decimal {
+        self.avg_price
+    }
+
+    /// Value of the filled volume (secondary currency).
+    pub fn value(&self) -> Decimal {
+        self.value
+    }
+
+    pub fn fee_percent(&self) -> Decimal {
+        self.fee_percent
+    }
+
+    /// Volume not yet filled.
+    pub fn outstanding(&self) -> Decimal {
+        self.outstanding
+    }
+
+    /// Limit price, `None` for market orders.
+    pub fn price(&self) -> Option<Decimal> {
+        self.price
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+}
+
+/// Type of an order, as returned by GetValidOrderTypes.
+///
+/// Types we don't know about deserialize as `Other` so that a new type added
+/// by the exchange does not fail the whole response.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum OrderTypeKind {
+    LimitBid,
+    LimitOffer,
+    MarketBid,
+    MarketOffer,
+    Other(String),
+}
+
+impl OrderTypeKind {
+    /// True for buy orders.
+    pub fn is_bid(&self) -> bool {
+        matches!(self, OrderTypeKind::LimitBid | OrderTypeKind::MarketBid)
+    }
+
+    /// True for sell orders.
+    pub fn is_offer(&self) -> bool {
+        matches!(self, OrderTypeKind::LimitOffer | OrderTypeKind::MarketOffer)
+    }
+
+    /// The type as used by the exchange.
+    pub fn as_str(&self) -> &str {
+        match self {
+            OrderTypeKind::LimitBid => "LimitBid",
+            OrderTypeKind::LimitOffer => "LimitOffer",
+            OrderTypeKind::MarketBid => "MarketBid",
+            OrderTypeKind::MarketOffer => "MarketOffer",
+            OrderTypeKind::Other(s) => s,
+        }
+    }
+}
+
+impl From<String> for OrderTypeKind {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "LimitBid" => OrderTypeKind::LimitBid,
+            "LimitOffer" => OrderTypeKind::LimitOffer,
+            "MarketBid" => OrderTypeKind::MarketBid,
+            "MarketOffer" => OrderTypeKind::MarketOffer,
+            _ => OrderTypeKind::Other(s),
+        }
+    }
+}
+
+impl From<OrderTypeKind> for String {
+    fn from(kind: OrderTypeKind) -> Self {
+        kind.as_str().to_string()
+    }
+}
+
+impl fmt::Display for OrderTypeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Status of an order.
+///
+/// Statuses we don't know about deserialize as `Other` so that a new status
+/// added by the exchange does not fail the whole response.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum OrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    PartiallyFilledAndCancelled,
+    Cancelled,
+    PartiallyFilledAndExpired,
+    Expired,
+    Other(String),
+}
+
+impl OrderStatus {
+    /// True if an order with this status can no longer change.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled
+                | OrderStatus::Cancelled
+                | OrderStatus::PartiallyFilledAndCancelled
+                | OrderStatus::Expired
+                | OrderStatus::PartiallyFilledAndExpired
+        )
+    }
+
+    /// The status as used by the exchange.
+    pub fn as_str(&self) -> &str {
+        match self {
+            OrderStatus::Open => "Open",
+            OrderStatus::PartiallyFilled => "PartiallyFilled",
+            OrderStatus::Filled => "Filled",
+            OrderStatus::PartiallyFilledAndCancelled => "PartiallyFilledAndCancelled",
+            OrderStatus::Cancelled => "Cancelled",
+            OrderStatus::PartiallyFilledAndExpired => "PartiallyFilledAndExpired",
+            OrderStatus::Expired => "Expired",
+            OrderStatus::Other(s) => s,
+        }
+    }
+}
+
+impl From<String> for OrderStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Open" => OrderStatus::Open,
+            "PartiallyFilled" => OrderStatus::PartiallyFilled,
+            "Filled" => OrderStatus::Filled,
+            "PartiallyFilledAndCancelled" => OrderStatus::PartiallyFilledAndCancelled,
+            "Cancelled" => OrderStatus::Cancelled,
+            "PartiallyFilledAndExpired" => OrderStatus::PartiallyFilledAndExpired,
+            "Expired" => OrderStatus::Expired,
+            _ => OrderStatus::Other(s),
+        }
+    }
+}
+
+impl From<OrderStatus> for String {
+    fn from(status: OrderStatus) -> Self {
+        status.as_str().to_string()
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Returned by GetOrderDetails
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct OrderDetails {
     order_guid: String,
-    created_timestamp_utc: String,
-    #[serde(rename = "type")]
-    type_: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
+    #[serde(rename = "Type")]
+    type_: OrderTypeKind,
     volume_ordered: Decimal,
     volume_filled: Decimal,
-    price: Decimal,
+    /// Null for market orders.
+    price: Option<Decimal>,
     avg_price: Decimal,
     reserved_amount: Decimal,
-    status: String,
+    status: OrderStatus,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
+impl OrderDetails {
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn status(&self) -> &OrderStatus {
+        &self.status
+    }
+
+    pub fn volume_ordered(&self) -> Decimal {
+        self.volume_ordered
+    }
+
+    pub fn volume_filled(&self) -> Decimal {
+        self.volume_filled
+    }
+
+    pub fn avg_price(&self) -> Decimal {
+        self.avg_price
+    }
+
+    /// True if the order can no longer change i.e., it is filled, cancelled,
+    /// or expired.
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal()
+    }
+}
+
 /// Returned by GetAccounts
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Accounts(Vec<Account>);
 
+impl Accounts {
+    pub fn iter(&self) -> impl Iterator<Item = &Account> {
+        self.0.iter()
+    }
+
+    /// The account for `currency` e.g., "Aud", case insensitive.
+    pub fn account_for(&self, currency: &str) -> Option<&Account> {
+        self.iter()
+            .find(|a| a.currency_code.eq_ignore_ascii_case(currency))
+    }
+
+    /// Total balance of `currency`, including funds reserved by open orders.
+    pub fn total_in(&self, currency: &str) -> Option<Decimal> {
+        self.account_for(currency).map(Account::total_balance)
+    }
+
+    /// Balance of `currency` available for trading/withdrawal.
+    pub fn available_in(&self, currency: &str) -> Option<Decimal> {
+        self.account_for(currency).map(Account::available_balance)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Account {
     account_guid: String,
-    account_status: String,
+    account_status: AccountStatus,
     available_balance: Decimal,
     currency_code: String,
     total_balance: Decimal,
 }
 
+impl Account {
+    pub fn account_guid(&self) -> &str {
+        &self.account_guid
+    }
+
+    pub fn account_status(&self) -> &AccountStatus {
+        &self.account_status
+    }
+
+    pub fn currency_code(&self) -> &str {
+        &self.currency_code
+    }
+
+    /// Balance available for trading/withdrawal.
+    pub fn available_balance(&self) -> Decimal {
+        self.available_balance
+    }
+
+    /// Total balance, including funds reserved by open orders.
+    pub fn total_balance(&self) -> Decimal {
+        self.total_balance
+    }
+}
+
+/// Status of an account.
+///
+/// Statuses we don't know about deserialize as `Other` so that a new status
+/// added by the exchange does not fail the whole response.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum AccountStatus {
+    Active,
+    Inactive,
+    Other(String),
+}
+
+impl AccountStatus {
+    pub fn is_active(&self) -> bool {
+        *self == AccountStatus::Active
+    }
+
+    /// The status as used by the exchange.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AccountStatus::Active => "Active",
+            AccountStatus::Inactive => "Inactive",
+            AccountStatus::Other(s) => s,
+        }
+    }
+}
+
+impl From<String> for AccountStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Active" => AccountStatus::Active,
+            "Inactive" => AccountStatus::Inactive,
+            _ => AccountStatus::Other(s),
+        }
+    }
+}
+
+impl From<AccountStatus> for String {
+    fn from(status: AccountStatus) -> Self {
+        status.as_str().to_string()
+    }
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Returned by GetTransactions
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Transactions {
     total_items: usize,
     page_size: usize,
@@ -626,37 +1846,127 @@ pub struct Transactions {
     data: Vec<Transaction>,
 }
 
+impl Transactions {
+    /// Total number of pages available.
+    pub fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    /// Iterate the transactions on this page.
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.data.iter()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Transaction {
     balance: Decimal,
-    bitcoin_transaction_id: String,
-    bitcoin_transaction_output_index: String,
-    ethereum_transaction_id: String,
-    comment: String,
-    created_timestamp_utc: String,
-    credit: String,
+    bitcoin_transaction_id: Option<String>,
+    bitcoin_transaction_output_index: Option<u32>,
+    ethereum_transaction_id: Option<String>,
+    comment: Option<String>,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
+    credit: Option<Decimal>,
     currency_code: String,
-    debit: Decimal,
-    settle_timestamp_utc: String,
+    debit: Option<Decimal>,
+    #[serde(default, with = "timestamp::option")]
+    settle_timestamp_utc: Option<DateTime<Utc>>,
     status: String,
-    #[serde(rename = "type")]
-    type_: String,
+    #[serde(rename = "Type")]
+    type_: TransactionType,
+}
+
+impl Transaction {
+    /// Account balance after this transaction.
+    pub fn balance(&self) -> Decimal {
+        self.balance
+    }
+
+    /// Amount credited to the account, if any.
+    pub fn credit(&self) -> Option<Decimal> {
+        self.credit
+    }
+
+    /// Amount debited from the account, if any.
+    pub fn debit(&self) -> Option<Decimal> {
+        self.debit
+    }
+
+    pub fn currency_code(&self) -> &str {
+        &self.currency_code
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    /// Time the transaction settled, `None` if not yet settled.
+    pub fn settled(&self) -> Option<DateTime<Utc>> {
+        self.settle_timestamp_utc
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn transaction_type(&self) -> TransactionType {
+        self.type_
+    }
+}
+
+/// Transaction types, as returned by GetValidTransactionTypes.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum TransactionType {
+    AccountFee,
+    Brokerage,
+    Deposit,
+    DepositFee,
+    #[serde(rename = "GST")]
+    Gst,
+    ReferralCommission,
+    StatementFee,
+    Trade,
+    Withdrawal,
+    WithdrawalFee,
 }
 
 /// Returned by GetDigitalCurrencyDepositAddress,
 /// SyncDigitalCurrencyDepositAddressWithBlockchain
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct DigitalCurrencyDepositAddress {
     deposit_address: String,
-    last_checked_timestamp_utc: String,
-    next_update_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    last_checked_timestamp_utc: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    next_update_timestamp_utc: DateTime<Utc>,
+}
+
+impl DigitalCurrencyDepositAddress {
+    /// The deposit address.
+    pub fn deposit_address(&self) -> &str {
+        &self.deposit_address
+    }
+
+    /// True if the exchange's next scheduled update of this address is before
+    /// `now` i.e., the address may not yet reflect recent blockchain activity.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.next_update_timestamp_utc < now
+    }
 }
 
 /// Returned by GetDigitalCurrencyDepositAddresses
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct DigitalCurrencyDepositAddresses {
     total_items: usize,
     page_size: usize,
@@ -667,6 +1977,7 @@ pub struct DigitalCurrencyDepositAddresses {
 /// Returned by GetTrades
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Trades {
     total_items: usize,
     page_size: usize,
@@ -676,116 +1987,1656 @@ pub struct Trades {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Trade {
     trade_guid: String,
-    trade_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    trade_timestamp_utc: DateTime<Utc>,
     order_guid: String,
-    order_type: String,
-    order_timestamp_utc: String,
+    order_type: OrderTypeKind,
+    #[serde(with = "timestamp")]
+    order_timestamp_utc: DateTime<Utc>,
     volume_traded: Decimal,
     price: Decimal,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
+impl Trade {
+    pub fn trade_guid(&self) -> &str {
+        &self.trade_guid
+    }
+
+    /// Time the trade executed.
+    pub fn traded(&self) -> DateTime<Utc> {
+        self.trade_timestamp_utc
+    }
+
+    /// The order this trade filled, in part or in full.
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn order_type(&self) -> &OrderTypeKind {
+        &self.order_type
+    }
+
+    /// Time the order was placed.
+    pub fn order_created(&self) -> DateTime<Utc> {
+        self.order_timestamp_utc
+    }
+
+    pub fn volume_traded(&self) -> Decimal {
+        self.volume_traded
+    }
+
+    pub fn price(&self) -> Decimal {
+        self.price
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+}
+
 /// Returned by GetBrokerageFees
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct BrokerageFees(Vec<Fees>);
 
-#[derive(Debug, Deserialize, Serialize)]
+impl BrokerageFees {
+    /// Brokerage fee (as a fraction) charged for trades in `currency`.
+    pub fn fee_for(&self, currency: &str) -> Option<Decimal> {
+        self.0
+            .iter()
+            .find(|f| f.currency_code.eq_ignore_ascii_case(currency))
+            .map(|f| f.fee)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Fees {
-    currency_code: String,
-    fee: Decimal,
+    pub currency_code: String,
+    pub fee: Decimal,
 }
 
 /// Returned by PlaceLimitOrder
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct PlaceLimitOrder {
     order_guid: String,
-    created_timestamp_utc: String,
-    #[serde(rename = "type")]
-    type_: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
+    #[serde(rename = "Type")]
+    type_: OrderTypeKind,
     volume_ordered: Decimal,
     volume_filled: Decimal,
     price: Decimal,
     reserved_amount: Decimal,
-    status: String,
+    status: OrderStatus,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
+impl PlaceLimitOrder {
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn order_type(&self) -> &OrderTypeKind {
+        &self.type_
+    }
+
+    pub fn status(&self) -> &OrderStatus {
+        &self.status
+    }
+
+    pub fn volume_ordered(&self) -> Decimal {
+        self.volume_ordered
+    }
+
+    pub fn volume_filled(&self) -> Decimal {
+        self.volume_filled
+    }
+
+    /// Limit price.
+    pub fn price(&self) -> Decimal {
+        self.price
+    }
+
+    /// Funds reserved by the order.
+    pub fn reserved_amount(&self) -> Decimal {
+        self.reserved_amount
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+
+    /// True if the order can no longer change, see `OrderDetails::is_terminal`.
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal()
+    }
+}
+
 /// Returned by PlaceMarketOrder
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct PlaceMarketOrder {
     order_guid: String,
-    created_timestamp_utc: String,
-    #[serde(rename = "type")]
-    type_: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
+    #[serde(rename = "Type")]
+    type_: OrderTypeKind,
     volume_ordered: Decimal,
     volume_filled: Decimal,
     reserved_amount: Decimal,
-    status: String,
+    status: OrderStatus,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
-/// Returned by CancelOrder
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct CancelOrder {
-    order_guid: String,
-    created_timestamp_utc: String,
-    #[serde(rename = "type")]
-    type_: String,
-    volume_ordered: Decimal,
-    volume_filled: Decimal,
-    price: Decimal,
-    reserved_amount: Decimal,
-    status: String,
+impl PlaceMarketOrder {
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn order_type(&self) -> &OrderTypeKind {
+        &self.type_
+    }
+
+    pub fn status(&self) -> &OrderStatus {
+        &self.status
+    }
+
+    pub fn volume_ordered(&self) -> Decimal {
+        self.volume_ordered
+    }
+
+    pub fn volume_filled(&self) -> Decimal {
+        self.volume_filled
+    }
+
+    /// Funds reserved by the order.
+    pub fn reserved_amount(&self) -> Decimal {
+        self.reserved_amount
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+
+    /// True if the order can no longer change, see `OrderDetails::is_terminal`.
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal()
+    }
+}
+
+/// Returned by CancelOrder
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
+pub struct CancelOrder {
+    order_guid: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
+    #[serde(rename = "Type")]
+    type_: OrderTypeKind,
+    volume_ordered: Decimal,
+    volume_filled: Decimal,
+    price: Decimal,
+    reserved_amount: Decimal,
+    status: OrderStatus,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
+impl CancelOrder {
+    pub fn order_guid(&self) -> &str {
+        &self.order_guid
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn order_type(&self) -> &OrderTypeKind {
+        &self.type_
+    }
+
+    /// Order status as reported by the cancel.
+    pub fn status(&self) -> &OrderStatus {
+        &self.status
+    }
+
+    pub fn volume_ordered(&self) -> Decimal {
+        self.volume_ordered
+    }
+
+    pub fn volume_filled(&self) -> Decimal {
+        self.volume_filled
+    }
+
+    pub fn price(&self) -> Decimal {
+        self.price
+    }
+
+    /// Funds still reserved by the order.
+    pub fn reserved_amount(&self) -> Decimal {
+        self.reserved_amount
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+
+    /// True if the order can no longer change, see `OrderDetails::is_terminal`.
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal()
+    }
+}
+
 /// Returned by WithdrawDigitalCurrency
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct DigitalCurrencyWithdrawal {
     transaction_guid: String,
     primary_currency_code: String,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     amount: Amount,
     destination: Destination,
     status: String,
-    transaction: String,
+    transaction: Option<String>,
+}
+
+impl DigitalCurrencyWithdrawal {
+    pub fn transaction_guid(&self) -> &str {
+        &self.transaction_guid
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn amount(&self) -> Amount {
+        self.amount
+    }
+
+    pub fn destination(&self) -> &Destination {
+        &self.destination
+    }
+
+    /// Withdrawal status e.g., "Pending".
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// Blockchain transaction, `None` until the withdrawal is sent.
+    pub fn transaction(&self) -> Option<&str> {
+        self.transaction.as_deref()
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Amount {
     total: Decimal,
     fee: Decimal,
 }
 
+impl Amount {
+    /// Total amount withdrawn, including the fee.
+    pub fn total(&self) -> Decimal {
+        self.total
+    }
+
+    pub fn fee(&self) -> Decimal {
+        self.fee
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Destination {
     address: String,
-    tag: String,
+    tag: Option<String>,
+}
+
+impl Destination {
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Destination tag or memo, for currencies that use one.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
 }
 
 /// Returned by RequestFiatwithdrawal
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct RequestFiatwithdrawal {
     account_guid: String,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     fiat_withdrawal_request_guid: String,
     status: String,
     total_withdrawal_amonut: Decimal,
     fee_amount: Decimal,
     currency: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::api::{
+        parse_timestamp,
+        tests::{mock_server, ok_json},
+        MockTransport,
+    };
+    use futures::{StreamExt, TryStreamExt};
+    use reqwest::StatusCode;
+    use spectral::prelude::*;
+    use std::cell::Cell;
+
+    fn deposit_address(next_update: &str) -> DigitalCurrencyDepositAddress {
+        DigitalCurrencyDepositAddress {
+            deposit_address: "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ".to_string(),
+            last_checked_timestamp_utc: parse_timestamp("2020-04-01T00:00:00Z").unwrap(),
+            next_update_timestamp_utc: parse_timestamp(next_update).unwrap(),
+        }
+    }
+
+    #[test]
+    fn deposit_address_is_stale_once_next_update_has_passed() {
+        let now = parse_timestamp("2020-04-02T00:00:00Z").unwrap();
+
+        let stale = deposit_address("2020-04-01T12:00:00.5437386Z");
+        assert_that(&stale.is_stale(now)).is_true();
+
+        let fresh = deposit_address("2020-04-02T12:00:00Z");
+        assert_that(&fresh.is_stale(now)).is_false();
+    }
+
+    #[test]
+    fn transaction_fields_are_typed() {
+        let tx: Transaction = serde_json::from_str(
+            r#"{
+                "Balance": 199.0,
+                "BitcoinTransactionId": null,
+                "BitcoinTransactionOutputIndex": null,
+                "EthereumTransactionId": null,
+                "Comment": null,
+                "CreatedTimestampUtc": "2014-08-03T05:33:55.865Z",
+                "Credit": null,
+                "CurrencyCode": "Aud",
+                "Debit": 2.0,
+                "SettleTimestampUtc": "2014-08-03T05:34:02Z",
+                "Status": "Confirmed",
+                "Type": "WithdrawalFee"
+            }"#,
+        )
+        .unwrap();
+
+        assert_that(&tx.balance()).is_equal_to(Decimal::from(199));
+        assert_that(&tx.credit()).is_none();
+        assert_that(&tx.debit()).is_equal_to(Some(Decimal::from(2)));
+        assert_that(&tx.transaction_type()).is_equal_to(TransactionType::WithdrawalFee);
+        assert_that(&tx.created())
+            .is_equal_to(parse_timestamp("2014-08-03T05:33:55.865Z").unwrap());
+        assert_that(&tx.settled())
+            .is_equal_to(Some(parse_timestamp("2014-08-03T05:34:02Z").unwrap()));
+    }
+
+    #[test]
+    fn brokerage_fee_lookup() {
+        let fees: BrokerageFees = serde_json::from_str(
+            r#"[
+                {"CurrencyCode": "Xbt", "Fee": 0.005},
+                {"CurrencyCode": "Eth", "Fee": 0.004}
+            ]"#,
+        )
+        .unwrap();
+
+        let want = Decimal::new(5, 3);
+        assert_that(&fees.fee_for("Xbt")).is_equal_to(Some(want));
+        assert_that(&fees.fee_for("Ltc")).is_none();
+    }
+
+    #[test]
+    fn sync_deposit_address_requires_admin_key() {
+        let url = Url::parse("https://api.independentreserve.com/Private/Foo").unwrap();
+        let address = "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ";
+
+        let api = Private::new(1, "read-key", "read-secret");
+        assert_that(&api.deposit_address_body(url.clone(), 1, address, "Xbt")).is_err();
+
+        let api = api.with_admin("admin-key", "admin-secret");
+        let body = api
+            .deposit_address_body(url, 1, address, "Xbt")
+            .expect("admin key is set");
+        assert_that(&body.param("apiKey")).is_equal_to(Some(&Value::from("admin-key")));
+    }
+
+    fn test_body(spec: &[&'static str]) -> (SignedBody, String) {
+        let key = Key {
+            key: "api-key".to_string(),
+            secret: "api-secret".to_string(),
+        };
+        let url = Url::parse("https://api.independentreserve.com/Private/GetOpenOrders").unwrap();
+        let values = [
+            ("primaryCurrencyCode", "Xbt".into()),
+            ("secondaryCurrencyCode", "Aud".into()),
+            ("pageIndex", 1.into()),
+            ("pageSize", 25.into()),
+        ];
+
+        let body = SignedBody::new(&key, &url, 42, spec, &values).unwrap();
+        let msg = signature_message(&url, &body.params);
+        (body, msg)
+    }
+
+    // Parameter names in the order they appear in the signature message.
+    fn message_order(msg: &str) -> Vec<String> {
+        msg.split(',')
+            .skip(1) // URL
+            .map(|p| p.split('=').next().unwrap().to_string())
+            .collect()
+    }
+
+    // Parameter names in the order they appear in the serialized JSON body.
+    fn body_order(body: &SignedBody) -> Vec<String> {
+        let json = serde_json::to_string(body).unwrap();
+        let mut names = vec![];
+        let mut rest = json.as_str();
+        while let Some(start) = rest.find("\":") {
+            let name_start = rest[..start].rfind('"').unwrap() + 1;
+            names.push(rest[name_start..start].to_string());
+            rest = &rest[start + 2..];
+        }
+        names.retain(|n| n != "signature");
+        names
+    }
+
+    #[test]
+    fn signature_message_follows_param_spec() {
+        let (body, msg) = test_body(ORDERS_PARAMS);
+
+        assert_that(&msg.as_str()).is_equal_to(
+            "https://api.independentreserve.com/Private/GetOpenOrders,apiKey=api-key,nonce=42,\
+             primaryCurrencyCode=Xbt,secondaryCurrencyCode=Aud,pageIndex=1,pageSize=25",
+        );
+        assert_that(&body_order(&body)).is_equal_to(message_order(&msg));
+    }
+
+    #[test]
+    fn open_orders_for_all_pairs_omits_currency_codes() {
+        let api = Private::new(1, "read-key", "read-secret");
+        let url = Url::parse("https://api.independentreserve.com/Private/GetOpenOrders").unwrap();
+
+        let body = api.page_index_body(url.clone(), 42, 1).unwrap();
+        let msg = signature_message(&url, &body.params);
+
+        assert_that(&msg.as_str()).is_equal_to(
+            "https://api.independentreserve.com/Private/GetOpenOrders,apiKey=read-key,nonce=42,\
+             pageIndex=1,pageSize=25",
+        );
+        assert_that(&body.param("primaryCurrencyCode")).is_none();
+        assert_that(&body.param("secondaryCurrencyCode")).is_none();
+        assert_that(&body_order(&body)).is_equal_to(message_order(&msg));
+    }
+
+    #[test]
+    fn reordering_param_spec_changes_signature_deterministically() {
+        let reordered: &[&str] = &[
+            "pageSize",
+            "pageIndex",
+            "secondaryCurrencyCode",
+            "primaryCurrencyCode",
+        ];
+
+        let (body, _) = test_body(ORDERS_PARAMS);
+        let (again, _) = test_body(ORDERS_PARAMS);
+        let (other, other_msg) = test_body(reordered);
+
+        assert_that(&body.signature).is_equal_to(&again.signature);
+        assert_that(&body.signature).is_not_equal_to(&other.signature);
+        assert_that(&body_order(&other)).is_equal_to(message_order(&other_msg));
+    }
+
+    #[test]
+    fn signed_body_rejects_missing_params() {
+        let key = Key {
+            key: "api-key".to_string(),
+            secret: "api-secret".to_string(),
+        };
+        let url = Url::parse("https://api.independentreserve.com/Private/GetTrades").unwrap();
+        let values = [("pageIndex", 1.into()), ("pageLength", 25.into())];
+
+        let res = SignedBody::new(&key, &url, 1, PAGE_INDEX_PARAMS, &values);
+        assert_that(&res.is_err()).is_true();
+    }
+
+    fn order_details(status: &str) -> OrderDetails {
+        OrderDetails {
+            order_guid: "c7347e4c-b865-4c94-8f74-d934d4b0b177".to_string(),
+            created_timestamp_utc: parse_timestamp("2020-04-01T00:00:00Z").unwrap(),
+            type_: OrderTypeKind::LimitBid,
+            volume_ordered: Decimal::from(1),
+            volume_filled: Decimal::from(0),
+            price: Some(Decimal::from(10_000)),
+            avg_price: Decimal::from(10_000),
+            reserved_amount: Decimal::from(10_000),
+            status: OrderStatus::from(status.to_string()),
+            primary_currency_code: "Xbt".to_string(),
+            secondary_currency_code: "Aud".to_string(),
+        }
+    }
+
+    // Mock GetOrderDetails that reports the order open for the first `open` calls.
+    async fn mock_order_details(calls: &Cell<usize>, open: usize) -> Result<OrderDetails> {
+        calls.set(calls.get() + 1);
+        if calls.get() <= open {
+            return Ok(order_details("Open"));
+        }
+        Ok(order_details("Filled"))
+    }
+
+    #[tokio::test]
+    async fn poll_order_resolves_once_filled() {
+        let calls = Cell::new(0);
+        let poll = Duration::from_millis(1);
+        let timeout = Duration::from_secs(5);
+
+        let got = poll_order(poll, timeout, || mock_order_details(&calls, 2))
+            .await
+            .unwrap();
+
+        assert_that(got.status()).is_equal_to(&OrderStatus::Filled);
+        assert_that(&calls.get()).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn await_order_polls_until_filled() {
+        let open = serde_json::to_string(&order_details("Open")).unwrap();
+        let filled = serde_json::to_string(&order_details("Filled")).unwrap();
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_json("GetOrderDetails", open.clone())
+                .with_json("GetOrderDetails", open)
+                .with_json("GetOrderDetails", filled),
+        );
+        let mut api = Private::new(1, "read-key", "read-secret").with_transport(mock.clone());
+
+        let got = api
+            .await_order(
+                "c7347e4c-b865-4c94-8f74-d934d4b0b177",
+                Duration::from_millis(1),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_that(got.status()).is_equal_to(&OrderStatus::Filled);
+        assert_that(&mock.requests().len()).is_equal_to(3);
+        let nonces: Vec<u64> = mock
+            .bodies()
+            .iter()
+            .map(|b| {
+                serde_json::from_str::<Value>(b).unwrap()["nonce"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_that(&nonces.windows(2).all(|w| w[0] < w[1])).is_true();
+    }
+
+    #[tokio::test]
+    async fn poll_order_times_out_while_open() {
+        let calls = Cell::new(0);
+        let poll = Duration::from_millis(5);
+        let timeout = Duration::from_millis(20);
+
+        let got = poll_order(poll, timeout, || mock_order_details(&calls, usize::MAX)).await;
+
+        assert_that(&got.is_err()).is_true();
+    }
+
+    #[test]
+    fn reduce_only_buy_always_rejected() {
+        let volume = Decimal::from(1);
+
+        assert_that(&check_reduce_only(Decimal::from(2), Side::Bid, volume).is_err()).is_true();
+    }
+
+    #[test]
+    fn reduce_only_sell_limited_to_available() {
+        let volume = Decimal::from(1);
+
+        assert_that(&check_reduce_only(Decimal::from(0), Side::Offer, volume).is_err()).is_true();
+        assert_that(&check_reduce_only(Decimal::from(1), Side::Offer, volume).is_ok()).is_true();
+        assert_that(&check_reduce_only(Decimal::new(5, 1), Side::Offer, volume).is_err()).is_true();
+    }
+
+    // Xbt account with 5 in total, 1 of which is available (the rest is
+    // reserved by open offers).
+    const RESERVED_ACCOUNTS: &str = r#"[{
+        "AccountGuid": "66dcac65-bf07-4e68-ad46-838f51100424",
+        "AccountStatus": "Active",
+        "AvailableBalance": 1.0,
+        "CurrencyCode": "Xbt",
+        "TotalBalance": 5.0
+    }]"#;
+
+    const PLACE_MARKET_ORDER: &str = r#"{
+        "OrderGuid": "5c8885cd-5384-4e05-b397-9f5119353e10",
+        "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+        "Type": "MarketOffer",
+        "VolumeOrdered": 0.5,
+        "VolumeFilled": 0.0,
+        "ReservedAmount": 0.5,
+        "Status": "Open",
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Usd"
+    }"#;
+
+    #[tokio::test]
+    async fn withdrawal_status_is_readable() {
+        let mock = Arc::new(MockTransport::new().with_json(
+            "GetDigitalCurrencyWithdrawal",
+            r#"{
+                "TransactionGuid": "dc932e19-562b-4c50-821e-a73fd048b93b",
+                "PrimaryCurrencyCode": "Xbt",
+                "CreatedTimestampUtc": "2020-04-01T05:26:30.5093622+00:00",
+                "Amount": {"Total": 0.1231, "Fee": 0.0001},
+                "Destination": {"Address": "12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ", "Tag": null},
+                "Status": "Pending",
+                "Transaction": null
+            }"#,
+        ));
+        let mut api = Private::new(1, "read-key", "read-secret").with_transport(mock);
+
+        let withdrawal = api
+            .get_digital_currency_withdrawal("dc932e19-562b-4c50-821e-a73fd048b93b")
+            .await
+            .unwrap();
+
+        assert_that(&withdrawal.transaction_guid())
+            .is_equal_to("dc932e19-562b-4c50-821e-a73fd048b93b");
+        assert_that(&withdrawal.status()).is_equal_to("Pending");
+        assert_that(&withdrawal.amount().total()).is_equal_to(Decimal::new(1231, 4));
+        assert_that(&withdrawal.amount().fee()).is_equal_to(Decimal::new(1, 4));
+        assert_that(&withdrawal.destination().address())
+            .is_equal_to("12a7FbBzSGvJd36wNesAxAksLXMWm4oLUJ");
+        assert_that(&withdrawal.destination().tag()).is_none();
+        assert_that(&withdrawal.transaction()).is_none();
+    }
+
+    #[test]
+    fn placed_orders_expose_guid_and_status() {
+        let market: PlaceMarketOrder = serde_json::from_str(PLACE_MARKET_ORDER).unwrap();
+        assert_that(&market.order_guid()).is_equal_to("5c8885cd-5384-4e05-b397-9f5119353e10");
+        assert_that(market.status()).is_equal_to(&OrderStatus::Open);
+        assert_that(market.order_type()).is_equal_to(&OrderTypeKind::MarketOffer);
+        assert_that(&market.volume_ordered()).is_equal_to(Decimal::new(5, 1));
+        assert_that(&market.volume_filled()).is_equal_to(Decimal::from(0));
+        assert_that(&market.is_terminal()).is_false();
+
+        let limit: PlaceLimitOrder = serde_json::from_str(
+            r#"{
+                "OrderGuid": "c7347e4c-b865-4c94-8f74-d934d4b0b177",
+                "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+                "Type": "LimitBid",
+                "VolumeOrdered": 5.0,
+                "VolumeFilled": 5.0,
+                "Price": 100.0,
+                "ReservedAmount": 0.0,
+                "Status": "Filled",
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Usd"
+            }"#,
+        )
+        .unwrap();
+        assert_that(&limit.order_guid()).is_equal_to("c7347e4c-b865-4c94-8f74-d934d4b0b177");
+        assert_that(&limit.price()).is_equal_to(Decimal::from(100));
+        assert_that(&limit.volume_filled()).is_equal_to(Decimal::from(5));
+        assert_that(&limit.is_terminal()).is_true();
+    }
+
+    fn reduce_only_api() -> (Private, Arc<MockTransport>) {
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_json("GetAccounts", RESERVED_ACCOUNTS)
+                .with_json("PlaceMarketOrder", PLACE_MARKET_ORDER),
+        );
+        let api = Private::new(1, "read-key", "read-secret")
+            .with_full_access("full-key", "full-secret")
+            .with_transport(mock.clone());
+        (api, mock)
+    }
+
+    fn methods(mock: &MockTransport) -> Vec<String> {
+        mock.requests()
+            .iter()
+            .filter_map(|url| url.path_segments()?.next_back().map(str::to_string))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reduce_only_offer_over_available_is_not_sent() {
+        let (mut api, mock) = reduce_only_api();
+
+        // Within the total balance but over what open offers leave available.
+        let volume = Decimal::from(2);
+        let res = api
+            .place_market_order("Xbt", "Usd", Side::Offer, volume, true)
+            .await;
+        assert_that(&res.is_err()).is_true();
+
+        let price = Decimal::from(10_000);
+        let res = api
+            .place_limit_order("Xbt", "Usd", Side::Offer, price, volume, true)
+            .await;
+        assert_that(&res.is_err()).is_true();
+
+        assert_that(&methods(&mock))
+            .is_equal_to(vec!["GetAccounts".to_string(), "GetAccounts".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn reduce_only_bid_is_not_sent() {
+        let (mut api, mock) = reduce_only_api();
+
+        let res = api
+            .place_market_order("Xbt", "Usd", Side::Bid, Decimal::from(1), true)
+            .await;
+
+        assert_that(&res.is_err()).is_true();
+        assert_that(&mock.requests()).is_empty();
+    }
+
+    #[tokio::test]
+    async fn reduce_only_offer_within_available_is_sent() {
+        let (mut api, mock) = reduce_only_api();
+
+        let res = api
+            .place_market_order("Xbt", "Usd", Side::Offer, Decimal::new(5, 1), true)
+            .await;
+
+        assert_that(&res).is_ok();
+        assert_that(&methods(&mock)).is_equal_to(vec![
+            "GetAccounts".to_string(),
+            "PlaceMarketOrder".to_string(),
+        ]);
+    }
+
+    // Rebuild the signature message from the serialized JSON body, in the order
+    // the keys appear in the JSON, and check it matches the body's signature.
+    fn assert_body_in_sync(url: &Url, body: &SignedBody, secret: &str, spec: &[&str]) {
+        let names = body_order(body);
+
+        let mut want = vec!["apiKey".to_string(), "nonce".to_string()];
+        want.extend(spec.iter().map(|s| s.to_string()));
+        assert_that(&names).is_equal_to(&want);
+
+        let json: Value = serde_json::to_value(body).unwrap();
+        let params: Vec<(&str, Value)> = names
+            .iter()
+            .map(|n| (n.as_str(), json[n.as_str()].clone()))
+            .collect();
+        let msg = signature_message(url, &params);
+
+        assert_that(&json["signature"].as_str()).is_equal_to(Some(sign(&msg, secret).as_str()));
+    }
+
+    #[test]
+    fn every_signed_body_serializes_in_signing_order() {
+        let url = Url::parse("https://api.independentreserve.com/Private/Foo").unwrap();
+        let api = Private::new(1, "read-key", "read-secret")
+            .with_admin("admin-key", "admin-secret")
+            .with_full_access("full-key", "full-secret");
+        let read = "read-secret";
+        let admin = "admin-secret";
+        let one = Decimal::from(1);
+
+        let bodies: Vec<(&str, Result<SignedBody>, &str, &[&str])> = vec![
+            (
+                "orders",
+                api.orders_body(url.clone(), 1, "Xbt", "Aud", 1),
+                read,
+                ORDERS_PARAMS,
+            ),
+            (
+                "simple",
+                api.simple_body(url.clone(), 1),
+                read,
+                SIMPLE_PARAMS,
+            ),
+            (
+                "order_guid",
+                api.order_guid_body(url.clone(), 1, "guid", KeyTier::ReadOnly),
+                read,
+                ORDER_GUID_PARAMS,
+            ),
+            (
+                "transactions",
+                api.transactions_body(url.clone(), 1, "guid", Some("from"), None, None, 1),
+                read,
+                TRANSACTIONS_PARAMS,
+            ),
+            (
+                "currency",
+                api.currency_body(url.clone(), 1, "Xbt"),
+                read,
+                CURRENCY_PARAMS,
+            ),
+            (
+                "tx_guid",
+                api.tx_guid_body(url.clone(), 1, "guid"),
+                read,
+                TX_GUID_PARAMS,
+            ),
+            (
+                "page_index",
+                api.page_index_body(url.clone(), 1, 1),
+                read,
+                PAGE_INDEX_PARAMS,
+            ),
+            (
+                "currency_page_index",
+                api.currency_page_index_body(url.clone(), 1, "Xbt", 1),
+                read,
+                CURRENCY_PAGE_INDEX_PARAMS,
+            ),
+            (
+                "deposit_address",
+                api.deposit_address_body(url.clone(), 1, "address", "Xbt"),
+                admin,
+                DEPOSIT_ADDRESS_PARAMS,
+            ),
+            (
+                "limit_order",
+                api.limit_order_body(url.clone(), 1, "Xbt", "Aud", Side::Bid, one, one),
+                admin,
+                LIMIT_ORDER_PARAMS,
+            ),
+            (
+                "withdrawal",
+                api.withdrawal_body(url.clone(), 1, "Xbt", one, "address", None, Some("tag")),
+                "full-secret",
+                WITHDRAWAL_PARAMS,
+            ),
+            (
+                "market_order",
+                api.market_order_body(url.clone(), 1, "Xbt", "Aud", Side::Offer, one),
+                admin,
+                MARKET_ORDER_PARAMS,
+            ),
+        ];
+
+        for (name, body, secret, spec) in bodies {
+            let body = body.unwrap_or_else(|e| panic!("{} body: {}", name, e));
+            assert_body_in_sync(&url, &body, secret, spec);
+        }
+    }
+
+    fn assert_missing_key<T: fmt::Debug>(res: Result<T>, required: KeyTier) {
+        let err = res.expect_err("read-only key should be rejected");
+        assert_that(&err.downcast_ref::<MissingKey>()).is_equal_to(Some(&MissingKey { required }));
+    }
+
+    #[tokio::test]
+    async fn mutating_methods_require_admin_key() {
+        let one = Decimal::from(1);
+        let mut api = Private::new(1, "read-key", "read-secret");
+
+        assert_missing_key(
+            api.sync_digital_currency_deposit_address_with_blockchain("address", "Xbt")
+                .await,
+            KeyTier::Admin,
+        );
+        assert_missing_key(
+            api.place_limit_order("Xbt", "Aud", Side::Bid, one, one, false)
+                .await,
+            KeyTier::Admin,
+        );
+        assert_missing_key(
+            api.place_market_order("Xbt", "Aud", Side::Offer, one, true)
+                .await,
+            KeyTier::Admin,
+        );
+
+        // Checked before any request is made, no nonces used.
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(1);
+    }
+
+    #[test]
+    fn key_tiers() {
+        let api = Private::new(1, "read-key", "read-secret");
+        assert_that(&api.key(KeyTier::ReadOnly)).is_ok();
+        assert_missing_key(api.key(KeyTier::FullAccess), KeyTier::FullAccess);
+
+        let api = api.with_full_access("full-key", "full-secret");
+        let admin = api.key(KeyTier::Admin).unwrap();
+        assert_that(&admin.key.as_str()).is_equal_to("full-key");
+
+        let err = MissingKey {
+            required: KeyTier::Admin,
+        };
+        assert_that(&err.to_string().as_str()).is_equal_to("this operation requires an admin key");
+    }
+
+    #[test]
+    fn cancel_order_body_signs_guid() {
+        let url = Url::parse("https://api.independentreserve.com/Private/CancelOrder").unwrap();
+        let guid = "c7347e4c-b865-4c94-8f74-d934d4b0b177";
+        let api =
+            Private::new(1, "read-key", "read-secret").with_admin("admin-key", "admin-secret");
+
+        let body = api
+            .order_guid_body(url.clone(), 7, guid, KeyTier::Admin)
+            .unwrap();
+        assert_that(&body.param("orderGuid")).is_equal_to(Some(&Value::from(guid)));
+
+        let msg = format!(
+            "{},apiKey=admin-key,nonce=7,orderGuid={}",
+            "https://api.independentreserve.com/Private/CancelOrder", guid
+        );
+        assert_that(&body.signature).is_equal_to(sign(&msg, "admin-secret"));
+    }
+
+    #[tokio::test]
+    async fn cancel_order_rejects_malformed_guid() {
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_admin("admin-key", "admin-secret");
+
+        let err = api.cancel_order("not-a-guid").await.unwrap_err();
+
+        assert_that(&err.to_string().contains("invalid order guid")).is_true();
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_order_exposes_status_and_fill() {
+        let mock = Arc::new(MockTransport::new().with_json(
+            "CancelOrder",
+            r#"{
+                "OrderGuid": "c7347e4c-b865-4c94-8f74-d934d4b0b177",
+                "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+                "Type": "LimitOffer",
+                "VolumeOrdered": 5.0,
+                "VolumeFilled": 1.0,
+                "Price": 485.76,
+                "ReservedAmount": 0.0,
+                "Status": "PartiallyFilledAndCancelled",
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Usd"
+            }"#,
+        ));
+        let mut api = Private::new(1, "read-key", "read-secret")
+            .with_admin("admin-key", "admin-secret")
+            .with_transport(mock);
+
+        let order = api
+            .cancel_order("c7347e4c-b865-4c94-8f74-d934d4b0b177")
+            .await
+            .unwrap();
+
+        assert_that(&order.order_guid()).is_equal_to("c7347e4c-b865-4c94-8f74-d934d4b0b177");
+        assert_that(order.order_type()).is_equal_to(&OrderTypeKind::LimitOffer);
+        assert_that(order.status()).is_equal_to(&OrderStatus::PartiallyFilledAndCancelled);
+        assert_that(&order.volume_ordered()).is_equal_to(Decimal::from(5));
+        assert_that(&order.volume_filled()).is_equal_to(Decimal::from(1));
+        assert_that(&order.price()).is_equal_to(Decimal::new(48576, 2));
+        assert_that(&order.is_terminal()).is_true();
+    }
+
+    #[test]
+    fn transactions_signature_message_format() {
+        let url = Url::parse("https://api.independentreserve.com/Private/GetTransactions").unwrap();
+        let api = Private::new(1, "read-key", "read-secret");
+
+        let body = api
+            .transactions_body(
+                url.clone(),
+                42,
+                "49994921-60ec-411e-8a78-d0eba078d5e9",
+                Some("2014-08-01T08:00:00Z"),
+                None,
+                Some(&[TransactionType::Brokerage, TransactionType::Trade]),
+                1,
+            )
+            .unwrap();
+
+        let msg = signature_message(&url, &body.params);
+        assert_that(&msg.as_str()).is_equal_to(
+            "https://api.independentreserve.com/Private/GetTransactions,apiKey=read-key,nonce=42,\
+             accountGuid=49994921-60ec-411e-8a78-d0eba078d5e9,\
+             fromTimestampUtc=2014-08-01T08:00:00Z,toTimestampUtc=,txTypes=Brokerage,Trade,\
+             pageIndex=1,pageSize=25",
+        );
+        assert_that(&body.signature).is_equal_to(sign(&msg, "read-secret"));
+
+        let json = serde_json::to_value(body).unwrap();
+        assert_that(&json["toTimestampUtc"]).is_equal_to(Value::Null);
+        assert_that(&json["txTypes"]).is_equal_to(serde_json::json!(["Brokerage", "Trade"]));
+    }
+
+    #[test]
+    fn market_order_body_has_no_price() {
+        let url =
+            Url::parse("https://api.independentreserve.com/Private/PlaceMarketOrder").unwrap();
+        let api =
+            Private::new(1, "read-key", "read-secret").with_admin("admin-key", "admin-secret");
+
+        let body = api
+            .market_order_body(url.clone(), 3, "Xbt", "Aud", Side::Bid, Decimal::new(15, 1))
+            .unwrap();
+
+        assert_that(&body.param("price")).is_none();
+        let json = serde_json::to_value(&body).unwrap();
+        assert_that(&json.get("price")).is_none();
+
+        let msg = signature_message(&url, &body.params);
+        assert_that(&msg.as_str()).is_equal_to(
+            "https://api.independentreserve.com/Private/PlaceMarketOrder,apiKey=admin-key,nonce=3,\
+             orderType=MarketBid,volume=1.5,primaryCurrencyCode=Xbt,secondaryCurrencyCode=Aud",
+        );
+    }
+
+    #[tokio::test]
+    async fn withdrawal_requires_full_access_key() {
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_admin("admin-key", "admin-secret");
+
+        assert_missing_key(
+            api.withdraw_digital_currency("Xbt", Decimal::from(1), "address", None, None)
+                .await,
+            KeyTier::FullAccess,
+        );
+    }
+
+    #[test]
+    fn withdrawal_optional_fields_sign_as_empty() {
+        let url = Url::parse("https://api.independentreserve.com/Private/WithdrawDigitalCurrency")
+            .unwrap();
+        let api =
+            Private::new(1, "read-key", "read-secret").with_full_access("full-key", "full-secret");
+
+        let body = api
+            .withdrawal_body(
+                url.clone(),
+                5,
+                "Xbt",
+                Decimal::new(5, 1),
+                "address",
+                None,
+                None,
+            )
+            .unwrap();
+
+        let msg = signature_message(&url, &body.params);
+        assert_that(&msg.as_str()).is_equal_to(
+            "https://api.independentreserve.com/Private/WithdrawDigitalCurrency,apiKey=full-key,\
+             nonce=5,amount=0.5,withdrawalAddress=address,comment=,primaryCurrencyCode=Xbt,\
+             destinationTag=",
+        );
+        assert_that(&body.signature).is_equal_to(sign(&msg, "full-secret"));
+
+        let json = serde_json::to_value(&body).unwrap();
+        assert_that(&json["comment"]).is_equal_to(Value::Null);
+        assert_that(&json["destinationTag"]).is_equal_to(Value::Null);
+    }
+
+    #[test]
+    fn guid_validation() {
+        assert_that(&validate_guid(
+            "transaction",
+            "2a93732f-3f40-4685-b3bc-ff3ec326090d",
+        ))
+        .is_ok();
+
+        for bad in &["", "2a93732f", "2a93732f-3f40-4685-b3bc-ff3ec326090z"] {
+            let err = validate_guid("transaction", bad).unwrap_err();
+            assert_that(&err.to_string().contains("invalid transaction guid")).is_true();
+        }
+    }
+
+    #[tokio::test]
+    async fn withdrawal_status_rejects_malformed_guid_without_using_nonce() {
+        let mut api = Private::new(1, "read-key", "read-secret");
+
+        assert_that(&api.get_digital_currency_withdrawal("nope").await).is_err();
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(1);
+    }
+
+    #[test]
+    fn order_status_deserializes_known_and_unknown() {
+        let known = vec![
+            ("Open", OrderStatus::Open),
+            ("PartiallyFilled", OrderStatus::PartiallyFilled),
+            ("Filled", OrderStatus::Filled),
+            (
+                "PartiallyFilledAndCancelled",
+                OrderStatus::PartiallyFilledAndCancelled,
+            ),
+            ("Cancelled", OrderStatus::Cancelled),
+            (
+                "PartiallyFilledAndExpired",
+                OrderStatus::PartiallyFilledAndExpired,
+            ),
+            ("Expired", OrderStatus::Expired),
+        ];
+
+        for (s, want) in known {
+            let json = format!("\"{}\"", s);
+            let got: OrderStatus = serde_json::from_str(&json).unwrap();
+            assert_that(&got).is_equal_to(&want);
+            assert_that(&serde_json::to_string(&got).unwrap()).is_equal_to(&json);
+        }
+
+        let got: OrderStatus = serde_json::from_str("\"PendingReview\"").unwrap();
+        assert_that(&got).is_equal_to(OrderStatus::Other("PendingReview".to_string()));
+        assert_that(&got.is_terminal()).is_false();
+    }
+
+    fn account(currency: &str, status: &str, available: u32, total: u32) -> Account {
+        Account {
+            account_guid: format!("{}-guid", currency),
+            account_status: AccountStatus::from(status.to_string()),
+            available_balance: Decimal::from(available),
+            currency_code: currency.to_string(),
+            total_balance: Decimal::from(total),
+        }
+    }
+
+    #[test]
+    fn accounts_balance_lookups() {
+        let accounts = Accounts(vec![
+            account("Xbt", "Active", 1, 2),
+            account("Aud", "Active", 1000, 3000),
+        ]);
+
+        assert_that(&accounts.available_in("Aud")).is_equal_to(Some(Decimal::from(1000)));
+        assert_that(&accounts.total_in("aud")).is_equal_to(Some(Decimal::from(3000)));
+        assert_that(&accounts.total_in("Xbt")).is_equal_to(Some(Decimal::from(2)));
+        assert_that(&accounts.available_in("Eth")).is_none();
+        assert_that(&accounts.total_in("Eth")).is_none();
+    }
+
+    #[test]
+    fn account_status_deserializes_known_and_unknown() {
+        let json = r#"{
+            "AccountGuid": "66dcac65-bf07-4e68-ad46-838f51100424",
+            "AccountStatus": "Active",
+            "AvailableBalance": 0.5,
+            "CurrencyCode": "Xbt",
+            "TotalBalance": 1.5
+        }"#;
+        let got: Account = serde_json::from_str(json).unwrap();
+        assert_that(got.account_status()).is_equal_to(&AccountStatus::Active);
+        assert_that(&got.account_status().is_active()).is_true();
+
+        let got: AccountStatus = serde_json::from_str("\"Inactive\"").unwrap();
+        assert_that(&got).is_equal_to(AccountStatus::Inactive);
+
+        let got: AccountStatus = serde_json::from_str("\"Suspended\"").unwrap();
+        assert_that(&got).is_equal_to(AccountStatus::Other("Suspended".to_string()));
+        assert_that(&got.is_active()).is_false();
+        assert_that(&serde_json::to_string(&got).unwrap()).is_equal_to("\"Suspended\"".to_string());
+    }
+
+    #[test]
+    fn transaction_type_round_trips_valid_types() {
+        // As returned by GetValidTransactionTypes.
+        let valid = vec![
+            "AccountFee",
+            "Brokerage",
+            "Deposit",
+            "DepositFee",
+            "GST",
+            "ReferralCommission",
+            "StatementFee",
+            "Trade",
+            "Withdrawal",
+            "WithdrawalFee",
+        ];
+
+        for s in valid {
+            let json = Value::from(s);
+            let tx_type: TransactionType = serde_json::from_value(json.clone()).unwrap();
+            assert_that(&serde_json::to_value(tx_type).unwrap()).is_equal_to(&json);
+        }
+
+        let typo: Result<TransactionType, _> = serde_json::from_str("\"Brokrage\"");
+        assert_that(&typo.is_err()).is_true();
+    }
+
+    #[test]
+    fn monetary_fields_roundtrip_without_drift() {
+        let json = r#"{
+            "AccountGuid": "66dcac65-bf07-4e68-ad46-838f51100424",
+            "AccountStatus": "Active",
+            "AvailableBalance": 12345.67,
+            "CurrencyCode": "Aud",
+            "TotalBalance": 0.00000001
+        }"#;
+
+        let account: Account = serde_json::from_str(json).unwrap();
+        assert_that(&account.available_balance()).is_equal_to(Decimal::new(1_234_567, 2));
+        assert_that(&account.total_balance()).is_equal_to(Decimal::new(1, 8));
+
+        let out = serde_json::to_value(&account).unwrap();
+        let again: Account = serde_json::from_value(out).unwrap();
+        assert_that(&again.available_balance().to_string()).is_equal_to("12345.67".to_string());
+        assert_that(&again.total_balance().to_string()).is_equal_to("0.00000001".to_string());
+    }
+
+    // Recorded GetOrderDetails and GetAccounts responses, with a new field in
+    // the order and in the first account of the list.
+    fn responses_with_extra_field() -> (Value, Value) {
+        let mut details: Value =
+            serde_json::from_str(include_str!("../../../testdata/GetOrderDetails.json")).unwrap();
+        details["SomeNewField"] = Value::from(42);
+
+        let mut accounts: Value =
+            serde_json::from_str(include_str!("../../../testdata/GetAccounts.json")).unwrap();
+        accounts[0]["SomeNewField"] = Value::from(42);
+
+        (details, accounts)
+    }
+
+    #[cfg(feature = "strict-schema")]
+    #[test]
+    fn unknown_private_field_is_an_error_with_strict_schema() {
+        let (details, accounts) = responses_with_extra_field();
+
+        assert_that(&serde_json::from_value::<OrderDetails>(details).is_err()).is_true();
+        assert_that(&serde_json::from_value::<Accounts>(accounts).is_err()).is_true();
+    }
+
+    #[cfg(not(feature = "strict-schema"))]
+    #[test]
+    fn unknown_private_field_is_ignored() {
+        let (details, accounts) = responses_with_extra_field();
+
+        assert_that(&serde_json::from_value::<OrderDetails>(details).is_ok()).is_true();
+        assert_that(&serde_json::from_value::<Accounts>(accounts).is_ok()).is_true();
+    }
+
+    #[test]
+    fn nonce_never_decreases_across_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nonce");
+
+        // Both instances seeded with the same clock time, as on a quick restart.
+        let seed = 1_000;
+
+        let first = Private::new(seed, "read-key", "read-secret")
+            .with_nonce_file(&path)
+            .unwrap();
+        let used: Vec<u64> = (0..3).map(|_| first.inc_nonce().unwrap()).collect();
+        drop(first);
+
+        let second = Private::new(seed, "read-key", "read-secret")
+            .with_nonce_file(&path)
+            .unwrap();
+        let next = second.inc_nonce().unwrap();
+
+        assert_that(&next).is_greater_than(used[2]);
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingStore {
+        saved: Mutex<Vec<u64>>,
+    }
+
+    impl NonceStore for CountingStore {
+        fn load(&self) -> Result<Option<u64>> {
+            Ok(self.saved.lock().unwrap().last().copied())
+        }
+
+        fn save(&self, nonce: u64) -> Result<()> {
+            self.saved.lock().unwrap().push(nonce);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn nonces_are_saved_a_block_at_a_time() {
+        let store = Arc::new(CountingStore::default());
+        let api = Private::new(1_000, "read-key", "read-secret")
+            .with_nonce_store(store.clone())
+            .unwrap();
+
+        let used: Vec<u64> = (0..250).map(|_| api.inc_nonce().unwrap()).collect();
+
+        let saved = store.saved.lock().unwrap().clone();
+        assert_that(&saved).is_equal_to(vec![1_100, 1_201, 1_302]);
+        // Restarting from the saved nonce can't reuse any we handed out.
+        assert_that(&used.last().copied()).is_less_than(saved.last().copied());
+    }
+
+    #[derive(Debug)]
+    struct FailingStore;
+
+    impl NonceStore for FailingStore {
+        fn load(&self) -> Result<Option<u64>> {
+            Ok(None)
+        }
+
+        fn save(&self, _: u64) -> Result<()> {
+            bail!("disk full")
+        }
+    }
+
+    #[tokio::test]
+    async fn request_is_not_sent_if_nonce_is_not_persisted() {
+        let mock = Arc::new(MockTransport::new().with_json("GetAccounts", "[]"));
+        let mut api = Private::new(1_000, "read-key", "read-secret")
+            .with_nonce_store(Arc::new(FailingStore))
+            .unwrap()
+            .with_transport(mock.clone());
+
+        let err = api.get_accounts().await.unwrap_err();
+
+        assert_that(&format!("{:#}", err)).contains("failed to persist nonce");
+        assert_that(&mock.requests()).is_empty();
+    }
+
+    #[tokio::test]
+    async fn concurrent_clones_use_unique_increasing_nonces() {
+        let api = Private::new(1_000, "read-key", "read-secret");
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let api = api.clone();
+                tokio::spawn(async move {
+                    (0..50)
+                        .map(|_| api.inc_nonce().unwrap())
+                        .collect::<Vec<u64>>()
+                })
+            })
+            .collect();
+
+        let mut all = vec![];
+        for task in tasks {
+            let used = task.await.unwrap();
+            // Increasing within each task.
+            assert_that(&used.windows(2).all(|w| w[0] < w[1])).is_true();
+            all.extend(used);
+        }
+
+        // Unique across tasks, none skipped.
+        all.sort_unstable();
+        all.dedup();
+        assert_that(&all).is_equal_to((1_000..1_800).collect::<Vec<u64>>());
+        assert_that(&api.inc_nonce().unwrap()).is_equal_to(1_800);
+    }
+
+    fn invalid_nonce(min: u64) -> HttpResponse {
+        HttpResponse::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                r#"{{"Message":"Invalid nonce: 1. Nonce must be greater than {}."}}"#,
+                min
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn nonce_is_resynced_after_invalid_nonce_error() {
+        let mock = Arc::new(
+            MockTransport::new()
+                .with_response("GetAccounts", invalid_nonce(1_000))
+                .with_json(
+                    "GetAccounts",
+                    include_str!("../../../testdata/GetAccounts.json"),
+                ),
+        );
+        let mut api = Private::new(1, "read-key", "read-secret").with_transport(mock.clone());
+
+        let accounts = api.get_accounts().await.unwrap();
+
+        assert_that(&accounts.0.is_empty()).is_false();
+        assert_that(&mock.requests()).has_length(2);
+        // Rejected nonce 1, then used 1,001.
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(1_002);
+    }
+
+    #[tokio::test]
+    async fn posted_body_keeps_signed_parameter_order() {
+        let mock = Arc::new(MockTransport::new().with_json("GetOpenOrders", "{}"));
+        let mut api = Private::new(1, "read-key", "read-secret").with_transport(mock.clone());
+
+        // Response doesn't parse, we only care about the request.
+        let _ = api.get_open_orders("Xbt", "Aud", 1).await;
+
+        let body = &mock.bodies()[0];
+        let keys = [
+            "apiKey",
+            "nonce",
+            "primaryCurrencyCode",
+            "secondaryCurrencyCode",
+            "pageIndex",
+            "pageSize",
+            "signature",
+        ];
+        let positions = keys
+            .iter()
+            .map(|k| body.find(&format!("\"{}\":", k)).unwrap())
+            .collect::<Vec<_>>();
+        assert_that(&positions.windows(2).all(|w| w[0] < w[1])).is_true();
+    }
+
+    #[tokio::test]
+    async fn nonce_is_resynced_only_once_per_request() {
+        let mock =
+            Arc::new(MockTransport::new().with_response("GetAccounts", invalid_nonce(1_000)));
+        let mut api = Private::new(1, "read-key", "read-secret").with_transport(mock.clone());
+
+        let err = api.get_accounts().await.unwrap_err();
+
+        assert_that(&err.downcast_ref::<ApiError>().unwrap().min_nonce()).is_equal_to(Some(1_000));
+        assert_that(&mock.requests()).has_length(2);
+    }
+
+    #[test]
+    fn resync_never_moves_the_nonce_backwards() {
+        let api = Private::new(5_000, "read-key", "read-secret");
+
+        api.resync_nonce(1_000);
+
+        assert_that(&api.inc_nonce().unwrap()).is_equal_to(5_000);
+    }
+
+    #[test]
+    fn debug_output_hides_secrets() {
+        let read_secret = "read-secret-0123456789";
+        let admin_secret = "admin-secret-0123456789";
+        let private =
+            Private::new(1, "read-key", read_secret).with_admin("admin-key", admin_secret);
+
+        let key = format!("{:?}", private.keys.read);
+        assert_that(&key.contains(read_secret)).is_false();
+        assert_that(&key).contains("read-key");
+
+        let debug = format!("{:?}", private);
+        assert_that(&debug.contains(read_secret)).is_false();
+        assert_that(&debug.contains(admin_secret)).is_false();
+    }
+
+    #[test]
+    fn order_type_kind_deserializes_known_and_unknown() {
+        let kind = |s: &str| serde_json::from_str::<OrderTypeKind>(s).unwrap();
+
+        assert_that(&kind("\"LimitBid\"")).is_equal_to(OrderTypeKind::LimitBid);
+        assert_that(&kind("\"LimitOffer\"")).is_equal_to(OrderTypeKind::LimitOffer);
+        assert_that(&kind("\"MarketBid\"")).is_equal_to(OrderTypeKind::MarketBid);
+        assert_that(&kind("\"MarketOffer\"")).is_equal_to(OrderTypeKind::MarketOffer);
+        assert_that(&kind("\"StopBid\"")).is_equal_to(OrderTypeKind::Other("StopBid".to_string()));
+
+        assert_that(&OrderTypeKind::MarketBid.is_bid()).is_true();
+        assert_that(&OrderTypeKind::LimitOffer.is_offer()).is_true();
+        assert_that(&OrderTypeKind::Other("StopBid".to_string()).is_bid()).is_false();
+        assert_that(&serde_json::to_string(&OrderTypeKind::LimitOffer).unwrap())
+            .is_equal_to("\"LimitOffer\"".to_string());
+    }
+
+    // A GetTrades response with one trade for each of `guids`.
+    fn trades_page(total_pages: usize, guids: &[&str]) -> String {
+        let data: Vec<Value> = guids
+            .iter()
+            .map(|guid| {
+                serde_json::json!({
+                    "TradeGuid": guid,
+                    "TradeTimestampUtc": "2014-12-16T03:44:19.2187707Z",
+                    "OrderGuid": "a6a8b5da-5a5b-4fb3-94f7-61e7bc0a6f42",
+                    "OrderType": "LimitBid",
+                    "OrderTimestampUtc": "2014-12-16T03:43:36.7423769Z",
+                    "VolumeTraded": 0.5,
+                    "Price": 410.0,
+                    "PrimaryCurrencyCode": "Xbt",
+                    "SecondaryCurrencyCode": "Aud"
+                })
+            })
+            .collect();
+        let body = serde_json::json!({
+            "PageSize": PAGE_SIZE,
+            "TotalItems": 3,
+            "TotalPages": total_pages,
+            "Data": data,
+        });
+        ok_json(&body.to_string())
+    }
+
+    #[tokio::test]
+    async fn get_all_trades_fetches_every_page() {
+        let addr = mock_server(vec![
+            trades_page(2, &["trade-1", "trade-2"]),
+            trades_page(2, &["trade-3"]),
+        ])
+        .await;
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_url(format!("http://{}", addr));
+
+        let trades = api.get_all_trades().await.unwrap();
+
+        let guids: Vec<&str> = trades.iter().map(Trade::trade_guid).collect();
+        assert_that(&guids).is_equal_to(vec!["trade-1", "trade-2", "trade-3"]);
+        // One nonce per page.
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn trades_stream_yields_each_page_in_order() {
+        let addr = mock_server(vec![
+            trades_page(2, &["trade-1", "trade-2"]),
+            trades_page(2, &["trade-3"]),
+        ])
+        .await;
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_url(format!("http://{}", addr));
+
+        let trades: Vec<Trade> = api.trades_stream().try_collect().await.unwrap();
+
+        let guids: Vec<&str> = trades.iter().map(Trade::trade_guid).collect();
+        assert_that(&guids).is_equal_to(vec!["trade-1", "trade-2", "trade-3"]);
+        let trade = &trades[0];
+        assert_that(&trade.order_guid()).is_equal_to("a6a8b5da-5a5b-4fb3-94f7-61e7bc0a6f42");
+        assert_that(trade.order_type()).is_equal_to(&OrderTypeKind::LimitBid);
+        assert_that(&trade.volume_traded()).is_equal_to(Decimal::new(5, 1));
+        assert_that(&trade.price()).is_equal_to(Decimal::from(410));
+        assert_that(&trade.traded())
+            .is_equal_to(parse_timestamp("2014-12-16T03:44:19.2187707Z").unwrap());
+        assert_that(&trade.order_created())
+            .is_equal_to(parse_timestamp("2014-12-16T03:43:36.7423769Z").unwrap());
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn trades_stream_fetches_pages_on_demand() {
+        // Only serves the first page.
+        let addr = mock_server(vec![trades_page(2, &["trade-1", "trade-2"])]).await;
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_url(format!("http://{}", addr));
+
+        let first: Vec<Result<Trade>> = api.trades_stream().take(2).collect().await;
+
+        assert_that(&first.iter().all(Result::is_ok)).is_true();
+        assert_that(&api.nonce.load(Ordering::SeqCst)).is_equal_to(2);
+    }
+
+    #[tokio::test]
+    async fn records_rate_limit_from_response_headers() {
+        let body = trades_page(1, &[]);
+        let limited = body.replacen(
+            "\r\n",
+            "\r\nX-RateLimit-Remaining: 7\r\nX-RateLimit-Reset: 1590969600\r\n",
+            1,
+        );
+        let addr = mock_server(vec![limited, body]).await;
+        let mut api =
+            Private::new(1, "read-key", "read-secret").with_url(format!("http://{}", addr));
+        assert_that(&api.last_rate_limit()).is_none();
+
+        api.get_trades(1).await.unwrap();
+        let limit = api.last_rate_limit().unwrap();
+        assert_that(&limit.remaining).is_equal_to(7);
+        assert_that(&limit.reset.timestamp()).is_equal_to(1_590_969_600);
+
+        // Cleared by a response without rate limit headers.
+        api.get_trades(1).await.unwrap();
+        assert_that(&api.last_rate_limit()).is_none();
+    }
+
+    #[tokio::test]
+    async fn get_all_pages_stops_at_max_pages() {
+        let addr = mock_server(vec![trades_page(2, &["trade-1"])]).await;
+        let mut api = Private::new(1, "read-key", "read-secret")
+            .with_url(format!("http://{}", addr))
+            .with_max_pages(1);
+
+        let err = api.get_all_trades().await.unwrap_err();
+
+        assert_that(&err.to_string().contains("maximum of 1")).is_true();
+    }
+}
@@ -1,10 +1,36 @@
-use anyhow::Result;
+use super::{
+    default_transport, from_json, send_with_retry, timestamp, HttpResponse, HttpTransport,
+    RateLimit, RateLimiter, Request, ReqwestTransport, RetryPolicy,
+};
+use crate::{
+    market::{self, option_decimal_from_str_or_num},
+    num,
+};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use num_traits::identities::{One, Zero};
 use reqwest::Client;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::fmt::{self, Display};
+use std::{
+    cmp,
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::instrument;
 use url::Url;
 
+/// Number of trades to request in the first `trades_since` batch.
+const RECENT_TRADES_BATCH: usize = 10;
+
+/// Maximum number of trades the exchange returns from GetRecentTrades.
+const MAX_RECENT_TRADES: usize = 50;
+
+/// How long to cache the valid codes and order types lists, they rarely change.
+pub const VALID_LISTS_TTL: Duration = Duration::from_secs(60 * 60);
+
 // Independent Reserve Public API methods
 //
 // GetValidPrimaryCurrencyCodes
@@ -20,47 +46,160 @@ use url::Url;
 // GetRecentTrades
 // GetFxRates
 
+// Cached `get_valid_*` lists, keyed by API method, and when they were fetched.
+type Lists = HashMap<&'static str, (Instant, Vec<String>)>;
+
 /// Implements the public methods for Inedependent Reserve crypto exchange API.
 #[derive(Clone, Debug)]
 pub struct Public {
-    client: Client,
+    transport: Arc<dyn HttpTransport>,
+    retry: RetryPolicy,
+    limiter: Option<RateLimiter>,
+    /// Base URL of the public API.
+    url: String,
+    /// Check currency codes locally before making a request.
+    validate_codes: bool,
+    /// How long to cache the `get_valid_*` lists for.
+    lists_ttl: Duration,
+    /// Cached `get_valid_*` lists, shared by clones.
+    lists: Arc<Mutex<Lists>>,
+    /// Rate limit reported by the last response, shared by clones.
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
 }
 
 impl Public {
     /// Public API URL
     const URL: &'static str = "https://api.independentreserve.com/Public";
 
+    /// Use `client` to make requests e.g., to set a custom timeout.
+    pub fn with_client(client: Client) -> Self {
+        Public::with_transport(Arc::new(ReqwestTransport::new(client)))
+    }
+
+    /// Make requests using `transport` e.g., a `MockTransport` in tests.
+    pub fn with_transport(transport: Arc<dyn HttpTransport>) -> Self {
+        Public {
+            transport,
+            retry: RetryPolicy::default(),
+            limiter: None,
+            url: Self::URL.to_string(),
+            validate_codes: false,
+            lists_ttl: VALID_LISTS_TTL,
+            lists: Arc::default(),
+            rate_limit: Arc::default(),
+        }
+    }
+
+    /// Retry transient API errors according to `retry`.
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Public { retry, ..self }
+    }
+
+    /// Wait on `limiter` before each request. Pass a clone of the same limiter
+    /// to `Private::with_rate_limiter` to limit requests made by both.
+    pub fn with_rate_limiter(self, limiter: RateLimiter) -> Self {
+        Public {
+            limiter: Some(limiter),
+            ..self
+        }
+    }
+
+    /// Send requests to `url` instead of the Independent Reserve public API
+    /// e.g., a staging endpoint or a local mock server.
+    pub fn with_url(self, url: impl ToString) -> Self {
+        Public {
+            url: url.to_string(),
+            ..self
+        }
+    }
+
+    /// Check currency codes against the exchange's valid codes before each
+    /// request, so an unknown code e.g., "Btc" fails locally with a clear
+    /// error. The valid codes are cached, see `with_lists_ttl`.
+    pub fn with_code_validation(self) -> Self {
+        Public {
+            validate_codes: true,
+            ..self
+        }
+    }
+
+    /// The transport used to make requests.
+    pub fn transport(&self) -> Arc<dyn HttpTransport> {
+        Arc::clone(&self.transport)
+    }
+
+    /// Rate limit reported by the exchange in the last response, `None` if
+    /// it did not include rate limit headers.
+    pub fn last_rate_limit(&self) -> Option<RateLimit> {
+        *self.rate_limit.lock().expect("rate limit lock poisoned")
+    }
+
+    /// Cache the `get_valid_*` lists for `ttl`, defaults to `VALID_LISTS_TTL`.
+    pub fn with_lists_ttl(self, ttl: Duration) -> Self {
+        Public {
+            lists_ttl: ttl,
+            ..self
+        }
+    }
+
+    /// Drop the cached `get_valid_*` lists so the next call of each fetches
+    /// them again. Affects all clones.
+    pub fn refresh(&self) {
+        self.lists.lock().expect("lists lock poisoned").clear();
+    }
+
     /// API call: GetValidPrimaryCurrencyCodes
     pub async fn get_valid_primary_currency_codes(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidPrimaryCurrencyCodes").await
+        self.cached_vec_api_call("GetValidPrimaryCurrencyCodes")
+            .await
     }
 
     /// API call: GetValidSecondaryCurrencyCodes
     pub async fn get_valid_secondary_currency_codes(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidSecondaryCurrencyCodes").await
+        self.cached_vec_api_call("GetValidSecondaryCurrencyCodes")
+            .await
     }
+
+    /// Check that `base` and `quote` are valid primary and secondary currency
+    /// codes.
+    pub async fn check_currency_codes(&self, base: &str, quote: &str) -> Result<()> {
+        let bases = self.get_valid_primary_currency_codes().await?;
+        if !contains_code(&bases, base) {
+            bail!("invalid primary currency code: {}", base);
+        }
+
+        let quotes = self.get_valid_secondary_currency_codes().await?;
+        if !contains_code(&quotes, quote) {
+            bail!("invalid secondary currency code: {}", quote);
+        }
+
+        Ok(())
+    }
+
     /// API call: GetValidLimitOrderTypes
     pub async fn get_valid_limit_order_types(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidLimitOrderTypes").await
+        self.cached_vec_api_call("GetValidLimitOrderTypes").await
     }
 
     /// API call: GetValidMarketOrderTypes
     pub async fn get_valid_market_order_types(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidMarketOrderTypes").await
+        self.cached_vec_api_call("GetValidMarketOrderTypes").await
     }
 
     /// API call: GetValidOrderTypes
     pub async fn get_valid_order_types(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidOrderTypes").await
+        self.cached_vec_api_call("GetValidOrderTypes").await
     }
 
     /// API call: GetValidTransactionTypes
     pub async fn get_valid_transaction_types(&self) -> Result<Vec<String>> {
-        self.vec_api_call("GetValidTransactionTypes").await
+        self.cached_vec_api_call("GetValidTransactionTypes").await
     }
 
     /// API call: GetMarketSummary
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_market_summary(&self, base: &str, quote: &str) -> Result<MarketSummary> {
+        self.maybe_check_currency_codes(base, quote).await?;
         let url = self.build_url("GetMarketSummary")?;
 
         let url = Url::parse_with_params(url.as_str(), &[
@@ -68,14 +207,16 @@ impl Public {
             ("secondaryCurrencyCode", quote),
         ])?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: MarketSummary = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: MarketSummary = from_json("GetMarketSummary", &body)?;
 
         Ok(res)
     }
 
     /// API call: GetOrderBook
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_order_book(&self, base: &str, quote: &str) -> Result<OrderBook> {
+        self.maybe_check_currency_codes(base, quote).await?;
         let url = self.build_url("GetOrderBook")?;
 
         let url = Url::parse_with_params(url.as_str(), &[
@@ -83,14 +224,30 @@ impl Public {
             ("secondaryCurrencyCode", quote),
         ])?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: OrderBook = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: OrderBook = from_json("GetOrderBook", &body)?;
 
         Ok(res)
     }
 
+    /// API call: GetOrderBook, keeping only the best `depth` orders on each
+    /// side. The exchange always returns the full book, trimming it here
+    /// saves downstream work on orders the caller doesn't need.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_order_book_top(
+        &self,
+        base: &str,
+        quote: &str,
+        depth: usize,
+    ) -> Result<market::OrderBook> {
+        let book = self.get_order_book(base, quote).await?;
+        Ok(market::OrderBook::from_top_n(book, depth))
+    }
+
     /// API call: GetAllOrders
-    pub async fn get_all_orders(&self, base: &str, quote: &str) -> Result<Orders> {
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_all_orders(&self, base: &str, quote: &str) -> Result<AllOrders> {
+        self.maybe_check_currency_codes(base, quote).await?;
         let url = self.build_url("GetAllOrders")?;
 
         let url = Url::parse_with_params(url.as_str(), &[
@@ -98,40 +255,47 @@ impl Public {
             ("secondaryCurrencyCode", quote),
         ])?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: Orders = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: AllOrders = from_json("GetAllOrders", &body)?;
 
         Ok(res)
     }
 
     /// API call: GetTradeHistorySummary
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_trade_history_summary(
         &self,
         base: &str,
         quote: &str,
-        hours_past: usize,
+        hours_past: HoursPast,
     ) -> Result<TradeHistorySummary> {
+        self.maybe_check_currency_codes(base, quote).await?;
         let url = self.build_url("GetTradeHistorySummary")?;
 
         let url = Url::parse_with_params(url.as_str(), &[
             ("primaryCurrencyCode", base),
             ("secondaryCurrencyCode", quote),
-            ("numberOfHoursInThePastToRetrieve", &hours_past.to_string()),
+            (
+                "numberOfHoursInThePastToRetrieve",
+                &hours_past.get().to_string(),
+            ),
         ])?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: TradeHistorySummary = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: TradeHistorySummary = from_json("GetTradeHistorySummary", &body)?;
 
         Ok(res)
     }
 
     /// API call: GetRecentTrades
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_recent_trades(
         &self,
         base: &str,
         quote: &str,
         num_trades: usize,
     ) -> Result<RecentTrades> {
+        self.maybe_check_currency_codes(base, quote).await?;
         let url = self.build_url("GetRecentTrades")?;
 
         let url = Url::parse_with_params(url.as_str(), &[
@@ -140,18 +304,39 @@ impl Public {
             ("numberOfRecentTradesToRetrieve", &num_trades.to_string()),
         ])?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: RecentTrades = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: RecentTrades = from_json("GetRecentTrades", &body)?;
 
         Ok(res)
     }
 
+    /// Get all recent trades at or after `since`, oldest first.
+    ///
+    /// Fetches increasingly large batches of recent trades until the batch
+    /// reaches back past `since` or we hit the exchange's maximum batch size.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn trades_since(
+        &self,
+        base: &str,
+        quote: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Trade>> {
+        let mut num_trades = RECENT_TRADES_BATCH;
+        loop {
+            let recent = self.get_recent_trades(base, quote, num_trades).await?;
+            if num_trades >= MAX_RECENT_TRADES || recent.covers(since, num_trades) {
+                return Ok(recent.into_trades_since(since));
+            }
+            num_trades = cmp::min(num_trades * 2, MAX_RECENT_TRADES);
+        }
+    }
+
     /// API call: GetFxRates
     pub async fn get_fx_rates(&self) -> Result<FxRates> {
         let url = self.build_url("GetFxRates")?;
 
-        let body = self.client.get(url).send().await?.text().await?;
-        let res: FxRates = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let res: FxRates = from_json("GetFxRates", &body)?;
 
         Ok(res)
     }
@@ -159,15 +344,55 @@ impl Public {
     // Simple vector return type API call.
     async fn vec_api_call(&self, path: &str) -> Result<Vec<String>> {
         let url = self.build_url(path)?;
-        let body = self.client.get(url).send().await?.text().await?;
-        let v: Vec<String> = serde_json::from_str(&body)?;
+        let body = self.get(url).await?.body;
+        let v: Vec<String> = from_json(path, &body)?;
+
+        Ok(v)
+    }
+
+    // Like `vec_api_call` but returns the cached list if it is younger than
+    // `lists_ttl`.
+    async fn cached_vec_api_call(&self, path: &'static str) -> Result<Vec<String>> {
+        if let Some((fetched, v)) = self.lists.lock().expect("lists lock poisoned").get(path) {
+            if fetched.elapsed() < self.lists_ttl {
+                return Ok(v.clone());
+            }
+        }
+
+        let v = self.vec_api_call(path).await?;
+        self.lists
+            .lock()
+            .expect("lists lock poisoned")
+            .insert(path, (Instant::now(), v.clone()));
 
         Ok(v)
     }
 
+    // Check currency codes if code validation is enabled.
+    async fn maybe_check_currency_codes(&self, base: &str, quote: &str) -> Result<()> {
+        if self.validate_codes {
+            self.check_currency_codes(base, quote).await?;
+        }
+        Ok(())
+    }
+
+    // GET `url`, all public methods are idempotent so may be retried.
+    #[instrument(level = "debug", skip(self, url), fields(endpoint = url.path()))]
+    async fn get(&self, url: Url) -> Result<HttpResponse> {
+        let transport = self.transport.as_ref();
+        let res = send_with_retry(transport, self.retry, self.limiter.as_ref(), true, || {
+            Ok(Request::Get(url.clone()))
+        })
+        .await?;
+        *self.rate_limit.lock().expect("rate limit lock poisoned") =
+            RateLimit::from_headers(&res.headers);
+
+        Ok(res)
+    }
+
     // Build a URL from the Public API URL plus given path.
     fn build_url(&self, path: &str) -> Result<Url> {
-        let s = format!("{}/{}", Self::URL, path);
+        let s = format!("{}/{}", self.url, path);
         let url = Url::parse(&s)?;
 
         Ok(url)
@@ -177,27 +402,44 @@ impl Public {
 impl Default for Public {
     fn default() -> Self {
         Self {
-            client: Client::new(),
+            transport: default_transport(),
+            retry: RetryPolicy::default(),
+            limiter: None,
+            url: Self::URL.to_string(),
+            validate_codes: false,
+            lists_ttl: VALID_LISTS_TTL,
+            lists: Arc::default(),
+            rate_limit: Arc::default(),
         }
     }
 }
 
+// True if `codes` contains `code`, the exchange is case insensitive.
+pub(crate) fn contains_code(codes: &[String], code: &str) -> bool {
+    codes.iter().any(|c| c.eq_ignore_ascii_case(code))
+}
+
 /// Returned by GetOrderBook.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct OrderBook {
     pub buy_orders: Vec<PublicOrder>,
     pub sell_orders: Vec<PublicOrder>,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct PublicOrder {
     pub order_type: OrderType,
+    #[serde(default, deserialize_with = "option_decimal_from_str_or_num")]
     pub price: Option<Decimal>,
+    #[serde(default, deserialize_with = "option_decimal_from_str_or_num")]
     pub volume: Option<Decimal>,
 }
 
@@ -213,8 +455,10 @@ pub enum OrderType {
 /// Returned by GetMarketSummary
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct MarketSummary {
-    pub created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    pub created_timestamp_utc: DateTime<Utc>,
     pub current_highest_bid_price: Option<Decimal>,
     pub current_lowest_offer_price: Option<Decimal>,
     pub day_avg_price: Option<Decimal>,
@@ -227,6 +471,21 @@ pub struct MarketSummary {
     pub secondary_currency_code: String,
 }
 
+impl MarketSummary {
+    /// Mid market price i.e., the average of the highest bid and lowest
+    /// offer. `None` if either side of the book is empty.
+    pub fn mid_price(&self) -> Option<Decimal> {
+        let bid = self.current_highest_bid_price.as_ref()?;
+        let ask = self.current_lowest_offer_price.as_ref()?;
+        Some(num::mid_market_price(bid, ask))
+    }
+
+    /// Price of the last trade.
+    pub fn last(&self) -> Option<Decimal> {
+        self.last_price
+    }
+}
+
 impl Display for MarketSummary {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match serde_json::to_string_pretty(self) {
@@ -240,38 +499,137 @@ impl Display for MarketSummary {
 /// Returned by GetAllOrders
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
-pub struct Orders {
-    buy_orders: Vec<OrderGuid>,
-    sell_orders: Vec<OrderGuid>,
-    created_timestamp_utc: String,
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
+pub struct AllOrders {
+    pub buy_orders: Vec<OrderGuid>,
+    pub sell_orders: Vec<OrderGuid>,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
+impl AllOrders {
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct OrderGuid {
-    guid: String,
-    price: Option<Decimal>,
-    volume: Option<Decimal>,
+    pub guid: String,
+    pub price: Option<Decimal>,
+    pub volume: Option<Decimal>,
+}
+
+/// Number of hours of history to request from GetTradeHistorySummary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HoursPast(usize);
+
+impl HoursPast {
+    /// Maximum number of hours the exchange returns.
+    pub const MAX: usize = 240;
+
+    /// Errors if `hours` is zero or more than `HoursPast::MAX`.
+    pub fn new(hours: usize) -> Result<Self> {
+        if hours == 0 || hours > Self::MAX {
+            bail!("hours past must be between 1 and {}: {}", Self::MAX, hours);
+        }
+        Ok(HoursPast(hours))
+    }
+
+    pub fn get(&self) -> usize {
+        self.0
+    }
 }
 
 /// Returned by GetTradeHistorySummary
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct TradeHistorySummary {
     history_summary_items: Vec<HistorySummary>,
     number_of_hours_in_the_past_to_retrieve: usize,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl TradeHistorySummary {
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    /// Number of hours of history summarised.
+    pub fn hours(&self) -> usize {
+        self.number_of_hours_in_the_past_to_retrieve
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+
+    /// Summary of each hour, oldest first.
+    pub fn items(&self) -> &[HistorySummary] {
+        &self.history_summary_items
+    }
+
+    /// Total volume traded (primary currency) over the whole window.
+    pub fn volume(&self) -> Decimal {
+        self.items().iter().filter_map(|h| h.volume()).sum()
+    }
+
+    /// Volume weighted average price over the whole window, `None` if there
+    /// were no trades. Hours missing either volume are skipped.
+    pub fn vwap(&self) -> Option<Decimal> {
+        let (value, volume) = self
+            .items()
+            .iter()
+            .filter_map(|h| Some((h.secondary_currency_volume?, h.primary_currency_volume?)))
+            .fold(
+                (Decimal::zero(), Decimal::zero()),
+                |(value, volume), (v, p)| (value + v, volume + p),
+            );
+
+        if volume.is_zero() {
+            return None;
+        }
+        Some(value / volume)
+    }
+
+    /// OHLCV candle for each period, oldest first. Periods with no trades
+    /// have no prices and are skipped.
+    pub fn candles(&self) -> Vec<Candle> {
+        self.items()
+            .iter()
+            .filter_map(Candle::from_summary)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct HistorySummary {
-    start_timestamp_utc: String,
-    end_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    start_timestamp_utc: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    end_timestamp_utc: DateTime<Utc>,
     primary_currency_volume: Option<Decimal>,
     secondary_currency_volume: Option<Decimal>,
     opening_secondary_currency_price: Option<Decimal>,
@@ -282,22 +640,212 @@ pub struct HistorySummary {
     number_of_trades: usize,
 }
 
+impl HistorySummary {
+    /// Start of the period summarised.
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start_timestamp_utc
+    }
+
+    /// End of the period summarised.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.end_timestamp_utc
+    }
+
+    /// Volume traded (primary currency) during the period.
+    pub fn volume(&self) -> Option<Decimal> {
+        self.primary_currency_volume
+    }
+
+    /// Value traded (secondary currency) during the period.
+    pub fn value(&self) -> Option<Decimal> {
+        self.secondary_currency_volume
+    }
+
+    pub fn opening_price(&self) -> Option<Decimal> {
+        self.opening_secondary_currency_price
+    }
+
+    pub fn closing_price(&self) -> Option<Decimal> {
+        self.closing_secondary_currency_price
+    }
+
+    pub fn highest_price(&self) -> Option<Decimal> {
+        self.highest_secondary_currency_price
+    }
+
+    pub fn lowest_price(&self) -> Option<Decimal> {
+        self.lowest_secondary_currency_price
+    }
+
+    /// Average trade price during the period.
+    pub fn average_price(&self) -> Option<Decimal> {
+        self.average_secondary_currency_price
+    }
+
+    pub fn number_of_trades(&self) -> usize {
+        self.number_of_trades
+    }
+}
+
+/// Open, high, low, close, and volume over one period of trade history.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Candle {
+    #[serde(with = "timestamp")]
+    pub start: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub end: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// Volume traded (primary currency).
+    pub volume: Decimal,
+}
+
+impl Candle {
+    /// `None` if any of the prices are missing.
+    fn from_summary(h: &HistorySummary) -> Option<Self> {
+        Some(Candle {
+            start: h.start(),
+            end: h.end(),
+            open: h.opening_price()?,
+            high: h.highest_price()?,
+            low: h.lowest_price()?,
+            close: h.closing_price()?,
+            volume: h.volume().unwrap_or_default(),
+        })
+    }
+
+    /// Close less open, negative if the price fell.
+    pub fn body(&self) -> Decimal {
+        self.close - self.open
+    }
+
+    /// High less low.
+    pub fn range(&self) -> Decimal {
+        self.high - self.low
+    }
+}
+
 /// Returned by GetRecentTrades
+///
+/// ```
+/// use crypto_trader::market::RecentTrades;
+/// use rust_decimal::Decimal;
+///
+/// let json = r#"{
+///     "CreatedTimestampUtc": "2020-04-08T01:50:00Z",
+///     "PrimaryCurrencyCode": "Xbt",
+///     "SecondaryCurrencyCode": "Aud",
+///     "Trades": [{
+///         "PrimaryCurrencyAmount": 0.5,
+///         "SecondaryCurrencyTradePrice": 11500.0,
+///         "TradeTimestampUtc": "2020-04-08T01:49:00Z"
+///     }]
+/// }"#;
+/// let recent: RecentTrades = serde_json::from_str(json)?;
+///
+/// assert_eq!(recent.primary_currency_code(), "Xbt");
+/// let trade = recent.iter().next().unwrap();
+/// assert_eq!(trade.amount(), Some(Decimal::new(5, 1)));
+/// assert_eq!(trade.price(), Some(Decimal::from(11_500)));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct RecentTrades {
     trades: Vec<Trade>,
-    created_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    created_timestamp_utc: DateTime<Utc>,
     primary_currency_code: String,
     secondary_currency_code: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl RecentTrades {
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created_timestamp_utc
+    }
+
+    pub fn primary_currency_code(&self) -> &str {
+        &self.primary_currency_code
+    }
+
+    pub fn secondary_currency_code(&self) -> &str {
+        &self.secondary_currency_code
+    }
+
+    /// The trades, most recent first.
+    pub fn iter(&self) -> impl Iterator<Item = &Trade> {
+        self.trades.iter()
+    }
+
+    /// Volume weighted average price of these trades, `None` if there are no
+    /// trades (or they have no volume).
+    pub fn vwap(&self) -> Option<Decimal> {
+        let (value, volume) = self
+            .iter()
+            .filter_map(|t| {
+                Some((
+                    t.primary_currency_amount?,
+                    t.secondary_currency_trade_price?,
+                ))
+            })
+            .fold(
+                (Decimal::zero(), Decimal::zero()),
+                |(value, volume), (amount, price)| (value + amount * price, volume + amount),
+            );
+
+        if volume.is_zero() {
+            return None;
+        }
+        Some(value / volume)
+    }
+
+    // True if these trades reach back to `since` or there are no older trades
+    // to fetch i.e., the exchange returned fewer trades than `requested`.
+    fn covers(&self, since: DateTime<Utc>, requested: usize) -> bool {
+        self.trades.len() < requested || self.trades.iter().any(|t| t.timestamp() < since)
+    }
+
+    // Consume self returning the trades at or after `since`, oldest first.
+    fn into_trades_since(self, since: DateTime<Utc>) -> Vec<Trade> {
+        let mut trades: Vec<Trade> = self
+            .trades
+            .into_iter()
+            .filter(|t| t.timestamp() >= since)
+            .collect();
+        trades.sort_by_key(Trade::timestamp);
+
+        trades
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Trade {
     primary_currency_amount: Option<Decimal>,
     secondary_currency_trade_price: Option<Decimal>,
-    trade_timestamp_utc: String,
+    #[serde(with = "timestamp")]
+    trade_timestamp_utc: DateTime<Utc>,
+}
+
+impl Trade {
+    /// Volume traded (primary currency).
+    pub fn amount(&self) -> Option<Decimal> {
+        self.primary_currency_amount
+    }
+
+    /// Trade price (secondary currency).
+    pub fn price(&self) -> Option<Decimal> {
+        self.secondary_currency_trade_price
+    }
+
+    /// Time at which the trade occurred.
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.trade_timestamp_utc
+    }
 }
 
 /// Returned by GetFxRates
@@ -305,18 +853,66 @@ pub struct Trade {
 #[serde(rename_all = "PascalCase")]
 pub struct FxRates(Vec<Rate>);
 
+impl FxRates {
+    pub fn iter(&self) -> impl Iterator<Item = &Rate> {
+        self.0.iter()
+    }
+
+    /// Rate to convert currency `from` into currency `to` e.g., ("Usd", "Aud").
+    /// If the exchange only quotes the rate from `to` to `from` we use its
+    /// inverse.
+    pub fn get(&self, from: &str, to: &str) -> Option<Decimal> {
+        let find = |a: &str, b: &str| {
+            self.iter()
+                .find(|r| {
+                    r.currency_code_a.eq_ignore_ascii_case(a)
+                        && r.currency_code_b.eq_ignore_ascii_case(b)
+                })
+                .and_then(|r| r.rate)
+        };
+
+        find(from, to).or_else(|| {
+            find(to, from)
+                .filter(|rate| !rate.is_zero())
+                .map(|rate| Decimal::one() / rate)
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
+#[cfg_attr(feature = "strict-schema", serde(deny_unknown_fields))]
 pub struct Rate {
     currency_code_a: String,
     currency_code_b: String,
     rate: Option<Decimal>,
 }
 
+impl Rate {
+    pub fn currency_code_a(&self) -> &str {
+        &self.currency_code_a
+    }
+
+    pub fn currency_code_b(&self) -> &str {
+        &self.currency_code_b
+    }
+
+    /// Units of currency B per unit of currency A.
+    pub fn rate(&self) -> Option<Decimal> {
+        self.rate
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::market::{
+        api::{parse_timestamp, MockTransport},
+        ApiError,
+    };
+    use reqwest::StatusCode;
     use spectral::prelude::*;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn get_valid_primary_currency_codes_contains_xbt() {
@@ -417,29 +1013,247 @@ mod tests {
         }
     }
 
+    const MARKET_SUMMARY: &str = r#"{
+        "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+        "CurrentHighestBidPrice": 500.00000000,
+        "CurrentLowestOfferPrice": 1001.00000000,
+        "DayAvgPrice": 510.00000000,
+        "DayHighestPrice": 510.00000000,
+        "DayLowestPrice": 510.00000000,
+        "DayVolumeXbt": 1.00000000,
+        "DayVolumeXbtInSecondaryCurrrency": 0.00000000,
+        "LastPrice": 510.00000000,
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Usd"
+    }"#;
+
+    const ORDER_BOOK: &str = r#"{
+        "BuyOrders": [
+            {"OrderType": "LimitBid", "Price": 497.02, "Volume": 0.01},
+            {"OrderType": "LimitBid", "Price": 490.0, "Volume": 1.0}
+        ],
+        "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Usd",
+        "SellOrders": [
+            {"OrderType": "LimitOffer", "Price": 500.0, "Volume": 1.0},
+            {"OrderType": "LimitOffer", "Price": 505.0, "Volume": 1.0}
+        ]
+    }"#;
+
+    const MARKET_SUMMARY_EXTRA_FIELD: &str = r#"{
+        "CreatedTimestampUtc": "2014-08-05T06:42:11.3032208Z",
+        "CurrentHighestBidPrice": 500.0,
+        "CurrentLowestOfferPrice": 1001.0,
+        "DayAvgPrice": 510.0,
+        "DayHighestPrice": 510.0,
+        "DayLowestPrice": 510.0,
+        "DayVolumeXbt": 1.0,
+        "DayVolumeXbtInSecondaryCurrrency": 0.0,
+        "LastPrice": 510.0,
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Usd",
+        "SomeNewField": 42
+    }"#;
+
+    #[cfg(feature = "strict-schema")]
+    #[test]
+    fn unknown_field_is_an_error_with_strict_schema() {
+        let res: Result<MarketSummary, _> = serde_json::from_str(MARKET_SUMMARY_EXTRA_FIELD);
+        assert_that(&res.is_err()).is_true();
+    }
+
+    #[cfg(not(feature = "strict-schema"))]
+    #[test]
+    fn unknown_field_is_ignored() {
+        let res: Result<MarketSummary, _> = serde_json::from_str(MARKET_SUMMARY_EXTRA_FIELD);
+        assert_that(&res.is_ok()).is_true();
+    }
+
+    fn mock_api(mock: MockTransport) -> (Public, Arc<MockTransport>) {
+        let mock = Arc::new(mock);
+        (Public::with_transport(mock.clone()), mock)
+    }
+
+    fn currency_codes_mock() -> MockTransport {
+        MockTransport::new()
+            .with_json("GetValidPrimaryCurrencyCodes", r#"["Xbt","Eth"]"#)
+            .with_json("GetValidSecondaryCurrencyCodes", r#"["Aud","Usd"]"#)
+            .with_json("GetOrderBook", ORDER_BOOK)
+    }
+
+    fn request_count(mock: &MockTransport, method: &str) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|url| url.path().ends_with(method))
+            .count()
+    }
+
     #[tokio::test]
-    async fn can_get_market_summary_xbt_aud() {
-        let api = Public::default();
-        let _ = api
-            .get_market_summary("Xbt", "Aud")
-            .await
-            .expect("API call failed");
+    async fn invalid_currency_code_is_rejected_locally() {
+        let (api, mock) = mock_api(currency_codes_mock());
+        let api = api.with_code_validation();
+
+        let err = api.get_order_book("Btc", "Aud").await.unwrap_err();
+        assert_that(&err.to_string()).is_equal_to("invalid primary currency code: Btc".to_string());
+        let err = api.get_order_book("Xbt", "Nzd").await.unwrap_err();
+        assert_that(&err.to_string())
+            .is_equal_to("invalid secondary currency code: Nzd".to_string());
+        assert_that(&request_count(&mock, "GetOrderBook")).is_equal_to(0);
+
+        api.get_order_book("xbt", "Aud").await.unwrap();
+        assert_that(&request_count(&mock, "GetOrderBook")).is_equal_to(1);
+        // Valid codes are only fetched once.
+        assert_that(&request_count(&mock, "GetValidPrimaryCurrencyCodes")).is_equal_to(1);
+        assert_that(&request_count(&mock, "GetValidSecondaryCurrencyCodes")).is_equal_to(1);
     }
 
     #[tokio::test]
-    async fn can_get_order_book_xbt_aud() {
+    async fn valid_lists_are_cached() {
+        let (api, mock) =
+            mock_api(MockTransport::new().with_json("GetValidOrderTypes", r#"["LimitBid"]"#));
+
+        api.get_valid_order_types().await.unwrap();
+        let got = api.clone().get_valid_order_types().await.unwrap();
+        assert_that(&got).is_equal_to(vec!["LimitBid".to_string()]);
+        assert_that(&request_count(&mock, "GetValidOrderTypes")).is_equal_to(1);
+
+        api.refresh();
+        api.get_valid_order_types().await.unwrap();
+        assert_that(&request_count(&mock, "GetValidOrderTypes")).is_equal_to(2);
+
+        let api = api.with_lists_ttl(Duration::from_secs(0));
+        api.get_valid_order_types().await.unwrap();
+        assert_that(&request_count(&mock, "GetValidOrderTypes")).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn currency_codes_are_not_checked_by_default() {
+        let (api, mock) = mock_api(currency_codes_mock());
+
+        api.get_order_book("Btc", "Aud").await.unwrap();
+
+        assert_that(&request_count(&mock, "GetValidPrimaryCurrencyCodes")).is_equal_to(0);
+    }
+
+    #[test]
+    fn base_url_can_be_overridden() {
         let api = Public::default();
-        let _ = api
-            .get_order_book("Xbt", "Aud")
-            .await
-            .expect("API call failed");
+        assert_that(&api.build_url("GetFxRates").unwrap().as_str())
+            .is_equal_to("https://api.independentreserve.com/Public/GetFxRates");
+
+        let api = api.with_url("http://127.0.0.1:8080/Public");
+        assert_that(&api.build_url("GetFxRates").unwrap().as_str())
+            .is_equal_to("http://127.0.0.1:8080/Public/GetFxRates");
+    }
+
+    #[tokio::test]
+    async fn get_market_summary_from_mock() {
+        let (api, mock) =
+            mock_api(MockTransport::new().with_json("GetMarketSummary", MARKET_SUMMARY));
+
+        let summary = api.get_market_summary("Xbt", "Usd").await.unwrap();
+
+        assert_that(&summary.mid_price()).is_equal_to(Some(Decimal::new(7505, 1)));
+        assert_that(&summary.last()).is_equal_to(Some(Decimal::from(510)));
+        let url = &mock.requests()[0];
+        assert_that(&url.query())
+            .is_equal_to(Some("primaryCurrencyCode=Xbt&secondaryCurrencyCode=Usd"));
+    }
+
+    #[tokio::test]
+    async fn get_order_book_from_mock() {
+        let (api, _) = mock_api(MockTransport::new().with_json("GetOrderBook", ORDER_BOOK));
+
+        let book = api.get_order_book("Xbt", "Usd").await.unwrap();
+
+        assert_that(&book.buy_orders.len()).is_equal_to(2);
+        assert_that(&book.sell_orders[0].price).is_equal_to(Some(Decimal::from(500)));
+    }
+
+    #[tokio::test]
+    async fn get_order_book_top_trims_each_side() {
+        let (api, _) = mock_api(MockTransport::new().with_json("GetOrderBook", ORDER_BOOK));
+
+        let book = api.get_order_book_top("Xbt", "Usd", 1).await.unwrap();
+
+        assert_that(&book.buys.len()).is_equal_to(1);
+        assert_that(&book.sells.len()).is_equal_to(1);
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::new(49702, 2)));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from(500)));
+    }
+
+    #[tokio::test]
+    async fn error_status_from_mock_is_an_api_error() {
+        let res = HttpResponse::new(
+            StatusCode::BAD_REQUEST,
+            r#"{"Message":"Invalid currency."}"#,
+        );
+        let (api, mock) = mock_api(MockTransport::new().with_response("GetOrderBook", res));
+
+        let err = api.get_order_book("Xbt", "Foo").await.unwrap_err();
+        let api_err = err.downcast_ref::<ApiError>().unwrap();
+
+        assert_that(&api_err.is_validation()).is_true();
+        assert_that(&api_err.message()).is_equal_to(Some("Invalid currency.".to_string()));
+        // Client errors are not retried.
+        assert_that(&mock.requests().len()).is_equal_to(1);
+    }
+
+    #[tokio::test]
+    async fn error_response_is_logged_within_request_spans() {
+        let buf = crate::trace::tests::TraceBuf::default();
+        let writer = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let res = HttpResponse::new(StatusCode::BAD_REQUEST, "Invalid currency.");
+        let (api, _) = mock_api(MockTransport::new().with_response("GetOrderBook", res));
+        let _ = api.get_order_book("Xbt", "Foo").await;
+
+        let out = buf.contents();
+        assert_that(&out.contains("get_order_book{base=\"Xbt\" quote=\"Foo\"}")).is_true();
+        assert_that(&out.contains("endpoint=\"/Public/GetOrderBook\"")).is_true();
+        assert_that(&out.contains("400 Bad Request")).is_true();
+        assert_that(&out.contains("Invalid currency.")).is_true();
+    }
+
+    #[tokio::test]
+    async fn transient_errors_from_mock_are_retried() {
+        let unavailable = HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, "");
+        let mock = MockTransport::new()
+            .with_response("GetMarketSummary", unavailable)
+            .with_json("GetMarketSummary", MARKET_SUMMARY);
+        let (api, mock) = mock_api(mock);
+        let api = api.with_retry(RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::from_millis(1),
+        });
+
+        api.get_market_summary("Xbt", "Usd").await.unwrap();
+
+        assert_that(&mock.requests().len()).is_equal_to(2);
+    }
+
+    #[tokio::test]
+    async fn malformed_body_from_mock_is_a_decode_error() {
+        let (api, _) = mock_api(MockTransport::new().with_json("GetOrderBook", "<html></html>"));
+
+        let err = api.get_order_book("Xbt", "Usd").await.unwrap_err();
+
+        assert_that(&err.downcast_ref::<ApiError>().is_some()).is_true();
+        assert_that(&err.to_string().contains("GetOrderBook")).is_true();
     }
 
     #[tokio::test]
     async fn can_get_trade_history_summary_xbt_aud() {
         let api = Public::default();
         let _ = api
-            .get_trade_history_summary("Xbt", "Aud", 1)
+            .get_trade_history_summary("Xbt", "Aud", HoursPast::new(1).unwrap())
             .await
             .expect("API call failed");
     }
@@ -453,9 +1267,324 @@ mod tests {
             .expect("API call failed");
     }
 
+    const TRADE_HISTORY_SUMMARY: &str = r#"{
+        "CreatedTimestampUtc": "2020-04-08T02:00:00.5718327Z",
+        "HistorySummaryItems": [
+            {
+                "AverageSecondaryCurrencyPrice": 11000.0,
+                "ClosingSecondaryCurrencyPrice": 11000.0,
+                "StartTimestampUtc": "2020-04-08T00:00:00Z",
+                "EndTimestampUtc": "2020-04-08T01:00:00Z",
+                "HighestSecondaryCurrencyPrice": 11000.0,
+                "LowestSecondaryCurrencyPrice": 11000.0,
+                "NumberOfTrades": 2,
+                "OpeningSecondaryCurrencyPrice": 11000.0,
+                "PrimaryCurrencyVolume": 1.0,
+                "SecondaryCurrencyVolume": 11000.0
+            },
+            {
+                "AverageSecondaryCurrencyPrice": 12000.0,
+                "ClosingSecondaryCurrencyPrice": 12000.0,
+                "StartTimestampUtc": "2020-04-08T01:00:00Z",
+                "EndTimestampUtc": "2020-04-08T02:00:00Z",
+                "HighestSecondaryCurrencyPrice": 12000.0,
+                "LowestSecondaryCurrencyPrice": 12000.0,
+                "NumberOfTrades": 1,
+                "OpeningSecondaryCurrencyPrice": 12000.0,
+                "PrimaryCurrencyVolume": 3.0,
+                "SecondaryCurrencyVolume": 36000.0
+            }
+        ],
+        "NumberOfHoursInThePastToRetrieve": 2,
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Aud"
+    }"#;
+
+    #[test]
+    fn trade_history_summary_accessors() {
+        let summary: TradeHistorySummary = serde_json::from_str(TRADE_HISTORY_SUMMARY).unwrap();
+
+        assert_that(&summary.items().len()).is_equal_to(2);
+        let first = &summary.items()[0];
+        assert_that(&first.start()).is_equal_to(parse_timestamp("2020-04-08T00:00:00Z").unwrap());
+        assert_that(&first.end()).is_equal_to(parse_timestamp("2020-04-08T01:00:00Z").unwrap());
+        assert_that(&first.average_price()).is_equal_to(Some(Decimal::from(11_000)));
+        assert_that(&first.number_of_trades()).is_equal_to(2);
+
+        assert_that(&summary.volume()).is_equal_to(Decimal::from(4));
+        // (11,000 + 36,000) / 4
+        assert_that(&summary.vwap()).is_equal_to(Some(Decimal::from(11_750)));
+    }
+
+    #[test]
+    fn trade_history_vwap_skips_hours_missing_a_volume() {
+        let with_null = |hour: usize, field: &str| {
+            let mut json: serde_json::Value = serde_json::from_str(TRADE_HISTORY_SUMMARY).unwrap();
+            json["HistorySummaryItems"][hour][field] = serde_json::Value::Null;
+            serde_json::from_value::<TradeHistorySummary>(json).unwrap()
+        };
+
+        // Only the second hour counts, 36,000 / 3.
+        let summary = with_null(0, "SecondaryCurrencyVolume");
+        assert_that(&summary.vwap()).is_equal_to(Some(Decimal::from(12_000)));
+
+        // Only the first hour counts, 11,000 / 1.
+        let summary = with_null(1, "PrimaryCurrencyVolume");
+        assert_that(&summary.vwap()).is_equal_to(Some(Decimal::from(11_000)));
+    }
+
+    #[test]
+    fn trade_history_summary_candles() {
+        let summary: TradeHistorySummary = serde_json::from_str(
+            r#"{
+                "CreatedTimestampUtc": "2020-04-08T02:00:00Z",
+                "HistorySummaryItems": [
+                    {
+                        "AverageSecondaryCurrencyPrice": 11200.0,
+                        "ClosingSecondaryCurrencyPrice": 11100.0,
+                        "StartTimestampUtc": "2020-04-08T00:00:00Z",
+                        "EndTimestampUtc": "2020-04-08T01:00:00Z",
+                        "HighestSecondaryCurrencyPrice": 11500.0,
+                        "LowestSecondaryCurrencyPrice": 10900.0,
+                        "NumberOfTrades": 3,
+                        "OpeningSecondaryCurrencyPrice": 11300.0,
+                        "PrimaryCurrencyVolume": 2.5,
+                        "SecondaryCurrencyVolume": 28000.0
+                    },
+                    {
+                        "AverageSecondaryCurrencyPrice": null,
+                        "ClosingSecondaryCurrencyPrice": null,
+                        "StartTimestampUtc": "2020-04-08T01:00:00Z",
+                        "EndTimestampUtc": "2020-04-08T02:00:00Z",
+                        "HighestSecondaryCurrencyPrice": null,
+                        "LowestSecondaryCurrencyPrice": null,
+                        "NumberOfTrades": 0,
+                        "OpeningSecondaryCurrencyPrice": null,
+                        "PrimaryCurrencyVolume": 0.0,
+                        "SecondaryCurrencyVolume": 0.0
+                    }
+                ],
+                "NumberOfHoursInThePastToRetrieve": 2,
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Aud"
+            }"#,
+        )
+        .unwrap();
+
+        let candles = summary.candles();
+
+        assert_that(&candles).is_equal_to(vec![Candle {
+            start: parse_timestamp("2020-04-08T00:00:00Z").unwrap(),
+            end: parse_timestamp("2020-04-08T01:00:00Z").unwrap(),
+            open: Decimal::from(11_300),
+            high: Decimal::from(11_500),
+            low: Decimal::from(10_900),
+            close: Decimal::from(11_100),
+            volume: Decimal::new(25, 1),
+        }]);
+        assert_that(&candles[0].body()).is_equal_to(Decimal::from(-200));
+        assert_that(&candles[0].range()).is_equal_to(Decimal::from(600));
+    }
+
+    #[test]
+    fn hours_past_is_validated() {
+        assert_that(&HoursPast::new(1).unwrap().get()).is_equal_to(1);
+        assert_that(&HoursPast::new(HoursPast::MAX).is_ok()).is_true();
+        assert_that(&HoursPast::new(0).is_err()).is_true();
+        assert_that(&HoursPast::new(HoursPast::MAX + 1).is_err()).is_true();
+    }
+
+    const RECENT_TRADES: &str = r#"{
+        "CreatedTimestampUtc": "2020-04-08T01:50:00.5718327Z",
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Aud",
+        "Trades": [
+            {
+                "PrimaryCurrencyAmount": 0.1,
+                "SecondaryCurrencyTradePrice": 11500.0,
+                "TradeTimestampUtc": "2020-04-08T01:49:00Z"
+            },
+            {
+                "PrimaryCurrencyAmount": 0.2,
+                "SecondaryCurrencyTradePrice": 11480.0,
+                "TradeTimestampUtc": "2020-04-08T01:40:00Z"
+            },
+            {
+                "PrimaryCurrencyAmount": 0.3,
+                "SecondaryCurrencyTradePrice": 11450.0,
+                "TradeTimestampUtc": "2020-04-08T01:30:00Z"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn trades_since_trims_older_trades() {
+        let recent: RecentTrades = serde_json::from_str(RECENT_TRADES).unwrap();
+        let since = parse_timestamp("2020-04-08T01:35:00Z").unwrap();
+
+        assert_that(&recent.covers(since, 3)).is_true();
+
+        let trades = recent.into_trades_since(since);
+        let got: Vec<DateTime<Utc>> = trades.iter().map(Trade::timestamp).collect();
+
+        assert_that(&got).is_equal_to(vec![
+            parse_timestamp("2020-04-08T01:40:00Z").unwrap(),
+            parse_timestamp("2020-04-08T01:49:00Z").unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn full_batch_newer_than_since_does_not_cover() {
+        let recent: RecentTrades = serde_json::from_str(RECENT_TRADES).unwrap();
+        let since = parse_timestamp("2020-04-08T01:00:00Z").unwrap();
+
+        assert_that(&recent.covers(since, 3)).is_false();
+        assert_that(&recent.covers(since, 10)).is_true();
+    }
+
+    // GetRecentTrades response with one trade at each of `minutes` past 01:00,
+    // most recent first as the exchange returns them.
+    fn recent_trades_json(minutes: impl Iterator<Item = u32>) -> String {
+        let trades: Vec<String> = minutes
+            .map(|m| {
+                format!(
+                    r#"{{"PrimaryCurrencyAmount": 1, "SecondaryCurrencyTradePrice": 100,
+                        "TradeTimestampUtc": "2020-04-08T01:{:02}:00Z"}}"#,
+                    m
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"CreatedTimestampUtc": "2020-04-08T01:50:00Z", "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Aud", "Trades": [{}]}}"#,
+            trades.join(",")
+        )
+    }
+
+    #[tokio::test]
+    async fn trades_since_fetches_larger_batches_until_cutoff() {
+        // First batch is full and newer than `since`, the second overlaps it
+        // and reaches back past `since`.
+        let (api, mock) = mock_api(
+            MockTransport::new()
+                .with_json("GetRecentTrades", recent_trades_json((41..=50).rev()))
+                .with_json("GetRecentTrades", recent_trades_json((31..=50).rev())),
+        );
+        let since = parse_timestamp("2020-04-08T01:35:00Z").unwrap();
+
+        let trades = api.trades_since("Xbt", "Aud", since).await.unwrap();
+
+        let batches: Vec<String> = mock
+            .requests()
+            .iter()
+            .filter_map(|url| {
+                url.query_pairs()
+                    .find(|(k, _)| k == "numberOfRecentTradesToRetrieve")
+                    .map(|(_, v)| v.into_owned())
+            })
+            .collect();
+        assert_that(&batches).is_equal_to(vec!["10".to_string(), "20".to_string()]);
+
+        // Each trade at or after the cutoff once, oldest first.
+        let got: Vec<DateTime<Utc>> = trades.iter().map(Trade::timestamp).collect();
+        let want: Vec<DateTime<Utc>> = (35..=50)
+            .map(|m| parse_timestamp(&format!("2020-04-08T01:{:02}:00Z", m)).unwrap())
+            .collect();
+        assert_that(&got).is_equal_to(want);
+    }
+
+    fn recent_trades(trades: &[(u32, u32)]) -> RecentTrades {
+        let ts = parse_timestamp("2020-04-08T01:50:00Z").unwrap();
+        RecentTrades {
+            trades: trades
+                .iter()
+                .map(|(amount, price)| Trade {
+                    primary_currency_amount: Some(Decimal::from(*amount)),
+                    secondary_currency_trade_price: Some(Decimal::from(*price)),
+                    trade_timestamp_utc: ts,
+                })
+                .collect(),
+            created_timestamp_utc: ts,
+            primary_currency_code: "Xbt".to_string(),
+            secondary_currency_code: "Aud".to_string(),
+        }
+    }
+
+    #[test]
+    fn recent_trades_vwap() {
+        let recent = recent_trades(&[(1, 100), (3, 120), (0, 500)]);
+
+        assert_that(&recent.iter().count()).is_equal_to(3);
+        // (100 + 360) / 4
+        assert_that(&recent.vwap()).is_equal_to(Some(Decimal::from(115)));
+    }
+
+    #[test]
+    fn recent_trades_vwap_of_no_trades_is_none() {
+        assert_that(&recent_trades(&[]).vwap()).is_none();
+    }
+
     #[tokio::test]
     async fn can_get_fx_rates() {
         let api = Public::default();
         let _ = api.get_fx_rates().await.expect("API call failed");
     }
+
+    fn market_summary(bid: Option<u32>, ask: Option<u32>) -> MarketSummary {
+        MarketSummary {
+            created_timestamp_utc: parse_timestamp("2020-01-01T00:00:00Z").unwrap(),
+            current_highest_bid_price: bid.map(Decimal::from),
+            current_lowest_offer_price: ask.map(Decimal::from),
+            day_avg_price: None,
+            day_highest_price: None,
+            day_lowest_price: None,
+            day_volume_xbt: None,
+            day_volume_xbt_in_secondary_currrency: None,
+            last_price: Some(Decimal::from(100)),
+            primary_currency_code: "Xbt".to_string(),
+            secondary_currency_code: "Aud".to_string(),
+        }
+    }
+
+    #[test]
+    fn market_summary_mid_price() {
+        let summary = market_summary(Some(99), Some(102));
+        assert_that(&summary.mid_price()).is_equal_to(Some(Decimal::new(1005, 1)));
+        assert_that(&summary.last()).is_equal_to(Some(Decimal::from(100)));
+
+        assert_that(&market_summary(None, Some(102)).mid_price()).is_none();
+        assert_that(&market_summary(Some(99), None).mid_price()).is_none();
+    }
+
+    const FX_RATES: &str = r#"[
+        {"CurrencyCodeA": "Aud", "CurrencyCodeB": "Usd", "Rate": 0.8},
+        {"CurrencyCodeA": "Usd", "CurrencyCodeB": "Nzd", "Rate": null}
+    ]"#;
+
+    #[test]
+    fn fx_rate_direct() {
+        let rates: FxRates = serde_json::from_str(FX_RATES).unwrap();
+
+        assert_that(&rates.get("Aud", "Usd")).is_equal_to(Some(Decimal::new(8, 1)));
+        assert_that(&rates.get("aud", "usd")).is_equal_to(Some(Decimal::new(8, 1)));
+    }
+
+    #[test]
+    fn fx_rate_inverse() {
+        let rates: FxRates = serde_json::from_str(FX_RATES).unwrap();
+
+        assert_that(&rates.get("Usd", "Aud")).is_equal_to(Some(Decimal::new(125, 2)));
+    }
+
+    #[test]
+    fn fx_rate_missing() {
+        let rates: FxRates = serde_json::from_str(FX_RATES).unwrap();
+
+        // Quoted by the exchange without a rate.
+        assert_that(&rates.get("Usd", "Nzd")).is_none();
+        assert_that(&rates.get("Nzd", "Usd")).is_none();
+        // Not quoted at all.
+        assert_that(&rates.get("Usd", "Eur")).is_none();
+        assert_that(&rates.iter().count()).is_equal_to(2);
+    }
 }
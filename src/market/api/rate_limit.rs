@@ -0,0 +1,70 @@
+//! Rate limit state reported by the exchange in response headers.
+//!
+//! Responses may include `X-RateLimit-Remaining`, the number of requests left
+//! in the current window, and `X-RateLimit-Reset`, the Unix time (seconds) at
+//! which the window resets. We record these so callers can back off before the
+//! exchange starts returning 429.
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::HeaderMap;
+
+const REMAINING: &str = "x-ratelimit-remaining";
+const RESET: &str = "x-ratelimit-reset";
+
+/// Rate limit state as of the last response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests remaining before we are rate limited.
+    pub remaining: u32,
+    /// When the number of remaining requests resets.
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimit {
+    /// Parse the rate limit headers in `headers`, `None` if either is missing
+    /// or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name| headers.get(name)?.to_str().ok().map(str::trim);
+
+        let remaining = header(REMAINING)?.parse().ok()?;
+        let reset = header(RESET)?.parse().ok()?;
+        let reset = Utc.timestamp_opt(reset, 0).single()?;
+
+        Some(RateLimit { remaining, reset })
+    }
+
+    /// True if there are no requests remaining before `reset`.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use spectral::prelude::*;
+
+    fn headers(remaining: &'static str, reset: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from_static(remaining));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from_static(reset));
+        headers
+    }
+
+    #[test]
+    fn parses_rate_limit_headers() {
+        let got = RateLimit::from_headers(&headers("42", "1590969600")).unwrap();
+
+        assert_that(&got.remaining).is_equal_to(42);
+        assert_that(&got.reset).is_equal_to(Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap());
+        assert_that(&got.is_exhausted()).is_false();
+    }
+
+    #[test]
+    fn missing_or_malformed_headers() {
+        assert_that(&RateLimit::from_headers(&HeaderMap::new())).is_none();
+        assert_that(&RateLimit::from_headers(&headers("lots", "1590969600"))).is_none();
+        assert_that(&RateLimit::from_headers(&headers("0", "soon"))).is_none();
+    }
+}
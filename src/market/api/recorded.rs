@@ -0,0 +1,94 @@
+//! Recorded exchange responses in testdata/.
+//!
+//! Checks that our models still parse real-world payloads, without touching
+//! the network. To add a fixture save the response body as
+//! testdata/<Method>.json and list it in `recorded_responses_deserialize`.
+
+use super::{from_json, Accounts, MarketSummary, OrderBook, OrderDetails, OrderStatus};
+use crate::market::ApiError;
+use anyhow::Result;
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use spectral::prelude::*;
+
+// Deserialize `body` as the response returned by `endpoint`.
+fn parses<T: DeserializeOwned>(endpoint: &str, body: &str) -> Result<()> {
+    from_json::<T>(endpoint, body).map(|_| ())
+}
+
+#[test]
+fn recorded_responses_deserialize() {
+    type Parser = fn(&str, &str) -> Result<()>;
+    let fixtures: &[(&str, &str, Parser)] = &[
+        (
+            "GetOrderBook",
+            include_str!("../../../testdata/GetOrderBook.json"),
+            parses::<OrderBook>,
+        ),
+        (
+            "GetMarketSummary",
+            include_str!("../../../testdata/GetMarketSummary.json"),
+            parses::<MarketSummary>,
+        ),
+        (
+            "GetAccounts",
+            include_str!("../../../testdata/GetAccounts.json"),
+            parses::<Accounts>,
+        ),
+        (
+            "GetOrderDetails",
+            include_str!("../../../testdata/GetOrderDetails.json"),
+            parses::<OrderDetails>,
+        ),
+    ];
+
+    for (endpoint, body, parse) in fixtures {
+        if let Err(e) = parse(endpoint, body) {
+            panic!("recorded {} response: {:#}", endpoint, e);
+        }
+    }
+}
+
+#[test]
+fn recorded_order_book() {
+    let body = include_str!("../../../testdata/GetOrderBook.json");
+    let book: OrderBook = from_json("GetOrderBook", body).unwrap();
+
+    assert_that(&book.buy_orders.len()).is_equal_to(3);
+    assert_that(&book.sell_orders[0].price).is_equal_to(Some(Decimal::new(1_356_091, 2)));
+}
+
+#[test]
+fn recorded_accounts() {
+    let body = include_str!("../../../testdata/GetAccounts.json");
+    let accounts: Accounts = from_json("GetAccounts", body).unwrap();
+
+    assert_that(&accounts.available_in("Aud")).is_equal_to(Some(Decimal::new(1401, 2)));
+    assert_that(&accounts.total_in("Xbt")).is_equal_to(Some(Decimal::new(4681, 2)));
+}
+
+#[test]
+fn recorded_order_details() {
+    let body = include_str!("../../../testdata/GetOrderDetails.json");
+    let details: OrderDetails = from_json("GetOrderDetails", body).unwrap();
+
+    assert_that(&details.order_guid()).is_equal_to("c7347e4c-b865-4c94-8f74-d934d4b0b177");
+    assert_that(details.status()).is_equal_to(&OrderStatus::PartiallyFilled);
+    assert_that(&details.volume_filled()).is_equal_to(Decimal::new(25, 2));
+    assert_that(&details.avg_price()).is_equal_to(Decimal::new(4_710_050, 2));
+    assert_that(&details.is_terminal()).is_false();
+}
+
+#[test]
+fn recorded_error_response() {
+    let err = ApiError::Http {
+        status: StatusCode::BAD_REQUEST,
+        body: include_str!("../../../testdata/error-invalid-nonce.json").to_string(),
+    };
+
+    assert_that(&err.message()).is_equal_to(Some(
+        "Invalid nonce: 1586310453. Nonce must be greater than 1586310454.".to_string(),
+    ));
+    assert_that(&err.is_validation()).is_true();
+}
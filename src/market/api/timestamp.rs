@@ -0,0 +1,87 @@
+//! Serde helpers for the ISO 8601 timestamps used by the exchange.
+//!
+//! Use as `#[serde(with = "timestamp")]`, or `#[serde(default, with =
+//! "timestamp::option")]` for nullable timestamps.
+
+use super::parse_timestamp;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S>(t: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&t.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_timestamp(&s).map_err(D::Error::custom)
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(t: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match t {
+            Some(t) => super::serialize(t, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => parse_timestamp(&s).map(Some).map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde::Deserialize;
+    use spectral::prelude::*;
+
+    #[derive(Deserialize)]
+    struct Created {
+        #[serde(with = "super")]
+        at: DateTime<Utc>,
+    }
+
+    fn created(json: &str) -> DateTime<Utc> {
+        serde_json::from_str::<Created>(json).unwrap().at
+    }
+
+    #[test]
+    fn deserializes_whole_seconds() {
+        assert_that(&created(r#"{"at": "2014-08-01T08:00:00Z"}"#))
+            .is_equal_to(Utc.with_ymd_and_hms(2014, 8, 1, 8, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn deserializes_fractional_seconds() {
+        let want = Utc.with_ymd_and_hms(2014, 8, 5, 6, 42, 11).unwrap()
+            + chrono::Duration::nanoseconds(303_220_800);
+
+        assert_that(&created(r#"{"at": "2014-08-05T06:42:11.3032208Z"}"#)).is_equal_to(want);
+        // Some endpoints omit the offset.
+        assert_that(&created(r#"{"at": "2014-08-05T06:42:11.3032208"}"#)).is_equal_to(want);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let res = serde_json::from_str::<Created>(r#"{"at": "yesterday"}"#);
+        assert_that(&res.is_err()).is_true();
+    }
+}
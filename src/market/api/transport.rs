@@ -0,0 +1,213 @@
+//! The HTTP transport used by the API clients.
+//!
+//! `Public` and `Private` make requests through an `HttpTransport`, by default
+//! `ReqwestTransport`. Tests can use `MockTransport` to return canned
+//! responses without touching the network.
+
+use crate::market::ApiError;
+use anyhow::{anyhow, Result};
+use futures::future::{BoxFuture, FutureExt};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Client, RequestBuilder, StatusCode,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+};
+use url::Url;
+
+/// Makes HTTP requests on behalf of the API clients.
+pub trait HttpTransport: fmt::Debug + Send + Sync {
+    /// GET `url`.
+    fn get(&self, url: Url) -> BoxFuture<'_, Result<HttpResponse>>;
+
+    /// POST `body`, serialized JSON, to `url`. The body is sent as is so the
+    /// order of its keys is preserved.
+    fn post(&self, url: Url, body: Vec<u8>) -> BoxFuture<'_, Result<HttpResponse>>;
+}
+
+/// A response read in full.
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// A response with `status`, `body`, and no headers.
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        HttpResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+}
+
+/// Transport that makes requests over the network using `reqwest`.
+#[derive(Clone, Debug)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn get(&self, url: Url) -> BoxFuture<'_, Result<HttpResponse>> {
+        send(self.client.get(url)).boxed()
+    }
+
+    fn post(&self, url: Url, body: Vec<u8>) -> BoxFuture<'_, Result<HttpResponse>> {
+        let req = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(body);
+        send(req).boxed()
+    }
+}
+
+// Send `req` and read the whole response.
+async fn send(req: RequestBuilder) -> Result<HttpResponse> {
+    let res = req.send().await.map_err(ApiError::Transport)?;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = res.text().await.map_err(ApiError::Transport)?;
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Transport that returns canned responses, keyed by API method name (the last
+/// segment of the request URL path) e.g., "GetOrderBook".
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    /// Responses are returned in order, the last one is repeated.
+    responses: Mutex<HashMap<String, VecDeque<HttpResponse>>>,
+    /// URLs requested so far.
+    requests: Mutex<Vec<Url>>,
+    /// Bodies posted so far.
+    bodies: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Respond to requests for `method` with `response`, after any responses
+    /// already added for `method`.
+    pub fn with_response(self, method: &str, response: HttpResponse) -> Self {
+        self.responses
+            .lock()
+            .expect("mock transport lock poisoned")
+            .entry(method.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Respond to requests for `method` with status 200 and `json`.
+    pub fn with_json(self, method: &str, json: impl Into<String>) -> Self {
+        self.with_response(method, HttpResponse::new(StatusCode::OK, json))
+    }
+
+    /// URLs requested so far, oldest first.
+    pub fn requests(&self) -> Vec<Url> {
+        self.requests
+            .lock()
+            .expect("mock transport lock poisoned")
+            .clone()
+    }
+
+    /// Bodies posted so far, oldest first.
+    pub fn bodies(&self) -> Vec<String> {
+        self.bodies
+            .lock()
+            .expect("mock transport lock poisoned")
+            .iter()
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+            .collect()
+    }
+
+    fn respond(&self, url: Url) -> Result<HttpResponse> {
+        let method = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_string();
+        self.requests
+            .lock()
+            .expect("mock transport lock poisoned")
+            .push(url);
+
+        let mut responses = self.responses.lock().expect("mock transport lock poisoned");
+        let queue = responses
+            .get_mut(&method)
+            .ok_or_else(|| anyhow!("no mock response for: {}", method))?;
+        let res = if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        };
+        res.ok_or_else(|| anyhow!("no mock response for: {}", method))
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn get(&self, url: Url) -> BoxFuture<'_, Result<HttpResponse>> {
+        futures::future::ready(self.respond(url)).boxed()
+    }
+
+    fn post(&self, url: Url, body: Vec<u8>) -> BoxFuture<'_, Result<HttpResponse>> {
+        self.bodies
+            .lock()
+            .expect("mock transport lock poisoned")
+            .push(body);
+        futures::future::ready(self.respond(url)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[tokio::test]
+    async fn mock_returns_responses_in_order_then_repeats_last() {
+        let mock = MockTransport::new()
+            .with_json("GetFxRates", "first")
+            .with_json("GetFxRates", "second");
+        let url = Url::parse("https://example.com/Public/GetFxRates").unwrap();
+
+        let mut got = vec![];
+        for _ in 0..3 {
+            got.push(mock.get(url.clone()).await.unwrap().body);
+        }
+
+        assert_that(&got).is_equal_to(vec![
+            "first".to_string(),
+            "second".to_string(),
+            "second".to_string(),
+        ]);
+        assert_that(&mock.requests().len()).is_equal_to(3);
+    }
+
+    #[tokio::test]
+    async fn mock_errors_for_unknown_method() {
+        let mock = MockTransport::new();
+        let url = Url::parse("https://example.com/Public/GetOrderBook").unwrap();
+
+        assert_that(&mock.get(url).await.is_err()).is_true();
+    }
+}
@@ -0,0 +1,242 @@
+//! Connect to the Binance public API.
+
+use super::{
+    api::{self, DEFAULT_TIMEOUT},
+    decimal_from_str_or_num, ApiError, Exchange, HttpResponse, HttpTransport, OrderBook, Pair,
+    ReqwestTransport, Summary,
+};
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::sync::Arc;
+use url::Url;
+
+/// Binance REST API URL.
+const URL: &str = "https://api.binance.com/api/v3";
+
+/// Number of levels to request on each side of the book.
+const ORDER_BOOK_DEPTH: &str = "100";
+
+#[derive(Clone, Debug)]
+pub struct Api {
+    transport: Arc<dyn HttpTransport>,
+    url: String,
+}
+
+impl Api {
+    /// Make requests using `transport` e.g., a `MockTransport` in tests.
+    pub fn with_transport(transport: Arc<dyn HttpTransport>) -> Self {
+        Api {
+            transport,
+            url: URL.to_string(),
+        }
+    }
+
+    /// Send requests to `url` instead of the Binance API.
+    pub fn with_url(self, url: impl ToString) -> Self {
+        Api {
+            url: url.to_string(),
+            ..self
+        }
+    }
+
+    /// Get the order book for Binance `symbol` e.g., "BTCUSDT".
+    pub async fn order_book(&self, symbol: &str) -> Result<OrderBook> {
+        let url = Url::parse_with_params(&format!("{}/depth", self.url), &[
+            ("symbol", symbol),
+            ("limit", ORDER_BOOK_DEPTH),
+        ])?;
+
+        let res = self.get(url).await?;
+        parse_order_book(&res.body)
+    }
+
+    /// Get the 24 hour ticker for Binance `symbol` e.g., "BTCUSDT".
+    pub async fn market_summary(&self, symbol: &str) -> Result<Summary> {
+        let url =
+            Url::parse_with_params(&format!("{}/ticker/24hr", self.url), &[("symbol", symbol)])?;
+
+        let res = self.get(url).await?;
+        parse_ticker(&res.body)
+    }
+
+    // GET `url`, non-200 responses are an `ApiError`.
+    async fn get(&self, url: Url) -> Result<HttpResponse> {
+        let res = self.transport.get(url).await?;
+        if !res.status.is_success() {
+            return Err(ApiError::Http {
+                status: res.status,
+                body: res.body,
+            }
+            .into());
+        }
+
+        Ok(res)
+    }
+}
+
+impl Default for Api {
+    fn default() -> Self {
+        let client =
+            api::client_with_timeout(DEFAULT_TIMEOUT).expect("failed to build default HTTP client");
+        Api::with_transport(Arc::new(ReqwestTransport::new(client)))
+    }
+}
+
+impl Exchange for Api {
+    fn order_book<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<OrderBook>> {
+        async move { self.order_book(&symbol(pair)).await }.boxed()
+    }
+
+    fn market_summary<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<Summary>> {
+        async move { self.market_summary(&symbol(pair)).await }.boxed()
+    }
+}
+
+/// Binance symbol for `pair` e.g., Xbt/Usdt -> "BTCUSDT". Binance quotes BTC
+/// in stablecoins, not USD.
+pub fn symbol(pair: &Pair) -> String {
+    format!("{}{}", code(&pair.base), code(&pair.quote))
+}
+
+// Binance currency code for `code`, Binance uses BTC where IR uses Xbt.
+fn code(code: &str) -> String {
+    if code.eq_ignore_ascii_case("Xbt") {
+        "BTC".to_string()
+    } else {
+        code.to_ascii_uppercase()
+    }
+}
+
+/// Order book as returned by Binance.
+#[derive(Debug, Deserialize)]
+struct BinanceOrderBook {
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+}
+
+/// Price and quantity, strings to preserve precision.
+#[derive(Debug, Deserialize)]
+struct Level(
+    #[serde(deserialize_with = "decimal_from_str_or_num")] Decimal,
+    #[serde(deserialize_with = "decimal_from_str_or_num")] Decimal,
+);
+
+// Parse the body of a Binance order book response.
+fn parse_order_book(body: &str) -> Result<OrderBook> {
+    let book: BinanceOrderBook = api::from_json("binance depth", body)?;
+
+    let levels = |levels: Vec<Level>| -> Vec<(Decimal, Decimal)> {
+        levels.into_iter().map(|l| (l.0, l.1)).collect()
+    };
+    Ok(OrderBook::from_levels(levels(book.bids), levels(book.asks)))
+}
+
+/// The fields we use from a Binance 24 hour ticker.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker {
+    #[serde(deserialize_with = "decimal_from_str_or_num")]
+    bid_price: Decimal,
+    #[serde(deserialize_with = "decimal_from_str_or_num")]
+    ask_price: Decimal,
+    #[serde(deserialize_with = "decimal_from_str_or_num")]
+    last_price: Decimal,
+}
+
+// Parse the body of a Binance 24 hour ticker response.
+fn parse_ticker(body: &str) -> Result<Summary> {
+    let ticker: BinanceTicker = api::from_json("binance ticker/24hr", body)?;
+
+    Ok(Summary {
+        best_bid: Some(ticker.bid_price),
+        best_ask: Some(ticker.ask_price),
+        last: Some(ticker.last_price),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{MockTransport, Position};
+    use reqwest::StatusCode;
+    use spectral::prelude::*;
+    use std::str::FromStr;
+
+    const RECORDED: &str = include_str!("../../testdata/binance-orderbook.json");
+
+    #[test]
+    fn parses_recorded_order_book() {
+        let book = parse_order_book(RECORDED).unwrap();
+
+        assert_that(&book.buys).has_length(3);
+        assert_that(&book.sells).has_length(3);
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from_str("9455.01").unwrap()));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from_str("9456.99").unwrap()));
+        let price = Decimal::from_str("9457.2").unwrap();
+        assert_that(&book.cumulative_volume_to_price(Position::Buy, price))
+            .is_equal_to(Decimal::new(212, 2));
+    }
+
+    #[test]
+    fn malformed_body_error_includes_body() {
+        let err = parse_order_book("<html>Service Unavailable</html>").unwrap_err();
+        let msg = format!("{:#}", err);
+
+        assert_that(&msg).contains("binance depth");
+        assert_that(&msg).contains("Service Unavailable");
+    }
+
+    #[tokio::test]
+    async fn market_summary_from_recorded_ticker() {
+        let mock = MockTransport::new()
+            .with_json("24hr", include_str!("../../testdata/binance-ticker.json"));
+        let api = Api::with_transport(Arc::new(mock));
+
+        let summary = api.market_summary("BTCUSDT").await.unwrap();
+
+        assert_that(&summary).is_equal_to(Summary {
+            best_bid: Some(Decimal::from_str("9455.01").unwrap()),
+            best_ask: Some(Decimal::from_str("9456.99").unwrap()),
+            last: Some(Decimal::from_str("9456.1").unwrap()),
+        });
+    }
+
+    #[test]
+    fn symbol_uses_binance_codes() {
+        assert_that(&symbol(&Pair::new("Xbt", "Usdt"))).is_equal_to("BTCUSDT".to_string());
+        assert_that(&symbol(&Pair::new("Eth", "Btc"))).is_equal_to("ETHBTC".to_string());
+    }
+
+    #[tokio::test]
+    async fn exchange_order_book_from_mock() {
+        let mock = Arc::new(MockTransport::new().with_json("depth", RECORDED));
+        let api = Api::with_transport(mock.clone());
+        let exchange: &dyn Exchange = &api;
+
+        let book = exchange
+            .order_book(&Pair::new("Xbt", "Usdt"))
+            .await
+            .unwrap();
+
+        assert_that(&book.buys).has_length(3);
+        assert_that(&mock.requests()[0].query()).is_equal_to(Some("symbol=BTCUSDT&limit=100"));
+    }
+
+    #[tokio::test]
+    async fn error_status_is_an_api_error() {
+        let mock = MockTransport::new().with_response(
+            "depth",
+            HttpResponse::new(
+                StatusCode::BAD_REQUEST,
+                r#"{"code":-1121,"msg":"Invalid symbol."}"#,
+            ),
+        );
+        let api = Api::with_transport(Arc::new(mock));
+
+        let err = api.order_book("XBTUSDT").await.unwrap_err();
+
+        assert_that(&err.downcast_ref::<ApiError>().unwrap().is_validation()).is_true();
+    }
+}
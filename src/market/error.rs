@@ -0,0 +1,140 @@
+//! Errors returned by the exchange API.
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt;
+
+/// Error returned when an API call fails. Returned wrapped in an
+/// `anyhow::Error`, callers wishing to handle specific failures can
+/// `downcast_ref::<ApiError>()`.
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    /// The exchange returned a non-200 HTTP status.
+    #[error("api call returned status: {status}{}", Message(.body))]
+    Http { status: StatusCode, body: String },
+    /// The request could not be sent or the response could not be read.
+    #[error("api request failed")]
+    Transport(#[from] reqwest::Error),
+    /// The response body is not what we expected.
+    #[error("failed to decode api response")]
+    Decode(#[from] serde_json::Error),
+}
+
+impl ApiError {
+    /// The error message returned by the exchange, if any.
+    pub fn message(&self) -> Option<String> {
+        match self {
+            ApiError::Http { body, .. } => error_message(body),
+            _ => None,
+        }
+    }
+
+    /// True if we were rate limited.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// True if the exchange rejected our API key or signature.
+    pub fn is_auth(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(StatusCode::UNAUTHORIZED) | Some(StatusCode::FORBIDDEN)
+        )
+    }
+
+    /// The nonce the exchange requires ours to be greater than, if the request
+    /// was rejected for an invalid nonce e.g., "Invalid nonce: 1. Nonce must
+    /// be greater than 2.".
+    pub fn min_nonce(&self) -> Option<u64> {
+        let msg = self.message()?;
+        let rest = msg.split("Nonce must be greater than ").nth(1)?;
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    }
+
+    /// True if the exchange rejected the request parameters.
+    pub fn is_validation(&self) -> bool {
+        self.status() == Some(StatusCode::BAD_REQUEST)
+    }
+
+    fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+/// Error body returned by Independent Reserve.
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+fn error_message(body: &str) -> Option<String> {
+    serde_json::from_str::<ErrorBody>(body)
+        .ok()
+        .map(|b| b.message)
+}
+
+// Displays the exchange's error message (if any) for an HTTP error.
+struct Message<'a>(&'a str);
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match error_message(self.0) {
+            Some(msg) => write!(f, ": {}", msg),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn http(status: StatusCode, body: &str) -> ApiError {
+        ApiError::Http {
+            status,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn error_body_message_is_parsed() {
+        let err = http(
+            StatusCode::BAD_REQUEST,
+            r#"{"Message":"Invalid nonce: 1. Nonce must be greater than 2."}"#,
+        );
+
+        assert_that(&err.message()).is_equal_to(Some(
+            "Invalid nonce: 1. Nonce must be greater than 2.".to_string(),
+        ));
+        assert_that(&err.to_string()).is_equal_to(
+            "api call returned status: 400 Bad Request: Invalid nonce: 1. Nonce must be greater than 2."
+                .to_string(),
+        );
+        assert_that(&err.is_validation()).is_true();
+        assert_that(&err.min_nonce()).is_equal_to(Some(2));
+    }
+
+    #[test]
+    fn non_json_error_body_is_kept() {
+        let err = http(StatusCode::SERVICE_UNAVAILABLE, "<html>down</html>");
+
+        assert_that(&err.message()).is_none();
+        assert_that(&err.min_nonce()).is_none();
+        assert_that(&err.to_string())
+            .is_equal_to("api call returned status: 503 Service Unavailable".to_string());
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_that(&http(StatusCode::TOO_MANY_REQUESTS, "").is_rate_limited()).is_true();
+        assert_that(&http(StatusCode::UNAUTHORIZED, "").is_auth()).is_true();
+        assert_that(&http(StatusCode::FORBIDDEN, "").is_auth()).is_true();
+        assert_that(&http(StatusCode::FORBIDDEN, "").is_validation()).is_false();
+    }
+}
@@ -0,0 +1,83 @@
+//! A common interface to the exchanges we get market data from.
+//!
+//! Independent Reserve (`Market`), Kraken, and Binance return market data in
+//! different shapes, each backend converts its data into our types so the bots
+//! can be generic over the venue.
+
+use super::{MarketSummary, OrderBook, Pair};
+use anyhow::Result;
+use futures::future::BoxFuture;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// An exchange we can get market data from.
+pub trait Exchange: Send + Sync {
+    /// Get the order book for `pair`, currency codes as used by Independent
+    /// Reserve e.g., "Xbt". Backends map these to their own codes.
+    fn order_book<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<OrderBook>>;
+
+    /// Get the best bid/offer and last price for `pair`.
+    fn market_summary<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<Summary>>;
+}
+
+/// Best bid/offer and last traded price, the subset of a market summary all
+/// exchanges report.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    pub last: Option<Decimal>,
+}
+
+impl From<&MarketSummary> for Summary {
+    fn from(summary: &MarketSummary) -> Self {
+        Summary {
+            best_bid: summary.current_highest_bid_price,
+            best_ask: summary.current_lowest_offer_price,
+            last: summary.last_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::{binance, kraken, Market, MockTransport};
+    use spectral::prelude::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn exchanges_can_be_used_as_trait_objects() {
+        let ir = MockTransport::new().with_json(
+            "GetOrderBook",
+            include_str!("../../testdata/GetOrderBook.json"),
+        );
+        let binance = MockTransport::new().with_json(
+            "depth",
+            include_str!("../../testdata/binance-orderbook.json"),
+        );
+        let exchanges: Vec<(Box<dyn Exchange>, Pair)> = vec![
+            (
+                Box::new(Market::default().with_transport(Arc::new(ir))),
+                Pair::new("Xbt", "Aud"),
+            ),
+            (
+                Box::new(binance::Api::with_transport(Arc::new(binance))),
+                Pair::new("Xbt", "Usdt"),
+            ),
+            (
+                Box::new(kraken::Api::with_source(
+                    kraken::MockSource::new()
+                        .with_order_book(include_str!("../../testdata/kraken-orderbook.json")),
+                )),
+                Pair::new("Xbt", "Usd"),
+            ),
+        ];
+
+        for (exchange, pair) in exchanges.iter() {
+            let book = exchange.order_book(pair).await.unwrap();
+            assert_that(&book.best_bid()).is_some();
+            assert_that(&book.best_ask()).is_some();
+        }
+    }
+}
@@ -1,21 +1,113 @@
 //! Connect to kraken API.
 
+use crate::{
+    config::Key,
+    market::{decimal_from_str_or_num, Exchange, Number, OrderBook, Pair, Summary},
+};
+use anyhow::{anyhow, Context, Result};
 use coinnect::{
     error::Error,
     kraken::{KrakenApi, KrakenCreds},
 };
-use std::path::PathBuf;
-// use rust_decimal::Decimal;
-// use serde::{Deserialize, Serialize};
-// use serde_json::value::Value;
-// use std::{
-//     path::{Path, PathBuf},
-//     str::FromStr,
-// };
-
-#[derive(Debug)]
+use futures::future::{BoxFuture, FutureExt};
+use rust_decimal::Decimal;
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Number of levels to request on each side of the book.
+const ORDER_BOOK_DEPTH: usize = 100;
+
+/// Independent Reserve currency codes and the equivalent Kraken asset codes.
+const ASSET_CODES: &[(&str, &str)] = &[
+    ("Xbt", "XXBT"),
+    ("Eth", "XETH"),
+    ("Ltc", "XLTC"),
+    ("Xrp", "XXRP"),
+    ("Etc", "XETC"),
+    ("Xlm", "XXLM"),
+    ("Zec", "XZEC"),
+    ("Usd", "ZUSD"),
+    ("Eur", "ZEUR"),
+    ("Aud", "ZAUD"),
+    ("Gbp", "ZGBP"),
+    ("Cad", "ZCAD"),
+    ("Jpy", "ZJPY"),
+];
+
+/// Raw Kraken public market data, as returned by the coinnect client. Calls
+/// may block.
+pub trait Source: fmt::Debug + Send {
+    /// Order book for Kraken asset `pair`, `count` levels on each side.
+    fn order_book(&mut self, pair: &str, count: &str) -> Result<Map<String, Value>>;
+
+    /// Ticker information for Kraken asset `pair`.
+    fn ticker(&mut self, pair: &str) -> Result<Map<String, Value>>;
+}
+
+impl Source for KrakenApi {
+    fn order_book(&mut self, pair: &str, count: &str) -> Result<Map<String, Value>> {
+        self.get_order_book(pair, count)
+            .map_err(|e| anyhow!("kraken get_order_book: {}", e))
+    }
+
+    fn ticker(&mut self, pair: &str) -> Result<Map<String, Value>> {
+        self.get_ticker_information(pair)
+            .map_err(|e| anyhow!("kraken get_ticker_information: {}", e))
+    }
+}
+
+/// Source that returns canned responses e.g., recorded Kraken JSON in tests.
+#[derive(Debug, Default)]
+pub struct MockSource {
+    order_book: Option<String>,
+    ticker: Option<String>,
+}
+
+impl MockSource {
+    pub fn new() -> Self {
+        MockSource::default()
+    }
+
+    /// Respond to order book requests with `json`.
+    pub fn with_order_book(mut self, json: impl Into<String>) -> Self {
+        self.order_book = Some(json.into());
+        self
+    }
+
+    /// Respond to ticker requests with `json`.
+    pub fn with_ticker(mut self, json: impl Into<String>) -> Self {
+        self.ticker = Some(json.into());
+        self
+    }
+}
+
+impl Source for MockSource {
+    fn order_book(&mut self, _pair: &str, _count: &str) -> Result<Map<String, Value>> {
+        let json = self
+            .order_book
+            .as_ref()
+            .ok_or_else(|| anyhow!("no mock response for: order book"))?;
+        Ok(serde_json::from_str(json)?)
+    }
+
+    fn ticker(&mut self, _pair: &str) -> Result<Map<String, Value>> {
+        let json = self
+            .ticker
+            .as_ref()
+            .ok_or_else(|| anyhow!("no mock response for: ticker"))?;
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// The Kraken client blocks, it is shared with the blocking tasks that use it.
+#[derive(Clone, Debug)]
 pub struct Api {
-    api: KrakenApi,
+    api: Arc<Mutex<dyn Source>>,
 }
 
 impl Api {
@@ -26,72 +118,279 @@ impl Api {
             KrakenCreds::new_from_file("account_kraken", path).expect("failed to read creds");
         let api = KrakenApi::new(my_creds).expect("failed to create api");
 
-        Ok(Api { api })
+        Ok(Api::from(api))
     }
 
-    pub fn assert_public(&mut self) -> Result<(), Error> {
-        let tp = "XXBTZUSD";
-        let _ = self.api.get_order_book(tp, "1")?;
+    /// Create an API client using `key`.
+    pub fn with_key(key: &Key) -> Result<Api, Error> {
+        let creds = KrakenCreds::new("account_kraken", &key.api_key, &key.api_secret);
+        let api = KrakenApi::new(creds)?;
+
+        Ok(Api::from(api))
+    }
+
+    /// Get market data from `source` e.g., a `MockSource` in tests.
+    pub fn with_source(source: impl Source + 'static) -> Api {
+        Api {
+            api: Arc::new(Mutex::new(source)),
+        }
+    }
+
+    pub fn assert_public(&self) -> Result<()> {
+        let tp = pair_code("Xbt", "Usd").expect("Xbt/Usd has a kraken pair code");
+        let _ = self.lock().order_book(&tp, "1")?;
 
         Ok(())
     }
+
+    /// Get the order book for Kraken asset `pair` e.g., "XXBTZUSD". Blocks.
+    pub fn order_book(&self, pair: &str) -> Result<OrderBook> {
+        self.order_book_top(pair, ORDER_BOOK_DEPTH)
+    }
+
+    /// Get the best `depth` levels on each side of the order book for Kraken
+    /// asset `pair`, Kraken trims the book server side. Blocks.
+    pub fn order_book_top(&self, pair: &str, depth: usize) -> Result<OrderBook> {
+        let map = self.lock().order_book(pair, &depth.to_string())?;
+
+        parse_order_book(map, pair)
+    }
+
+    /// Get the best bid/offer and last price for Kraken asset `pair` e.g.,
+    /// "XXBTZUSD". Blocks.
+    pub fn market_summary(&self, pair: &str) -> Result<Summary> {
+        let map = self.lock().ticker(pair)?;
+
+        parse_ticker(map, pair)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, dyn Source + 'static> {
+        self.api.lock().expect("kraken lock poisoned")
+    }
 }
 
-// pub fn foo() {
-//     let tp = "XXBTZUSD";
+impl From<KrakenApi> for Api {
+    fn from(api: KrakenApi) -> Self {
+        Api::with_source(api)
+    }
+}
 
-//     let map = api
-//         .get_order_book(tp, "100000")
-//         .expect("failed to get order book");
+impl Exchange for Api {
+    fn order_book<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<OrderBook>> {
+        let api = self.clone();
+        async move {
+            let code = kraken_pair(pair)?;
+            tokio::task::spawn_blocking(move || api.order_book(&code)).await?
+        }
+        .boxed()
+    }
 
-//     let result = map.get("result").expect("no result");
+    fn market_summary<'a>(&'a self, pair: &'a Pair) -> BoxFuture<'a, Result<Summary>> {
+        let api = self.clone();
+        async move {
+            let code = kraken_pair(pair)?;
+            tokio::task::spawn_blocking(move || api.market_summary(&code)).await?
+        }
+        .boxed()
+    }
+}
+
+/// Kraken asset pair code for Independent Reserve currency codes `base` and
+/// `quote` e.g., ("Xbt", "Usd") -> "XXBTZUSD". `None` if we don't know the
+/// Kraken code for either currency.
+pub fn pair_code(base: &str, quote: &str) -> Option<String> {
+    let asset = |code: &str| {
+        ASSET_CODES
+            .iter()
+            .find(|(ir, _)| ir.eq_ignore_ascii_case(code))
+            .map(|(_, kraken)| *kraken)
+    };
+    Some(format!("{}{}", asset(base)?, asset(quote)?))
+}
+
+/// The pair for Kraken asset pair `code` e.g., "XXBTZUSD" -> Xbt/Usd. `None`
+/// if `code` is not made up of asset codes we know.
+pub fn from_pair_code(code: &str) -> Option<Pair> {
+    let asset = |code: &str| {
+        ASSET_CODES
+            .iter()
+            .find(|(_, kraken)| *kraken == code)
+            .map(|(ir, _)| *ir)
+    };
+    if code.len() != 8 || !code.is_ascii() {
+        return None;
+    }
+    let (base, quote) = code.split_at(4);
+    Some(Pair::new(asset(base)?, asset(quote)?))
+}
+
+// Kraken asset pair code for `pair`.
+fn kraken_pair(pair: &Pair) -> Result<String> {
+    pair_code(&pair.base, &pair.quote).ok_or_else(|| anyhow!("no kraken pair code for: {}", pair))
+}
+
+// Take the `result` entry for `pair` from a Kraken response.
+fn take_result(mut map: Map<String, Value>, pair: &str) -> Result<Value> {
+    if let Some(Value::Array(errors)) = map.get("error") {
+        if !errors.is_empty() {
+            return Err(anyhow!("kraken returned errors: {:?}", errors));
+        }
+    }
+    map.remove("result")
+        .and_then(|mut result| result.get_mut(pair).map(Value::take))
+        .ok_or_else(|| anyhow!("kraken response has no result for: {}", pair))
+}
+
+/// Order book for a single pair as returned by Kraken.
+#[derive(Debug, Deserialize)]
+struct KrakenOrderBook {
+    asks: Vec<Level>,
+    bids: Vec<Level>,
+}
+
+/// Price and volume are strings to preserve precision, the last element is a
+/// Unix timestamp which we don't use.
+#[derive(Debug, Deserialize)]
+struct Level(
+    #[serde(deserialize_with = "decimal_from_str_or_num")] Decimal,
+    #[serde(deserialize_with = "decimal_from_str_or_num")] Decimal,
+    IgnoredAny,
+);
+
+// Parse the response to a Kraken order book request for `pair`.
+fn parse_order_book(map: Map<String, Value>, pair: &str) -> Result<OrderBook> {
+    let book = take_result(map, pair)?;
+    let book: KrakenOrderBook = serde_json::from_value(book).context("kraken order book")?;
+
+    let levels = |levels: Vec<Level>| -> Vec<(Decimal, Decimal)> {
+        levels.into_iter().map(|l| (l.0, l.1)).collect()
+    };
+    Ok(OrderBook::from_levels(levels(book.bids), levels(book.asks)))
+}
+
+/// Ticker for a single pair as returned by Kraken, each field is an array
+/// starting with the price.
+#[derive(Debug, Deserialize)]
+struct KrakenTicker {
+    #[serde(rename = "a")]
+    ask: Vec<Number>,
+    #[serde(rename = "b")]
+    bid: Vec<Number>,
+    #[serde(rename = "c")]
+    last: Vec<Number>,
+}
+
+// Parse the response to a Kraken ticker request for `pair`.
+fn parse_ticker(map: Map<String, Value>, pair: &str) -> Result<Summary> {
+    let ticker = take_result(map, pair)?;
+    let ticker: KrakenTicker = serde_json::from_value(ticker).context("kraken ticker")?;
+
+    let price = |v: &[Number]| v.first().and_then(Number::get);
+    Ok(Summary {
+        best_bid: price(&ticker.bid),
+        best_ask: price(&ticker.ask),
+        last: price(&ticker.last),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::Position;
+    use spectral::prelude::*;
+    use std::str::FromStr;
+
+    fn recorded_api() -> Api {
+        Api::with_source(
+            MockSource::new()
+                .with_order_book(include_str!("../../testdata/kraken-orderbook.json"))
+                .with_ticker(include_str!("../../testdata/kraken-ticker.json")),
+        )
+    }
+
+    fn recorded() -> Map<String, Value> {
+        serde_json::from_str(include_str!("../../testdata/kraken-orderbook.json")).unwrap()
+    }
+
+    #[test]
+    fn parses_recorded_order_book() {
+        let book = parse_order_book(recorded(), "XXBTZUSD").unwrap();
+
+        assert_that(&book.buys).has_length(3);
+        assert_that(&book.sells).has_length(3);
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from_str("9455.00000").unwrap()));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from_str("9456.90000").unwrap()));
+        // Volumes are parsed exactly from their string encoding.
+        let price = Decimal::from_str("9457.20000").unwrap();
+        assert_that(&book.cumulative_volume_to_price(Position::Buy, price))
+            .is_equal_to(Decimal::new(1512, 3));
+    }
+
+    #[test]
+    fn parses_recorded_ticker() {
+        let map = serde_json::from_str(include_str!("../../testdata/kraken-ticker.json")).unwrap();
+
+        let summary = parse_ticker(map, "XXBTZUSD").unwrap();
 
-//     let xbt = result.get(tp).expect("no XBT");
-//     let asks = xbt.get("asks").expect("no asks");
-//     let bids = xbt.get("bids").expect("no bids");
+        assert_that(&summary).is_equal_to(Summary {
+            best_bid: Some(Decimal::from_str("9455.00000").unwrap()),
+            best_ask: Some(Decimal::from_str("9456.90000").unwrap()),
+            last: Some(Decimal::from_str("9456.10000").unwrap()),
+        });
+    }
 
-//     println!("ask[0]: {:?}", asks[0]);
-//     println!("bid[0]: {:?}", bids[0]);
+    #[test]
+    fn pair_code_round_trips() {
+        let code = pair_code("Xbt", "Usd").unwrap();
+        assert_that(&code).is_equal_to("XXBTZUSD".to_string());
+        assert_that(&from_pair_code(&code)).is_equal_to(Some(Pair::new("Xbt", "Usd")));
 
-//     let mut a = vec![];
-//     if let Value::Array(v) = asks {
-//         for ask in v.iter() {
-//             if let Value::Array(v) = ask {
-//                 let mut price = serde_json::to_string(&v[0])?;
-//                 price.pop();
-//                 price = price[1..].to_string();
+        let code = pair_code("eth", "EUR").unwrap();
+        assert_that(&code).is_equal_to("XETHZEUR".to_string());
+        assert_that(&from_pair_code(&code)).is_equal_to(Some(Pair::new("Eth", "Eur")));
+    }
 
-//                 let mut volume = serde_json::to_string(&v[1])?;
-//                 volume.pop();
-//                 volume = volume[1..].to_string();
+    #[test]
+    fn unknown_codes_have_no_mapping() {
+        assert_that(&pair_code("Doge", "Usd")).is_none();
+        assert_that(&from_pair_code("DOGEUSD")).is_none();
+        assert_that(&from_pair_code("XXBTZNZD")).is_none();
+    }
 
-//                 println!("{:?}", price);
-//                 a.push(Ask {
-//                     price: Decimal::from_str(&price).expect("price fail"),
-//                     volume: Decimal::from_str(&volume).unwrap(),
-//                     timestamp: v[2].as_u64().unwrap(),
-//                 });
-//             }
-//         }
-//     }
+    #[test]
+    fn missing_pair_is_an_error() {
+        assert_that(&parse_order_book(recorded(), "XETHZUSD").is_err()).is_true();
+    }
 
-//     println!("{:?}", a);
+    #[test]
+    fn kraken_errors_are_reported() {
+        let map = serde_json::from_str(r#"{"error": ["EQuery:Unknown asset pair"]}"#).unwrap();
 
-//     Ok(())
-// }
+        let err = parse_order_book(map, "XXBTZUSD").unwrap_err();
 
-// #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-// struct Ask {
-//     price: Decimal,
-//     volume: Decimal,
-//     timestamp: u64,
-// }
+        assert_that(&err.to_string().contains("Unknown asset pair")).is_true();
+    }
 
-// // fn split_ask(v: &Value) -> Result<(String, String)> {
-// //     //    let a: Vec<Value> = serde_json::to_string(v)?;
+    #[tokio::test]
+    async fn exchange_maps_pair_and_parses_recorded_data() {
+        let api = recorded_api();
+        let exchange: &dyn Exchange = &api;
+        let pair = Pair::new("Xbt", "Usd");
 
-// //     let price = v.get(0).expect("no price").to_string();
-// //     let volume = v.get(1).expect("no volume").to_string();
+        let book = exchange.order_book(&pair).await.unwrap();
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from_str("9455.00000").unwrap()));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from_str("9456.90000").unwrap()));
 
-// //     Ok((price, volume))
-// // }
+        let summary = exchange.market_summary(&pair).await.unwrap();
+        assert_that(&summary.last).is_equal_to(Some(Decimal::from_str("9456.10000").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn exchange_rejects_pair_without_kraken_code() {
+        let api = recorded_api();
+
+        let res = Exchange::order_book(&api, &Pair::new("Doge", "Usd")).await;
+
+        assert_that(&res.is_err()).is_true();
+    }
+}
@@ -1,34 +1,191 @@
-//! Custom Decimal wrapper type.
+//! Custom Decimal wrapper type and serde helpers.
 //!
 //! We use this to catch serder errors when ser/deser numbers from an API call.
+//! The API sometimes returns null, or a quoted string, where a number is
+//! expected.
+//!
+//! For plain `Decimal` fields use `#[serde(deserialize_with =
+//! "decimal_from_str_or_num")]`, or `#[serde(default, deserialize_with =
+//! "option_decimal_from_str_or_num")]` for nullable fields.
 
 use rust_decimal::Decimal;
 use serde::{
-    de::Error as _, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, Error as _, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
 };
+use std::{fmt, str::FromStr};
 
+/// A number that may be null. Serializes as a decimal string or null,
+/// deserializes from a number, a numeric string, or null.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Number {
     inner: Option<Decimal>,
 }
 
+impl Number {
+    /// The number, `None` if the API returned null.
+    pub fn get(&self) -> Option<Decimal> {
+        self.inner
+    }
+}
+
+impl From<Decimal> for Number {
+    fn from(d: Decimal) -> Self {
+        Number { inner: Some(d) }
+    }
+}
+
+impl From<Option<Decimal>> for Number {
+    fn from(inner: Option<Decimal>) -> Self {
+        Number { inner }
+    }
+}
+
 impl Serialize for Number {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.0.to_string())
+        match self.inner {
+            Some(d) => serializer.serialize_str(&d.to_string()),
+            None => serializer.serialize_none(),
+        }
     }
 }
 
 impl<'de> Deserialize<'de> for Number {
-    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+/// Deserialize a `Decimal` from a number or a numeric string, null is an
+/// error.
+pub fn decimal_from_str_or_num<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Number::deserialize(deserializer)?
+        .get()
+        .ok_or_else(|| D::Error::custom("expected a number, found null"))
+}
+
+/// Deserialize an optional `Decimal` from a number, a numeric string, or null.
+pub fn option_decimal_from_str_or_num<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Number::deserialize(deserializer)?.get())
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a number, a numeric string, or null")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Number::default())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Number::default())
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let role = String::deserialize(deserializer)?;
-        let role =
-            Role::from_str(role.as_str()).map_err(<D as Deserializer<'de>>::Error::custom)?;
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Decimal::from(v).into())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Decimal::from(v).into())
+    }
+
+    // Go via the shortest string representation so 0.1 is 0.1 and not the
+    // closest binary float.
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Decimal::from_str(v.trim())
+            .map(Number::from)
+            .map_err(|e| E::custom(format!("invalid number {:?}: {}", v, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn deserializes_number() {
+        let got: Number = serde_json::from_str("12345.67").unwrap();
+        assert_that(&got.get()).is_equal_to(Some(Decimal::new(1_234_567, 2)));
+
+        let got: Number = serde_json::from_str("42").unwrap();
+        assert_that(&got.get()).is_equal_to(Some(Decimal::from(42)));
+    }
+
+    #[test]
+    fn deserializes_null() {
+        let got: Number = serde_json::from_str("null").unwrap();
+        assert_that(&got.get()).is_none();
+        assert_that(&serde_json::to_string(&got).unwrap()).is_equal_to("null".to_string());
+    }
+
+    #[test]
+    fn deserializes_quoted_number() {
+        let got: Number = serde_json::from_str("\"0.00000001\"").unwrap();
+        assert_that(&got.get()).is_equal_to(Some(Decimal::new(1, 8)));
+        assert_that(&serde_json::to_string(&got).unwrap())
+            .is_equal_to("\"0.00000001\"".to_string());
+
+        let bad: Result<Number, _> = serde_json::from_str("\"lots\"");
+        assert_that(&bad.is_err()).is_true();
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Level {
+        #[serde(deserialize_with = "decimal_from_str_or_num")]
+        price: Decimal,
+        #[serde(default, deserialize_with = "option_decimal_from_str_or_num")]
+        volume: Option<Decimal>,
+    }
+
+    fn level(json: &str) -> Level {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn decimal_field_from_string_or_number() {
+        let want = Decimal::new(12345, 1);
+
+        assert_that(&level(r#"{"price": "1234.5"}"#).price).is_equal_to(want);
+        assert_that(&level(r#"{"price": 1234.5}"#).price).is_equal_to(want);
+        assert_that(&level(r#"{"price": 1, "volume": "1234.5"}"#).volume).is_equal_to(Some(want));
+        assert_that(&level(r#"{"price": 1, "volume": 1234.5}"#).volume).is_equal_to(Some(want));
+    }
+
+    #[test]
+    fn nullable_decimal_field() {
+        assert_that(&level(r#"{"price": 1, "volume": null}"#).volume).is_none();
+        assert_that(&level(r#"{"price": 1}"#).volume).is_none();
 
-        Ok(Http(role))
+        let bad: Result<Level, _> = serde_json::from_str(r#"{"price": null}"#);
+        assert_that(&bad.is_err()).is_true();
     }
 }
@@ -1,10 +1,16 @@
-use crate::market::api;
-use anyhow::{bail, Result};
-use num_traits::identities::Zero;
+use crate::{
+    market::api,
+    num::{self, Notional, Price, Volume},
+};
+use anyhow::Result;
+use num_traits::identities::{One, Zero};
 use rust_decimal::Decimal;
 use std::{convert::TryFrom, fmt};
 use tracing::warn;
 
+/// Number of price levels either side of the book shown by `Display`.
+pub const DISPLAY_DEPTH: usize = 5;
+
 #[derive(Clone, Debug)]
 pub struct OrderBook {
     /// Sorted list of bids, highest bid first (descending order).
@@ -14,8 +20,64 @@ pub struct OrderBook {
 }
 
 impl OrderBook {
+    /// The highest bid, `None` if there are no bids.
+    pub fn best_bid(&self) -> Option<Decimal> {
+        self.buys.first().map(|o| o.price)
+    }
+
+    /// The lowest offer, `None` if there are no offers.
+    pub fn best_ask(&self) -> Option<Decimal> {
+        self.sells.first().map(|o| o.price)
+    }
+
+    /// Mid market price, `None` if either side of the book is empty.
+    pub fn mid_price(&self) -> Option<Decimal> {
+        Some(num::mid_market_price(&self.best_bid()?, &self.best_ask()?))
+    }
+
+    /// Mid price weighted by the volume at the top of each side of the book
+    /// i.e., `(bid * ask_vol + ask * bid_vol) / (bid_vol + ask_vol)`. Leans
+    /// towards the thin side, making it a better fair value estimate than
+    /// `mid_price`. `None` if either side of the book is empty.
+    pub fn microprice(&self) -> Option<Decimal> {
+        let bid = self.best_bid()?;
+        let ask = self.best_ask()?;
+        let bid_vol = top_volume(&self.buys);
+        let ask_vol = top_volume(&self.sells);
+
+        let total = bid_vol + ask_vol;
+        if total.is_zero() {
+            return None;
+        }
+
+        Some((bid * ask_vol + ask * bid_vol) / total)
+    }
+
+    /// Difference between the best offer and the best bid, `None` if either
+    /// side of the book is empty.
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// True if the best bid is at or above the best offer i.e., the book is
+    /// crossed or locked. A live book never is, so it is stale or malformed.
+    pub fn is_crossed(&self) -> bool {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        }
+    }
+
     /// Get the spread if we were to fill a buy and sell order of `volume`.
+    /// Errors with a `FillError` if either side can't fill `volume` and with
+    /// a `CrossedBook` if the book is crossed, rather than returning a
+    /// nonsensical spread.
     pub fn spread_to_fill(&self, volume: Decimal) -> Result<(Decimal, Decimal)> {
+        if let (Some(bid), Some(ask)) = (self.best_bid(), self.best_ask()) {
+            if bid >= ask {
+                return Err(CrossedBook { bid, ask }.into());
+            }
+        }
         let buy_price = self.price_to_fill_buy_order(volume)?;
         let sell_price = self.price_to_fill_sell_order(volume)?;
         Ok((sell_price, buy_price))
@@ -31,25 +93,157 @@ impl OrderBook {
         self.price_to_fill(volume, Position::Sell)
     }
 
-    fn price_to_fill(&self, volume: Decimal, pos: Position) -> Result<Decimal> {
-        // Market order matches against the bid/ask e.g., a market buy order
-        // matches against an offer (sell).
-        let v = match pos {
-            Position::Buy => &self.sells,
-            Position::Sell => &self.buys,
+    /// The price if we were to fill a market order at `pos` for `volume`,
+    /// including a brokerage `fee` (as a fraction e.g., 0.005 for 0.5%). The
+    /// fee makes the price worse i.e., a buy costs more and a sell returns
+    /// less.
+    pub fn price_to_fill_with_fee(
+        &self,
+        volume: Decimal,
+        pos: Position,
+        fee: Decimal,
+    ) -> Result<Decimal> {
+        let price = self.price_to_fill(volume, pos)?;
+        let price = match pos {
+            Position::Buy => price * (Decimal::one() + fee),
+            Position::Sell => price * (Decimal::one() - fee),
         };
+        Ok(price)
+    }
+
+    /// Fill as much of a market order at `pos` for `volume` as the book
+    /// allows. Returns the filled volume and its average price, `None` if
+    /// nothing could be filled.
+    pub fn price_to_fill_partial(
+        &self,
+        volume: Decimal,
+        pos: Position,
+    ) -> Option<(Decimal, Decimal)> {
+        let (filled, spend) = self.sweep(volume, pos);
+        if filled.is_zero() {
+            return None;
+        }
 
+        let Price(price) = spend / Volume(filled);
+        Some((filled, price))
+    }
+
+    /// Order book imbalance over the top `depth` levels of each side i.e.,
+    /// `(bid_vol - ask_vol) / (bid_vol + ask_vol)`, a value in [-1, 1].
+    /// Returns `None` if there is no volume in the book.
+    pub fn imbalance(&self, depth: usize) -> Option<Decimal> {
+        let bid_vol: Decimal = self.buys.iter().take(depth).map(|o| o.volume).sum();
+        let ask_vol: Decimal = self.sells.iter().take(depth).map(|o| o.volume).sum();
+
+        let total = bid_vol + ask_vol;
+        if total.is_zero() {
+            return None;
+        }
+
+        Some((bid_vol - ask_vol) / total)
+    }
+
+    /// Slippage of a market order at `pos` for `volume` i.e., the difference
+    /// between the best price and the volume weighted fill price, as a
+    /// fraction of the best price. Errors if the book can't fill `volume`.
+    pub fn slippage(&self, volume: Decimal, pos: Position) -> Result<Decimal> {
+        let fill = self.price_to_fill(volume, pos)?;
+        let best = self.orders_to_match(pos)[0].price; // Filled so non-empty.
+
+        Ok((fill - best).abs() / best)
+    }
+
+    /// Volume available to a market order at `pos` within `percent` (as a
+    /// fraction e.g., 0.01 for 1%) of the best price.
+    pub fn volume_within_percent(&self, pos: Position, percent: Decimal) -> Decimal {
+        let best = match self.orders_to_match(pos).first() {
+            Some(order) => order.price,
+            None => return Decimal::zero(),
+        };
+        let price = match pos {
+            Position::Buy => best * (Decimal::one() + percent),
+            Position::Sell => best * (Decimal::one() - percent),
+        };
+        self.cumulative_volume_to_price(pos, price)
+    }
+
+    /// Volume available to a market order at `pos` at `price` or better i.e.,
+    /// offers at or below `price` for a buy, bids at or above for a sell.
+    pub fn cumulative_volume_to_price(&self, pos: Position, price: Decimal) -> Decimal {
+        self.orders_to_match(pos)
+            .iter()
+            .take_while(|order| match pos {
+                Position::Buy => order.price <= price,
+                Position::Sell => order.price >= price,
+            })
+            .map(|order| order.volume)
+            .sum()
+    }
+
+    /// Display view with consecutive same-price orders merged into a single
+    /// level.
+    pub fn display_aggregated(&self) -> OrderBookDisplay<'_> {
+        OrderBookDisplay {
+            book: self,
+            aggregate: true,
+            depth: None,
+        }
+    }
+
+    /// Display view with one row per order, as used for computation.
+    pub fn display_raw(&self) -> OrderBookDisplay<'_> {
+        OrderBookDisplay {
+            book: self,
+            aggregate: false,
+            depth: None,
+        }
+    }
+
+    /// Display view of the top `depth` price levels either side of the book
+    /// with the cumulative volume from the best price, this is what `Display`
+    /// uses with `DISPLAY_DEPTH`.
+    pub fn display_depth(&self, depth: usize) -> OrderBookDisplay<'_> {
+        OrderBookDisplay {
+            book: self,
+            aggregate: true,
+            depth: Some(depth),
+        }
+    }
+
+    /// Display view of the whole book, same-price orders aggregated.
+    pub fn fmt_full(&self) -> OrderBookDisplay<'_> {
+        self.display_aggregated()
+    }
+
+    fn price_to_fill(&self, volume: Decimal, pos: Position) -> Result<Decimal> {
+        let (filled, spend) = self.sweep(volume, pos);
+        if filled < volume {
+            return Err(FillError {
+                position: pos,
+                requested: volume,
+                available: filled,
+            }
+            .into());
+        }
+
+        let Price(price) = spend / Volume(volume);
+        Ok(price)
+    }
+
+    // Match a market order at `pos` for up to `volume` against the book, best
+    // price first. Returns the volume filled and the total spend.
+    fn sweep(&self, volume: Decimal, pos: Position) -> (Decimal, Notional) {
         let mut still_to_fill = volume;
-        let mut total_spend = Decimal::zero();
+        let mut total_spend = Notional::default();
 
-        for order in v.iter() {
+        for order in self.orders_to_match(pos).iter() {
             if still_to_fill > order.volume {
                 still_to_fill -= order.volume;
-                total_spend += order.volume * order.price;
+                total_spend += Price(order.price) * Volume(order.volume);
             } else {
                 let partial = still_to_fill;
                 still_to_fill = Decimal::zero();
-                total_spend += partial * order.price;
+                total_spend += Price(order.price) * Volume(partial);
             }
 
             if still_to_fill.is_zero() {
@@ -57,45 +251,240 @@ impl OrderBook {
             }
         }
 
-        if still_to_fill > Decimal::zero() {
-            bail!("failed to fill {} order", pos);
+        (volume - still_to_fill, total_spend)
+    }
+
+    // The orders a market order at `pos` matches against, best price first.
+    fn orders_to_match(&self, pos: Position) -> &[Order] {
+        // Market order matches against the bid/ask e.g., a market buy order
+        // matches against an offer (sell).
+        match pos {
+            Position::Buy => &self.sells,
+            Position::Sell => &self.buys,
         }
+    }
+}
 
-        let price = total_spend / volume;
-        Ok(price)
+impl fmt::Display for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display_depth(DISPLAY_DEPTH))
     }
 }
 
-impl From<api::OrderBook> for OrderBook {
-    fn from(orderbook: api::OrderBook) -> Self {
-        let mut buys = Vec::with_capacity(orderbook.buy_orders.len());
-        for order in orderbook.buy_orders.iter() {
-            if let Ok(o) = Order::try_from(order) {
-                if o.position == Position::Buy {
-                    buys.push(o);
-                } else {
-                    warn!("non-buy order in buys list");
+/// Displays an order book, offers (highest first) above bids (highest first).
+#[derive(Clone, Copy, Debug)]
+pub struct OrderBookDisplay<'a> {
+    book: &'a OrderBook,
+    aggregate: bool,
+    /// Only show this many levels each side, with cumulative volume.
+    depth: Option<usize>,
+}
+
+impl OrderBookDisplay<'_> {
+    // (price, volume) rows for `orders`.
+    fn levels(&self, orders: &[Order]) -> Vec<(Decimal, Decimal)> {
+        let mut levels: Vec<(Decimal, Decimal)> = Vec::with_capacity(orders.len());
+        for order in orders.iter() {
+            match levels.last_mut() {
+                Some((price, volume)) if self.aggregate && *price == order.price => {
+                    *volume += order.volume
                 }
+                _ => levels.push((order.price, order.volume)),
             }
         }
-        buys.sort_unstable_by(|a: &Order, b: &Order| a.price.cmp(&b.price).reverse());
+        levels
+    }
 
-        let mut sells = Vec::with_capacity(orderbook.sell_orders.len());
-        for order in orderbook.sell_orders.iter() {
-            if let Ok(o) = Order::try_from(order) {
-                if o.position == Position::Sell {
-                    sells.push(o);
-                } else {
-                    warn!("non-sell order in sells list");
-                }
-            }
+    // The top `depth` levels of `orders` as (price, volume, cumulative volume).
+    fn ladder(&self, orders: &[Order], depth: usize) -> Vec<(Decimal, Decimal, Decimal)> {
+        let mut total = Decimal::zero();
+        self.levels(orders)
+            .into_iter()
+            .take(depth)
+            .map(|(price, volume)| {
+                total += volume;
+                (price, volume, total)
+            })
+            .collect()
+    }
+
+    fn fmt_depth(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{:>12} {:>12} {:>12}", "price", "volume", "cumulative")?;
+        for (price, volume, total) in self.ladder(&self.book.sells, depth).iter().rev() {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} ask",
+                Price(*price),
+                Volume(*volume),
+                Volume(*total)
+            )?;
+        }
+        for (price, volume, total) in self.ladder(&self.book.buys, depth).iter() {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} bid",
+                Price(*price),
+                Volume(*volume),
+                Volume(*total)
+            )?;
         }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OrderBookDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(depth) = self.depth {
+            return self.fmt_depth(f, depth);
+        }
+
+        writeln!(f, "{:>12} {:>12}", "price", "volume")?;
+        for (price, volume) in self.levels(&self.book.sells).iter().rev() {
+            writeln!(f, "{:>12} {:>12} ask", Price(*price), Volume(*volume))?;
+        }
+        for (price, volume) in self.levels(&self.book.buys).iter() {
+            writeln!(f, "{:>12} {:>12} bid", Price(*price), Volume(*volume))?;
+        }
+        Ok(())
+    }
+}
+
+impl OrderBook {
+    /// Convert the order book returned by the API, orders with a null price or
+    /// volume and orders on the wrong side of the book are dropped. Returns
+    /// the book along with stats so callers know if it is incomplete.
+    pub(crate) fn from_api(orderbook: api::OrderBook) -> (Self, ConversionStats) {
+        let mut stats = ConversionStats::default();
+
+        let buys = convert_orders(&orderbook.buy_orders, Position::Buy, &mut stats);
+        let sells = convert_orders(&orderbook.sell_orders, Position::Sell, &mut stats);
+
+        (OrderBook::sorted(buys, sells), stats)
+    }
+
+    /// Convert an API order book keeping only the best `n` orders on each side
+    /// e.g., when only the top of the book is needed for spread calculations.
+    pub fn from_top_n(orderbook: api::OrderBook, n: usize) -> Self {
+        let mut book = OrderBook::from(orderbook);
+        // Sorted by conversion, so the best orders are first.
+        book.buys.truncate(n);
+        book.sells.truncate(n);
+        book
+    }
+
+    /// Build an order book from unsorted (price, volume) bids and offers.
+    pub(crate) fn from_levels(
+        bids: Vec<(Decimal, Decimal)>,
+        asks: Vec<(Decimal, Decimal)>,
+    ) -> Self {
+        let orders = |levels: Vec<(Decimal, Decimal)>, position| {
+            levels
+                .into_iter()
+                .map(|(price, volume)| Order {
+                    position,
+                    price,
+                    volume,
+                })
+                .collect()
+        };
+        OrderBook::sorted(orders(bids, Position::Buy), orders(asks, Position::Sell))
+    }
+
+    fn sorted(mut buys: Vec<Order>, mut sells: Vec<Order>) -> Self {
+        buys.sort_unstable_by(|a: &Order, b: &Order| a.price.cmp(&b.price).reverse());
         sells.sort_unstable_by(|a: &Order, b: &Order| a.price.cmp(&b.price));
 
         OrderBook { buys, sells }
     }
 }
 
+/// Build a book from the individual orders returned by GetAllOrders, unlike
+/// GetOrderBook same price orders are not aggregated by the exchange. Orders
+/// with a null price or volume are dropped.
+impl From<api::AllOrders> for OrderBook {
+    fn from(orders: api::AllOrders) -> Self {
+        let convert = |orders: &[api::OrderGuid], position| -> Vec<Order> {
+            orders
+                .iter()
+                .filter_map(|o| match (o.price, o.volume) {
+                    (Some(price), Some(volume)) => Some(Order {
+                        position,
+                        price,
+                        volume,
+                    }),
+                    _ => {
+                        warn!("dropping {} order {}: {}", position, o.guid, NullValue);
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        OrderBook::sorted(
+            convert(&orders.buy_orders, Position::Buy),
+            convert(&orders.sell_orders, Position::Sell),
+        )
+    }
+}
+
+// Total volume of the orders at the best price, `orders` must be sorted.
+fn top_volume(orders: &[Order]) -> Decimal {
+    match orders.first() {
+        Some(best) => orders
+            .iter()
+            .take_while(|o| o.price == best.price)
+            .map(|o| o.volume)
+            .sum(),
+        None => Decimal::zero(),
+    }
+}
+
+impl From<api::OrderBook> for OrderBook {
+    fn from(orderbook: api::OrderBook) -> Self {
+        OrderBook::from_api(orderbook).0
+    }
+}
+
+/// Stats from converting an API order book.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConversionStats {
+    /// Number of orders dropped from the book.
+    pub dropped: usize,
+}
+
+impl ConversionStats {
+    /// True if any orders were dropped.
+    pub fn is_incomplete(&self) -> bool {
+        self.dropped > 0
+    }
+}
+
+// Convert the API `orders` on side `pos` of the book, dropping invalid orders.
+fn convert_orders(
+    orders: &[api::PublicOrder],
+    pos: Position,
+    stats: &mut ConversionStats,
+) -> Vec<Order> {
+    let mut v = Vec::with_capacity(orders.len());
+    for (index, order) in orders.iter().enumerate() {
+        match Order::try_from(order) {
+            Ok(o) if o.position == pos => v.push(o),
+            Ok(_) => {
+                warn!(
+                    "dropping non-{} order at index {} in {}s list",
+                    pos, index, pos
+                );
+                stats.dropped += 1;
+            }
+            Err(e) => {
+                warn!("dropping {} order at index {}: {}", pos, index, e);
+                stats.dropped += 1;
+            }
+        }
+    }
+    v
+}
+
 /// Limit order.
 #[derive(Clone, Copy, Debug)]
 pub struct Order {
@@ -123,8 +512,31 @@ impl TryFrom<&api::PublicOrder> for Order {
 #[error("API returned a null value")]
 pub struct NullValue;
 
+/// Error returned when the book does not have enough volume to fill a market
+/// order. Returned wrapped in an `anyhow::Error`, callers can
+/// `downcast_ref::<FillError>()` e.g., to retry with a smaller order.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("failed to fill {position} order of {requested}, only {available} available")]
+pub struct FillError {
+    /// Side of the market order.
+    pub position: Position,
+    /// Volume of the order.
+    pub requested: Decimal,
+    /// Total volume on the matching side of the book.
+    pub available: Decimal,
+}
+
+/// Error returned when the best bid is at or above the best offer.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("order book is crossed, best bid {bid} >= best ask {ask}")]
+pub struct CrossedBook {
+    pub bid: Decimal,
+    pub ask: Decimal,
+}
+
+/// Side of a market order.
 #[derive(Clone, Copy, Debug, PartialEq)]
-enum Position {
+pub enum Position {
     Buy,
     Sell,
 }
@@ -147,3 +559,466 @@ impl From<api::OrderType> for Position {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::str::FromStr;
+
+    fn order(position: Position, price: u32, volume: u32) -> Order {
+        Order {
+            position,
+            price: Decimal::from(price),
+            volume: Decimal::from(volume),
+        }
+    }
+
+    fn book() -> OrderBook {
+        OrderBook {
+            buys: vec![order(Position::Buy, 99, 1)],
+            sells: vec![
+                order(Position::Sell, 100, 1),
+                order(Position::Sell, 100, 1),
+                order(Position::Sell, 101, 1),
+            ],
+        }
+    }
+
+    fn ask_rows(s: &str) -> Vec<&str> {
+        s.lines().filter(|l| l.ends_with("ask")).collect()
+    }
+
+    #[test]
+    fn same_price_orders_display_as_one_level() {
+        let book = book();
+
+        let aggregated = book.display_aggregated().to_string();
+        let rows = ask_rows(&aggregated);
+        assert_that(&rows).has_length(2);
+        assert_that(&rows[1].split_whitespace().collect::<Vec<_>>())
+            .is_equal_to(vec!["100", "2", "ask"]);
+        assert_that(&book.fmt_full().to_string()).is_equal_to(aggregated);
+
+        let raw = book.display_raw().to_string();
+        assert_that(&ask_rows(&raw)).has_length(3);
+    }
+
+    #[test]
+    fn display_shows_depth_ladder() {
+        let mut book = book();
+        book.buys.push(order(Position::Buy, 98, 3));
+
+        let got = book.display_depth(1).to_string();
+        assert_that(&got.lines().collect::<Vec<_>>()).is_equal_to(vec![
+            "       price       volume   cumulative",
+            "         100            2            2 ask",
+            "          99            1            1 bid",
+        ]);
+
+        let got = book.to_string();
+        assert_that(&got.lines().collect::<Vec<_>>()).is_equal_to(vec![
+            "       price       volume   cumulative",
+            "         101            1            3 ask",
+            "         100            2            2 ask",
+            "          99            1            1 bid",
+            "          98            3            4 bid",
+        ]);
+    }
+
+    #[test]
+    fn microprice_leans_towards_thin_side() {
+        let book = OrderBook::from_levels(vec![(Decimal::from(99), Decimal::from(3))], vec![(
+            Decimal::from(101),
+            Decimal::from(1),
+        )]);
+
+        // (99 * 1 + 101 * 3) / 4
+        assert_that(&book.microprice()).is_equal_to(Some(Decimal::new(1005, 1)));
+        assert_that(&book.mid_price()).is_equal_to(Some(Decimal::from(100)));
+
+        // Orders at the best price are summed, (99 * 2 + 100 * 1) / 3
+        let got = self::book().microprice().unwrap();
+        assert_that(&got).is_equal_to(Decimal::from(298) / Decimal::from(3));
+
+        let empty = OrderBook::from_levels(vec![], vec![]);
+        assert_that(&empty.microprice()).is_none();
+    }
+
+    #[test]
+    fn book_from_all_orders_keeps_each_order() {
+        let orders: api::AllOrders = serde_json::from_str(
+            r#"{
+                "BuyOrders": [
+                    {"Guid": "b1", "Price": 99, "Volume": 1},
+                    {"Guid": "b2", "Price": 100, "Volume": 2},
+                    {"Guid": "b3", "Price": 100, "Volume": 1},
+                    {"Guid": "b4", "Price": 98, "Volume": null}
+                ],
+                "SellOrders": [
+                    {"Guid": "s1", "Price": 102, "Volume": 1},
+                    {"Guid": "s2", "Price": 101, "Volume": 3}
+                ],
+                "CreatedTimestampUtc": "2020-01-01T00:00:00Z",
+                "PrimaryCurrencyCode": "Xbt",
+                "SecondaryCurrencyCode": "Aud"
+            }"#,
+        )
+        .unwrap();
+
+        let book = OrderBook::from(orders);
+
+        let prices = |orders: &[Order]| orders.iter().map(|o| o.price).collect::<Vec<_>>();
+        assert_that(&prices(&book.buys)).is_equal_to(vec![
+            Decimal::from(100),
+            Decimal::from(100),
+            Decimal::from(99),
+        ]);
+        assert_that(&prices(&book.sells)).is_equal_to(vec![Decimal::from(101), Decimal::from(102)]);
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from(100)));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from(101)));
+    }
+
+    #[test]
+    fn normal_book_is_not_crossed() {
+        let book = book();
+
+        assert_that(&book.is_crossed()).is_false();
+        assert_that(&book.spread_to_fill(Decimal::from(1))).is_ok();
+        assert_that(&OrderBook::from_levels(vec![], vec![]).is_crossed()).is_false();
+    }
+
+    #[test]
+    fn crossed_book_errors_instead_of_negative_spread() {
+        let one = Decimal::from(1);
+        let book = OrderBook::from_levels(vec![(Decimal::from(101), one)], vec![(
+            Decimal::from(100),
+            one,
+        )]);
+        assert_that(&book.is_crossed()).is_true();
+
+        let err = book.spread_to_fill(one).unwrap_err();
+        assert_that(&err.downcast_ref::<CrossedBook>()).is_equal_to(Some(&CrossedBook {
+            bid: Decimal::from(101),
+            ask: Decimal::from(100),
+        }));
+
+        let locked = OrderBook::from_levels(vec![(Decimal::from(100), one)], vec![(
+            Decimal::from(100),
+            one,
+        )]);
+        assert_that(&locked.is_crossed()).is_true();
+    }
+
+    // Book with a level at each of `bid_vols` from 99 down and each of
+    // `ask_vols` from 101 up.
+    fn sized_book(bid_vols: &[u32], ask_vols: &[u32]) -> OrderBook {
+        let levels = |vols: &[u32], price: fn(u32) -> u32| -> Vec<(Decimal, Decimal)> {
+            (0..)
+                .zip(vols)
+                .map(|(i, v)| (Decimal::from(price(i)), Decimal::from(*v)))
+                .collect()
+        };
+        OrderBook::from_levels(levels(bid_vols, |i| 99 - i), levels(ask_vols, |i| 101 + i))
+    }
+
+    #[test]
+    fn balanced_book_has_no_imbalance() {
+        let book = sized_book(&[2, 1], &[2, 1]);
+
+        assert_that(&book.imbalance(2)).is_equal_to(Some(Decimal::zero()));
+    }
+
+    #[test]
+    fn bid_heavy_book_has_positive_imbalance() {
+        let book = sized_book(&[3, 5], &[1, 100]);
+
+        // (3 - 1) / (3 + 1)
+        assert_that(&book.imbalance(1)).is_equal_to(Some(Decimal::new(5, 1)));
+        // Only the top `depth` levels count, (8 - 101) / (8 + 101)
+        assert_that(&book.imbalance(2)).is_equal_to(Some(Decimal::from(-93) / Decimal::from(109)));
+    }
+
+    #[test]
+    fn ask_heavy_book_has_negative_imbalance() {
+        let book = sized_book(&[1], &[3]);
+        assert_that(&book.imbalance(5)).is_equal_to(Some(Decimal::new(-5, 1)));
+
+        let book = sized_book(&[], &[3]);
+        assert_that(&book.imbalance(5)).is_equal_to(Some(-Decimal::one()));
+    }
+
+    #[test]
+    fn empty_book_has_no_imbalance() {
+        assert_that(&sized_book(&[], &[]).imbalance(5)).is_none();
+        assert_that(&sized_book(&[1], &[1]).imbalance(0)).is_none();
+    }
+
+    #[test]
+    fn same_price_orders_still_fill_independently() {
+        let book = book();
+
+        let one = book.price_to_fill_buy_order(Decimal::from(1)).unwrap();
+        let two = book.price_to_fill_buy_order(Decimal::from(2)).unwrap();
+        let three = book.price_to_fill_buy_order(Decimal::from(3)).unwrap();
+
+        assert_that(&one).is_equal_to(Decimal::from(100));
+        assert_that(&two).is_equal_to(Decimal::from(100));
+        assert_that(&three).is_equal_to(Decimal::from(301) / Decimal::from(3));
+        assert_that(&book.price_to_fill_buy_order(Decimal::from(4))).is_err();
+    }
+
+    #[test]
+    fn price_to_fill_order() {
+        let book = OrderBook {
+            buys: vec![order(Position::Buy, 1490, 1), order(Position::Buy, 1400, 1)],
+            sells: vec![
+                order(Position::Sell, 1500, 1),
+                order(Position::Sell, 1590, 1),
+            ],
+        };
+
+        let buy = book.price_to_fill_buy_order(Decimal::from(2)).unwrap();
+        assert_that(&buy).is_equal_to(Decimal::from(1545));
+
+        let sell = book.price_to_fill_sell_order(Decimal::from(2)).unwrap();
+        assert_that(&sell).is_equal_to(Decimal::from(1445));
+    }
+
+    #[test]
+    fn price_to_fill_volume_exceeding_depth_fails() {
+        let book = book();
+
+        let err = book.price_to_fill_sell_order(Decimal::from(2)).unwrap_err();
+        assert_that(&err.to_string())
+            .is_equal_to("failed to fill sell order of 2, only 1 available".to_string());
+    }
+
+    #[test]
+    fn spread_to_fill_oversized_order_reports_available_volume() {
+        let book = book();
+
+        let err = book.spread_to_fill(Decimal::from(5)).unwrap_err();
+        let got = *err.downcast_ref::<FillError>().unwrap();
+
+        assert_that(&got).is_equal_to(FillError {
+            position: Position::Buy,
+            requested: Decimal::from(5),
+            available: Decimal::from(3),
+        });
+    }
+
+    #[test]
+    fn price_to_fill_with_fee_is_worse_than_raw_price() {
+        let book = book();
+        let fee = Decimal::new(5, 3); // 0.5%
+
+        let raw = book.price_to_fill_buy_order(Decimal::from(2)).unwrap();
+        let with_fee = book
+            .price_to_fill_with_fee(Decimal::from(2), Position::Buy, fee)
+            .unwrap();
+        assert_that(&raw).is_equal_to(Decimal::from(100));
+        assert_that(&with_fee).is_equal_to(Decimal::new(1005, 1));
+
+        let raw = book.price_to_fill_sell_order(Decimal::from(1)).unwrap();
+        let with_fee = book
+            .price_to_fill_with_fee(Decimal::from(1), Position::Sell, fee)
+            .unwrap();
+        assert_that(&raw).is_equal_to(Decimal::from(99));
+        assert_that(&with_fee).is_equal_to(Decimal::new(98505, 3));
+
+        let zero = book.price_to_fill_with_fee(Decimal::from(1), Position::Sell, Decimal::zero());
+        assert_that(&zero.unwrap()).is_equal_to(raw);
+        assert_that(&book.price_to_fill_with_fee(Decimal::from(2), Position::Sell, fee)).is_err();
+    }
+
+    #[test]
+    fn price_to_fill_partial_full_fill() {
+        let book = book();
+
+        let got = book.price_to_fill_partial(Decimal::from(3), Position::Buy);
+        assert_that(&got).is_equal_to(Some((
+            Decimal::from(3),
+            Decimal::from(301) / Decimal::from(3),
+        )));
+
+        let got = book.price_to_fill_partial(Decimal::from(1), Position::Buy);
+        assert_that(&got).is_equal_to(Some((Decimal::from(1), Decimal::from(100))));
+    }
+
+    #[test]
+    fn price_to_fill_partial_thin_book() {
+        let book = book();
+
+        let got = book.price_to_fill_partial(Decimal::from(5), Position::Buy);
+        assert_that(&got).is_equal_to(Some((
+            Decimal::from(3),
+            Decimal::from(301) / Decimal::from(3),
+        )));
+
+        let got = book.price_to_fill_partial(Decimal::from(2), Position::Sell);
+        assert_that(&got).is_equal_to(Some((Decimal::from(1), Decimal::from(99))));
+
+        let empty = OrderBook::from_levels(vec![], vec![]);
+        assert_that(&empty.price_to_fill_partial(Decimal::from(1), Position::Sell)).is_none();
+    }
+
+    fn deep_book() -> OrderBook {
+        OrderBook {
+            buys: vec![
+                order(Position::Buy, 100, 1),
+                order(Position::Buy, 99, 2),
+                order(Position::Buy, 95, 4),
+            ],
+            sells: vec![
+                order(Position::Sell, 101, 1),
+                order(Position::Sell, 102, 2),
+                order(Position::Sell, 110, 4),
+            ],
+        }
+    }
+
+    #[test]
+    fn cumulative_volume_to_price() {
+        let book = deep_book();
+
+        let buy = |p| book.cumulative_volume_to_price(Position::Buy, Decimal::from(p));
+        assert_that(&buy(100)).is_equal_to(Decimal::zero());
+        assert_that(&buy(101)).is_equal_to(Decimal::from(1));
+        assert_that(&buy(105)).is_equal_to(Decimal::from(3));
+        assert_that(&buy(200)).is_equal_to(Decimal::from(7));
+
+        let sell = |p| book.cumulative_volume_to_price(Position::Sell, Decimal::from(p));
+        assert_that(&sell(101)).is_equal_to(Decimal::zero());
+        assert_that(&sell(99)).is_equal_to(Decimal::from(3));
+        assert_that(&sell(1)).is_equal_to(Decimal::from(7));
+    }
+
+    #[test]
+    fn volume_within_percent_of_best_price() {
+        let book = deep_book();
+        let pct = |s| Decimal::from_str(s).unwrap();
+
+        assert_that(&book.volume_within_percent(Position::Buy, Decimal::zero()))
+            .is_equal_to(Decimal::from(1));
+        // 101 * 1.01 = 102.01
+        assert_that(&book.volume_within_percent(Position::Buy, pct("0.01")))
+            .is_equal_to(Decimal::from(3));
+        // 100 * 0.99 = 99
+        assert_that(&book.volume_within_percent(Position::Sell, pct("0.01")))
+            .is_equal_to(Decimal::from(3));
+        assert_that(&book.volume_within_percent(Position::Sell, pct("0.05")))
+            .is_equal_to(Decimal::from(7));
+
+        let empty = OrderBook {
+            buys: vec![],
+            sells: vec![],
+        };
+        assert_that(&empty.volume_within_percent(Position::Buy, pct("0.5")))
+            .is_equal_to(Decimal::zero());
+    }
+
+    #[test]
+    fn slippage_is_zero_when_filled_at_top_level() {
+        let book = deep_book();
+
+        let buy = book.slippage(Decimal::from(1), Position::Buy).unwrap();
+        let sell = book.slippage(Decimal::new(5, 1), Position::Sell).unwrap();
+
+        assert_that(&buy).is_equal_to(Decimal::zero());
+        assert_that(&sell).is_equal_to(Decimal::zero());
+    }
+
+    #[test]
+    fn slippage_sweeping_multiple_levels() {
+        let book = deep_book();
+
+        // (101 + 2 * 102) / 3 = 101.666.., ~0.66% above the best offer.
+        let buy = book.slippage(Decimal::from(3), Position::Buy).unwrap();
+        assert_that(&buy.round_dp(4)).is_equal_to(Decimal::new(66, 4));
+
+        // (100 + 2 * 99) / 3 = 99.333.., ~0.67% below the best bid.
+        let sell = book.slippage(Decimal::from(3), Position::Sell).unwrap();
+        assert_that(&sell.round_dp(4)).is_equal_to(Decimal::new(67, 4));
+
+        assert_that(&book.slippage(Decimal::from(8), Position::Buy)).is_err();
+    }
+
+    #[test]
+    fn top_of_book() {
+        let book = deep_book();
+
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from(100)));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from(101)));
+        assert_that(&book.mid_price()).is_equal_to(Some(Decimal::new(1005, 1)));
+        assert_that(&book.spread()).is_equal_to(Some(Decimal::from(1)));
+    }
+
+    #[test]
+    fn top_of_book_with_empty_sell_side() {
+        let book = OrderBook {
+            buys: vec![order(Position::Buy, 100, 1)],
+            sells: vec![],
+        };
+
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::from(100)));
+        assert_that(&book.best_ask()).is_none();
+        assert_that(&book.mid_price()).is_none();
+        assert_that(&book.spread()).is_none();
+    }
+
+    #[test]
+    fn conversion_counts_dropped_orders() {
+        let json = r#"{
+            "BuyOrders": [
+                {"OrderType": "LimitBid", "Price": 100, "Volume": 1},
+                {"OrderType": "LimitBid", "Price": null, "Volume": 1},
+                {"OrderType": "LimitOffer", "Price": 99, "Volume": 1}
+            ],
+            "SellOrders": [
+                {"OrderType": "LimitOffer", "Price": 101, "Volume": null},
+                {"OrderType": "LimitOffer", "Price": 102, "Volume": 2}
+            ],
+            "CreatedTimestampUtc": "2020-01-01T00:00:00Z",
+            "PrimaryCurrencyCode": "Xbt",
+            "SecondaryCurrencyCode": "Aud"
+        }"#;
+        let api: api::OrderBook = serde_json::from_str(json).unwrap();
+
+        let (book, stats) = OrderBook::from_api(api);
+
+        assert_that(&stats.dropped).is_equal_to(3);
+        assert_that(&stats.is_incomplete()).is_true();
+        assert_that(&book.buys).has_length(1);
+        assert_that(&book.sells).has_length(1);
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from(102)));
+    }
+
+    #[test]
+    fn top_n_keeps_best_orders_on_each_side() {
+        let json = r#"{
+            "BuyOrders": [
+                {"OrderType": "LimitBid", "Price": 97, "Volume": 1},
+                {"OrderType": "LimitBid", "Price": 99, "Volume": 1},
+                {"OrderType": "LimitBid", "Price": 95, "Volume": 1},
+                {"OrderType": "LimitBid", "Price": 100, "Volume": 1}
+            ],
+            "SellOrders": [
+                {"OrderType": "LimitOffer", "Price": 103, "Volume": 1},
+                {"OrderType": "LimitOffer", "Price": 101, "Volume": 1},
+                {"OrderType": "LimitOffer", "Price": 104, "Volume": 1},
+                {"OrderType": "LimitOffer", "Price": 102, "Volume": 1}
+            ],
+            "CreatedTimestampUtc": "2020-01-01T00:00:00Z",
+            "PrimaryCurrencyCode": "Xbt",
+            "SecondaryCurrencyCode": "Aud"
+        }"#;
+        let api: api::OrderBook = serde_json::from_str(json).unwrap();
+
+        let book = OrderBook::from_top_n(api, 2);
+
+        let prices = |orders: &[Order]| orders.iter().map(|o| o.price).collect::<Vec<_>>();
+        assert_that(&prices(&book.buys)).is_equal_to(vec![Decimal::from(100), Decimal::from(99)]);
+        assert_that(&prices(&book.sells)).is_equal_to(vec![Decimal::from(101), Decimal::from(102)]);
+    }
+}
@@ -0,0 +1,335 @@
+//! Streams the order book from the Independent Reserve WebSocket feed.
+//!
+//! The feed publishes individual order events (new, changed, and cancelled
+//! orders) for a pair. We seed a local book from a REST snapshot (GetAllOrders)
+//! and apply each event to it, yielding the updated book. Events carry a
+//! sequential nonce, if we see a gap we have missed an event so we reconnect
+//! and take a fresh snapshot.
+//!
+//! The WebSocket connection does not go through a configured proxy, only the
+//! REST snapshot does.
+
+use super::{
+    api::{AllOrders, OrderType, Public},
+    OrderBook, Pair,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{channel::mpsc, SinkExt, Stream, StreamExt};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, warn};
+use url::Url;
+
+/// WebSocket feed URL.
+const WS_URL: &str = "wss://websockets.independentreserve.com";
+
+/// Delay before reconnecting after the feed disconnects.
+const RECONNECT_DELAY_SECS: u64 = 5;
+
+/// Number of order books buffered for a slow consumer.
+const BUFFERED_BOOKS: usize = 16;
+
+/// Stream the order book for `pair`, yielding the full book after each update.
+/// Reconnects if the feed disconnects, yielding the error that caused the
+/// disconnect. The stream ends only if dropped.
+pub fn order_book_stream(pair: Pair) -> impl Stream<Item = Result<OrderBook>> {
+    let (tx, rx) = mpsc::channel(BUFFERED_BOOKS);
+    tokio::spawn(run(pair, Public::default(), tx));
+    rx
+}
+
+async fn run(pair: Pair, public: Public, mut tx: mpsc::Sender<Result<OrderBook>>) {
+    loop {
+        match stream_books(&pair, &public, &mut tx).await {
+            Ok(()) => return, // Receiver dropped.
+            Err(e) => {
+                warn!(
+                    "order book stream disconnected, reconnecting in {}s: {:#}",
+                    RECONNECT_DELAY_SECS, e
+                );
+                if tx.send(Err(e)).await.is_err() {
+                    return;
+                }
+            }
+        }
+        tokio::time::delay_for(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+    }
+}
+
+// Connect to the feed and send books to `tx` until the feed errors. Returns
+// `Ok` if the receiver is dropped.
+async fn stream_books(
+    pair: &Pair,
+    public: &Public,
+    tx: &mut mpsc::Sender<Result<OrderBook>>,
+) -> Result<()> {
+    let url = Url::parse_with_params(WS_URL, &[("subscribe", channel(pair))])?;
+    let (mut ws, _) = connect_async(url)
+        .await
+        .context("failed to connect to order book feed")?;
+
+    // Subscribe before taking the snapshot so we don't miss events, events
+    // already reflected in the snapshot are harmless to apply again.
+    let snapshot = public.get_all_orders(&pair.base, &pair.quote).await?;
+    let mut book = LocalBook::from_snapshot(&snapshot, &pair.quote);
+    if tx.send(Ok(book.to_order_book())).await.is_err() {
+        return Ok(());
+    }
+
+    while let Some(msg) = ws.next().await {
+        let text = match msg.context("failed to read from order book feed")? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let frame: Frame = serde_json::from_str(&text)
+            .with_context(|| format!("invalid order book feed frame: {}", text))?;
+
+        if book.apply(frame)? && tx.send(Ok(book.to_order_book())).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    bail!("order book feed closed")
+}
+
+// Feed channel name e.g., "orderbook-xbt-aud".
+fn channel(pair: &Pair) -> String {
+    format!(
+        "orderbook-{}-{}",
+        pair.base.to_lowercase(),
+        pair.quote.to_lowercase()
+    )
+}
+
+/// A message received on the feed.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "Event")]
+enum Frame {
+    NewOrder {
+        #[serde(rename = "Nonce")]
+        nonce: u64,
+        #[serde(rename = "Data")]
+        data: OrderEvent,
+    },
+    OrderChanged {
+        #[serde(rename = "Nonce")]
+        nonce: u64,
+        #[serde(rename = "Data")]
+        data: OrderEvent,
+    },
+    OrderCanceled {
+        #[serde(rename = "Nonce")]
+        nonce: u64,
+        #[serde(rename = "Data")]
+        data: OrderEvent,
+    },
+    /// Heartbeat and subscription confirmations.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OrderEvent {
+    order_type: OrderType,
+    order_guid: String,
+    /// Price in each of the supported fiat currencies, keyed by lowercase
+    /// currency code.
+    price: Option<HashMap<String, Decimal>>,
+    /// Remaining volume.
+    volume: Option<Decimal>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Level {
+    price: Decimal,
+    volume: Decimal,
+}
+
+/// Order book maintained from feed events, orders keyed by guid.
+#[derive(Debug)]
+struct LocalBook {
+    /// Lowercase quote currency, used to select the event price.
+    quote: String,
+    bids: HashMap<String, Level>,
+    asks: HashMap<String, Level>,
+    /// Nonce of the last event applied.
+    nonce: Option<u64>,
+}
+
+impl LocalBook {
+    fn from_snapshot(snapshot: &AllOrders, quote: &str) -> Self {
+        fn levels(orders: &[super::api::OrderGuid]) -> HashMap<String, Level> {
+            orders
+                .iter()
+                .filter_map(|o| {
+                    let level = Level {
+                        price: o.price?,
+                        volume: o.volume?,
+                    };
+                    Some((o.guid.clone(), level))
+                })
+                .collect()
+        }
+
+        LocalBook {
+            quote: quote.to_lowercase(),
+            bids: levels(&snapshot.buy_orders),
+            asks: levels(&snapshot.sell_orders),
+            nonce: None,
+        }
+    }
+
+    /// Apply `frame` to the book, returns true if the book changed.
+    fn apply(&mut self, frame: Frame) -> Result<bool> {
+        let (nonce, data) = match &frame {
+            Frame::NewOrder { nonce, data }
+            | Frame::OrderChanged { nonce, data }
+            | Frame::OrderCanceled { nonce, data } => (*nonce, data),
+            Frame::Other => return Ok(false),
+        };
+
+        if let Some(last) = self.nonce {
+            if nonce != last + 1 {
+                bail!(
+                    "missed order book update, expected nonce {} got {}",
+                    last + 1,
+                    nonce
+                );
+            }
+        }
+        self.nonce = Some(nonce);
+
+        let quote = self.quote.clone();
+        let side = match data.order_type {
+            OrderType::Buy => &mut self.bids,
+            OrderType::Sell => &mut self.asks,
+        };
+        let guid = data.order_guid.clone();
+
+        let changed = match frame {
+            Frame::NewOrder { data, .. } => {
+                let price = data
+                    .price
+                    .as_ref()
+                    .and_then(|prices| prices.get(&quote))
+                    .copied()
+                    .ok_or_else(|| anyhow!("new order {} has no {} price", guid, quote))?;
+                let volume = data
+                    .volume
+                    .ok_or_else(|| anyhow!("new order {} has no volume", guid))?;
+
+                side.insert(guid, Level { price, volume });
+                true
+            }
+            Frame::OrderChanged { data, .. } => {
+                let volume = data
+                    .volume
+                    .ok_or_else(|| anyhow!("changed order {} has no volume", guid))?;
+
+                if volume.is_zero() {
+                    side.remove(&guid).is_some()
+                } else {
+                    match side.get_mut(&guid) {
+                        Some(level) => {
+                            level.volume = volume;
+                            true
+                        }
+                        None => {
+                            debug!("ignoring change to unknown order {}", guid);
+                            false
+                        }
+                    }
+                }
+            }
+            Frame::OrderCanceled { .. } => side.remove(&guid).is_some(),
+            Frame::Other => false,
+        };
+
+        Ok(changed)
+    }
+
+    fn to_order_book(&self) -> OrderBook {
+        let levels = |side: &HashMap<String, Level>| {
+            side.values()
+                .map(|l| (l.price, l.volume))
+                .collect::<Vec<_>>()
+        };
+        OrderBook::from_levels(levels(&self.bids), levels(&self.asks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    const SNAPSHOT: &str = r#"{
+        "BuyOrders": [
+            {"Guid": "b1", "Price": 100, "Volume": 1},
+            {"Guid": "b2", "Price": 99, "Volume": null}
+        ],
+        "SellOrders": [
+            {"Guid": "s1", "Price": 102, "Volume": 1}
+        ],
+        "CreatedTimestampUtc": "2020-01-01T00:00:00Z",
+        "PrimaryCurrencyCode": "Xbt",
+        "SecondaryCurrencyCode": "Aud"
+    }"#;
+
+    fn book() -> LocalBook {
+        let snapshot: AllOrders = serde_json::from_str(SNAPSHOT).unwrap();
+        LocalBook::from_snapshot(&snapshot, "Aud")
+    }
+
+    fn frames() -> Vec<Frame> {
+        include_str!("../../testdata/orderbook-stream.jsonl")
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn applies_recorded_update_frames() {
+        let mut book = book();
+
+        let changed: Vec<bool> = frames()
+            .into_iter()
+            .map(|frame| book.apply(frame).unwrap())
+            .collect();
+        // Subscriptions and heartbeat frames don't change the book.
+        assert_that(&changed).is_equal_to(vec![
+            false, true, true, false, true, true, true, true, false,
+        ]);
+
+        let book = book.to_order_book();
+        assert_that(&book.best_bid()).is_equal_to(Some(Decimal::new(1005, 1)));
+        assert_that(&book.best_ask()).is_equal_to(Some(Decimal::from(103)));
+        assert_that(&book.buys).has_length(2);
+        assert_that(&book.sells).has_length(1);
+        assert_that(
+            &book.cumulative_volume_to_price(crate::market::Position::Sell, Decimal::from(100)),
+        )
+        .is_equal_to(Decimal::new(25, 1));
+    }
+
+    #[test]
+    fn nonce_gap_is_an_error() {
+        let mut book = book();
+        let mut frames = frames().into_iter().filter(|f| !matches!(f, Frame::Other));
+
+        book.apply(frames.next().unwrap()).unwrap();
+        let _skipped = frames.next();
+
+        assert_that(&book.apply(frames.next().unwrap())).is_err();
+    }
+
+    #[test]
+    fn channel_name() {
+        let pair = Pair::new("Xbt", "Aud");
+        assert_that(&channel(&pair)).is_equal_to("orderbook-xbt-aud".to_string());
+    }
+}
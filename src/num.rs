@@ -1,5 +1,12 @@
-//! Utility functions for working with `Decimal`.
+//! Numeric types and utility functions for working with `Decimal`.
+//!
+//! `Price` and `Volume` are the primary types for displaying order book
+//! values, the free functions are kept for working with raw `Decimal`s.
 use rust_decimal::Decimal;
+use std::{
+    fmt,
+    ops::{AddAssign, Div, Mul},
+};
 
 /// Decimal places to use for displaying AUD.
 const AUD_DP: u32 = 2;
@@ -10,16 +17,104 @@ const BTC_DP: u32 = 8;
 /// Decimal places to use for displaying a percent.
 const PERCENT_DP: u32 = 4;
 
+/// Decimal places to use for displaying basis points.
+const BPS_DP: u32 = 2;
+
+/// Number of basis points in 1 i.e., 100%.
+const BPS_PER_UNIT: u32 = 10_000;
+
+/// A price in the quote currency (AUD), displayed to 2 decimal places.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Price(pub Decimal);
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.round_dp(AUD_DP), f)
+    }
+}
+
+/// A volume of the base currency (BTC), displayed to 8 decimal places.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Volume(pub Decimal);
+
+impl fmt::Display for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.round_dp(BTC_DP), f)
+    }
+}
+
+/// A value in the quote currency (AUD) e.g., the cost of filling an order,
+/// displayed to 2 decimal places.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Notional(pub Decimal);
+
+impl fmt::Display for Notional {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.round_dp(AUD_DP), f)
+    }
+}
+
+impl Mul<Volume> for Price {
+    type Output = Notional;
+
+    fn mul(self, volume: Volume) -> Notional {
+        Notional(self.0 * volume.0)
+    }
+}
+
+impl AddAssign for Notional {
+    fn add_assign(&mut self, other: Notional) {
+        self.0 += other.0;
+    }
+}
+
+/// Average price paid per unit of volume.
+impl Div<Volume> for Notional {
+    type Output = Price;
+
+    fn div(self, volume: Volume) -> Price {
+        Price(self.0 / volume.0)
+    }
+}
+
+/// A percentage stored as a fraction e.g., `Percent(0.002)` is 0.2%.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Percent(pub Decimal);
+
+impl Percent {
+    /// This percentage in basis points (1 bp = 0.01%).
+    pub fn to_bps(&self) -> Decimal {
+        self.0 * Decimal::from(BPS_PER_UNIT)
+    }
+
+    /// Format as basis points e.g., "20 bps".
+    pub fn to_bps_string(&self) -> String {
+        format!("{} bps", self.to_bps().round_dp(BPS_DP).normalize())
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", to_percent_string(&self.0))
+    }
+}
+
 pub fn to_percent_string(x: &Decimal) -> String {
     format!("{}", x.round_dp(PERCENT_DP))
 }
 
 pub fn to_aud_string(x: &Decimal) -> String {
-    format!("{}", x.round_dp(AUD_DP))
+    Price(*x).to_string()
 }
 
 pub fn to_btc_string(x: &Decimal) -> String {
-    format!("{}", x.round_dp(BTC_DP))
+    Volume(*x).to_string()
+}
+
+/// Convert `amount` into another currency at `rate` (units of the other
+/// currency per unit of `amount`'s currency).
+pub fn convert(amount: Decimal, rate: Decimal) -> Decimal {
+    amount * rate
 }
 
 pub fn mid_market_price(bid: &Decimal, ask: &Decimal) -> Decimal {
@@ -36,3 +131,61 @@ pub fn spread_percent(buy: &Decimal, sell: &Decimal) -> (Decimal, Decimal) {
 
     (spread, percent)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn percent_to_bps() {
+        let percent = Percent(Decimal::from_str("0.002").unwrap());
+
+        assert_that(&percent.to_bps()).is_equal_to(Decimal::from(20));
+        assert_that(&percent.to_bps_string()).is_equal_to("20 bps".to_string());
+    }
+
+    #[test]
+    fn convert_at_rate() {
+        let usd = Decimal::from(100);
+        let usd_aud = Decimal::from_str("1.45").unwrap();
+
+        assert_that(&convert(usd, usd_aud)).is_equal_to(Decimal::from(145));
+    }
+
+    #[test]
+    fn price_and_volume_format_like_free_functions() {
+        let x = Decimal::from_str("12345.678912346").unwrap();
+
+        assert_that(&Price(x).to_string()).is_equal_to("12345.68".to_string());
+        assert_that(&Price(x).to_string()).is_equal_to(to_aud_string(&x));
+        assert_that(&Volume(x).to_string()).is_equal_to("12345.67891235".to_string());
+        assert_that(&Volume(x).to_string()).is_equal_to(to_btc_string(&x));
+    }
+
+    #[test]
+    fn price_and_volume_honour_width() {
+        let price = Price(Decimal::from_str("497.021").unwrap());
+        let volume = Volume(Decimal::new(1, 2));
+
+        assert_that(&format!("{:>8}|{:>6}", price, volume))
+            .is_equal_to("  497.02|  0.01".to_string());
+    }
+
+    #[test]
+    fn percent_formats_to_four_places() {
+        let x = Decimal::from_str("0.123456").unwrap();
+
+        assert_that(&Percent(x).to_string()).is_equal_to("0.1235".to_string());
+        assert_that(&Percent(x).to_string()).is_equal_to(to_percent_string(&x));
+    }
+
+    #[test]
+    fn price_times_volume_is_notional() {
+        let got = Price(Decimal::from(100)) * Volume(Decimal::from_str("0.5").unwrap());
+
+        assert_that(&got).is_equal_to(Notional(Decimal::from(50)));
+        assert_that(&(got / Volume(Decimal::from(2)))).is_equal_to(Price(Decimal::from(25)));
+    }
+}
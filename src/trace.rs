@@ -1,11 +1,36 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use atty::{self, Stream};
 use log::LevelFilter;
-use tracing::{info, subscriber};
+use std::{env, io, str::FromStr};
+use tracing::{dispatcher, info, Dispatch};
 use tracing_log::LogTracer;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::{fmt::MakeWriter, EnvFilter, FmtSubscriber};
 
-pub fn init_tracing(level: LevelFilter) -> Result<()> {
+/// Format of the trace output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    /// Human readable lines, the default.
+    Pretty,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pretty" => Ok(TraceFormat::Pretty),
+            "json" => Ok(TraceFormat::Json),
+            _ => bail!("unknown log format: {} (expected pretty or json)", s),
+        }
+    }
+}
+
+/// Initialize tracing, if `to_stderr` is set trace output is written to stderr
+/// instead of stdout. Directives in `RUST_LOG` override `level` e.g.,
+/// `RUST_LOG=crypto_trader::market=trace`.
+pub fn init_tracing(level: LevelFilter, to_stderr: bool, format: TraceFormat) -> Result<()> {
     if level == LevelFilter::Off {
         return Ok(());
     }
@@ -13,14 +38,140 @@ pub fn init_tracing(level: LevelFilter) -> Result<()> {
     // We want upstream library log messages, just only at Info level.
     LogTracer::init_with_filter(LevelFilter::Info)?;
 
-    let is_terminal = atty::is(Stream::Stdout);
-    let subscriber = FmtSubscriber::builder()
-        .with_env_filter(format!("crypto_trader={},http=info,warp=info", level,))
-        .with_ansi(is_terminal)
-        .finish();
+    let rust_log = env::var("RUST_LOG").ok();
+    let filter = env_filter(level, rust_log.as_deref())?;
 
-    subscriber::set_global_default(subscriber)?;
+    let dispatch = if to_stderr {
+        dispatch(format, filter, atty::is(Stream::Stderr), io::stderr)
+    } else {
+        dispatch(format, filter, atty::is(Stream::Stdout), io::stdout)
+    };
+    dispatcher::set_global_default(dispatch)?;
     info!("Initialized tracing with level: {}", level);
 
     Ok(())
 }
+
+// Build the subscriber writing `format` output to `writer`.
+fn dispatch<W>(format: TraceFormat, filter: EnvFilter, ansi: bool, writer: W) -> Dispatch
+where
+    W: MakeWriter + Send + Sync + 'static,
+{
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        TraceFormat::Pretty => Dispatch::new(builder.with_ansi(ansi).finish()),
+        TraceFormat::Json => Dispatch::new(builder.json().finish()),
+    }
+}
+
+// Filter crate output at `level`, then apply the comma separated directives
+// in `rust_log`. A directive for the same target replaces the default one.
+fn env_filter(level: LevelFilter, rust_log: Option<&str>) -> Result<EnvFilter> {
+    let mut filter = EnvFilter::new(format!("crypto_trader={}", level));
+    for directive in rust_log.unwrap_or_default().split(',') {
+        let directive = directive.trim();
+        if directive.is_empty() {
+            continue;
+        }
+        let directive = directive
+            .parse()
+            .with_context(|| format!("invalid RUST_LOG directive: {}", directive))?;
+        filter = filter.add_directive(directive);
+    }
+    Ok(filter)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::{debug, trace};
+
+    /// Collects formatted trace output, use as a subscriber's writer.
+    #[derive(Clone, Default)]
+    pub(crate) struct TraceBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl TraceBuf {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl io::Write for TraceBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn output_with(filter: EnvFilter) -> String {
+        output_as(TraceFormat::Pretty, filter)
+    }
+
+    fn output_as(format: TraceFormat, filter: EnvFilter) -> String {
+        let buf = TraceBuf::default();
+        let writer = buf.clone();
+        let dispatch = dispatch(format, filter, false, move || writer.clone());
+
+        dispatcher::with_default(&dispatch, || {
+            trace!(target: "crypto_trader::market", "market trace");
+            debug!(target: "crypto_trader::bot", "bot debug");
+            info!(target: "crypto_trader::bot", "bot info");
+        });
+        buf.contents()
+    }
+
+    #[test]
+    fn level_applies_without_rust_log() {
+        let out = output_with(env_filter(LevelFilter::Info, None).unwrap());
+
+        assert_that(&out.contains("bot info")).is_true();
+        assert_that(&out.contains("bot debug")).is_false();
+        assert_that(&out.contains("market trace")).is_false();
+    }
+
+    #[test]
+    fn rust_log_takes_effect() {
+        let rust_log = "crypto_trader::market=trace";
+        let out = output_with(env_filter(LevelFilter::Info, Some(rust_log)).unwrap());
+
+        assert_that(&out.contains("market trace")).is_true();
+        assert_that(&out.contains("bot debug")).is_false();
+
+        let out = output_with(env_filter(LevelFilter::Info, Some("crypto_trader=debug")).unwrap());
+        assert_that(&out.contains("bot debug")).is_true();
+    }
+
+    #[test]
+    fn invalid_rust_log_is_an_error() {
+        assert_that(&env_filter(LevelFilter::Info, Some("crypto_trader=loud")).is_err()).is_true();
+    }
+
+    #[test]
+    fn json_format_emits_json_lines() {
+        let out = output_as(
+            TraceFormat::Json,
+            env_filter(LevelFilter::Info, None).unwrap(),
+        );
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_that(&lines.len()).is_equal_to(1);
+        let got: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_that(&got["fields"]["message"].as_str()).is_equal_to(Some("bot info"));
+        assert_that(&got["level"].as_str()).is_equal_to(Some("INFO"));
+    }
+
+    #[test]
+    fn parse_log_format() {
+        assert_that(&"pretty".parse::<TraceFormat>().unwrap()).is_equal_to(TraceFormat::Pretty);
+        assert_that(&"json".parse::<TraceFormat>().unwrap()).is_equal_to(TraceFormat::Json);
+        assert_that(&"xml".parse::<TraceFormat>().is_err()).is_true();
+    }
+}